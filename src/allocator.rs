@@ -0,0 +1,580 @@
+// src/allocator.rs
+/*
+ * Budget-constrained allocation of a program pool across scored applications
+ */
+
+use crate::{GrantError, Result};
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::fmt;
+
+/// Upper bound on the knapsack table size (applications x capacity units)
+const KNAPSACK_MAX_CELLS: f64 = 20_000_000.0;
+
+/// Allowed rounding slack when comparing money amounts
+const EPSILON: f64 = 0.005;
+
+/// How the pool is divided between applications
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum Strategy {
+    /// Fund full requests in descending score order while money lasts
+    Greedy,
+    /// Split the pool in proportion to score, capped at each request
+    Proportional,
+    /// Fund the set of full requests with the highest total score
+    Knapsack,
+    /// Give every application the program minimum, then split the rest by score
+    FloorProportional,
+}
+
+impl fmt::Display for Strategy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Strategy::Greedy => "greedy",
+            Strategy::Proportional => "proportional",
+            Strategy::Knapsack => "knapsack",
+            Strategy::FloorProportional => "floor-proportional",
+        };
+        f.write_str(name)
+    }
+}
+
+/// An eligible application competing for money
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ScoredApplication {
+    /// Application identifier
+    pub application_id: String,
+    /// Amount the application asked for
+    pub requested: f64,
+    /// Ranking score; higher is better
+    pub score: f64,
+}
+
+/// Money granted to a single application
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Award {
+    /// Application identifier
+    pub application_id: String,
+    /// Amount the application asked for
+    pub requested: f64,
+    /// Amount granted; zero when unfunded
+    pub awarded: f64,
+    /// Score the decision was based on
+    pub score: f64,
+    /// Why the application received this amount
+    pub rationale: String,
+}
+
+/// Outcome of allocating a pool
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Allocation {
+    /// Strategy that produced the awards
+    pub strategy: Strategy,
+    /// Money available before allocation
+    pub pool: f64,
+    /// Sum of all awards
+    pub total_awarded: f64,
+    /// Money left unallocated
+    pub remainder: f64,
+    /// One entry per application, in ranking order
+    pub awards: Vec<Award>,
+}
+
+/// Divide a pool between scored applications
+///
+/// # Arguments
+///
+/// * `pool` - Money available
+/// * `applications` - Eligible applications with their scores
+/// * `strategy` - Allocation strategy to use
+/// * `floor` - Minimum award used by `Strategy::FloorProportional`
+///
+/// # Returns
+///
+/// An `Allocation` listing every application with its award and rationale,
+/// or `GrantError::Allocation` when the pool or an application is unusable
+pub fn allocate(
+    pool: f64,
+    applications: &[ScoredApplication],
+    strategy: Strategy,
+    floor: f64,
+) -> Result<Allocation> {
+    if !pool.is_finite() || pool < 0.0 {
+        return Err(GrantError::allocation(format!(
+            "pool must be a non-negative amount, got {}",
+            pool
+        )));
+    }
+    if !floor.is_finite() {
+        return Err(GrantError::allocation(format!(
+            "floor must be a finite amount, got {}",
+            floor
+        )));
+    }
+    if let Some(application) = applications.iter().find(|application| {
+        !application.requested.is_finite()
+            || application.requested < 0.0
+            || application.score.is_nan()
+    }) {
+        return Err(GrantError::allocation(format!(
+            "application {} has unusable request {} or score {}",
+            application.application_id, application.requested, application.score
+        )));
+    }
+
+    let mut ranked: Vec<&ScoredApplication> = applications.iter().collect();
+    ranked.sort_by(|a, b| rank_order(a, b));
+
+    let amounts = match strategy {
+        Strategy::Greedy => greedy(pool, &ranked),
+        Strategy::Proportional => proportional(
+            pool,
+            &ranked,
+            vec![0.0; ranked.len()],
+            &vec![true; ranked.len()],
+        ),
+        Strategy::Knapsack => knapsack(pool, &ranked),
+        Strategy::FloorProportional => floor_proportional(pool, &ranked, floor),
+    };
+
+    let awards: Vec<Award> = ranked
+        .iter()
+        .zip(amounts)
+        .enumerate()
+        .map(|(rank, (application, amount))| {
+            let mut award = Award {
+                application_id: application.application_id.clone(),
+                requested: application.requested,
+                awarded: round_down_cents(amount),
+                score: application.score,
+                rationale: String::new(),
+            };
+            award.rationale = format!("rank {}: {}", rank + 1, rationale(strategy, &award));
+            award
+        })
+        .collect();
+
+    let total_awarded = round_down_cents(awards.iter().map(|a| a.awarded).sum());
+    Ok(Allocation {
+        strategy,
+        pool,
+        total_awarded,
+        remainder: round_down_cents(pool - total_awarded),
+        awards,
+    })
+}
+
+fn rank_order(a: &ScoredApplication, b: &ScoredApplication) -> Ordering {
+    b.score
+        .partial_cmp(&a.score)
+        .unwrap_or(Ordering::Equal)
+        .then_with(|| a.application_id.cmp(&b.application_id))
+}
+
+fn round_down_cents(amount: f64) -> f64 {
+    ((amount + 1e-9) * 100.0).floor() / 100.0
+}
+
+fn rationale(strategy: Strategy, award: &Award) -> String {
+    let fully = award.awarded + EPSILON >= award.requested;
+    match (strategy, award.awarded > 0.0, fully) {
+        (_, false, _) if award.score <= 0.0 => "not funded: score is zero".to_string(),
+        (Strategy::Knapsack, false, _) => {
+            "not funded: request is not part of the highest-scoring set that fits the pool"
+                .to_string()
+        }
+        (Strategy::Greedy, false, _) => format!(
+            "not funded: full request of {:.2} did not fit the remaining pool",
+            award.requested
+        ),
+        (_, false, _) => "not funded: pool exhausted".to_string(),
+        (Strategy::Greedy, true, _) => format!(
+            "funded in full with score {:.2}; request fit the remaining pool",
+            award.score
+        ),
+        (Strategy::Knapsack, true, _) => format!(
+            "funded in full as part of the highest-scoring set of requests that fits the pool (score {:.2})",
+            award.score
+        ),
+        (Strategy::Proportional, true, true) => format!(
+            "funded in full; score share of {:.2} covered the request",
+            award.score
+        ),
+        (Strategy::Proportional, true, false) => format!(
+            "partially funded with {:.2} of {:.2} in proportion to score {:.2}",
+            award.awarded, award.requested, award.score
+        ),
+        (Strategy::FloorProportional, true, true) => {
+            format!("funded in full from the floor plus score share (score {:.2})", award.score)
+        }
+        (Strategy::FloorProportional, true, false) => format!(
+            "received the floor plus a score share: {:.2} of {:.2} (score {:.2})",
+            award.awarded, award.requested, award.score
+        ),
+    }
+}
+
+fn greedy(pool: f64, ranked: &[&ScoredApplication]) -> Vec<f64> {
+    let mut remaining = pool;
+    ranked
+        .iter()
+        .map(|application| {
+            if application.score > 0.0 && application.requested <= remaining + EPSILON {
+                remaining -= application.requested;
+                application.requested
+            } else {
+                0.0
+            }
+        })
+        .collect()
+}
+
+/// Water-filling split: each round shares the remaining pool by score and
+/// retires applications whose share would exceed what they still need
+fn proportional(
+    pool: f64,
+    ranked: &[&ScoredApplication],
+    mut amounts: Vec<f64>,
+    participating: &[bool],
+) -> Vec<f64> {
+    let mut remaining = pool;
+    let mut active: Vec<usize> = (0..ranked.len())
+        .filter(|&i| {
+            participating[i] && ranked[i].score > 0.0 && ranked[i].requested - amounts[i] > EPSILON
+        })
+        .collect();
+
+    while !active.is_empty() && remaining > EPSILON {
+        let total_score: f64 = active.iter().map(|&i| ranked[i].score).sum();
+        let capped: Vec<usize> = active
+            .iter()
+            .copied()
+            .filter(|&i| {
+                remaining * ranked[i].score / total_score >= ranked[i].requested - amounts[i]
+            })
+            .collect();
+
+        if capped.is_empty() {
+            for &i in &active {
+                amounts[i] += remaining * ranked[i].score / total_score;
+            }
+            break;
+        }
+
+        for &i in &capped {
+            remaining -= ranked[i].requested - amounts[i];
+            amounts[i] = ranked[i].requested;
+        }
+        active.retain(|i| !capped.contains(i));
+    }
+
+    amounts
+}
+
+/// 0/1 knapsack over whole requests, maximising the total score funded
+fn knapsack(pool: f64, ranked: &[&ScoredApplication]) -> Vec<f64> {
+    let count = ranked.len();
+    if count == 0 {
+        return Vec::new();
+    }
+
+    // Work in whole cents, so requests with cents fit the pool exactly. Only
+    // when the table would grow too big are cents coarsened into larger units.
+    let cents = |amount: f64| (amount * 100.0).round().max(0.0);
+    let unit = (cents(pool) * count as f64 / KNAPSACK_MAX_CELLS)
+        .ceil()
+        .max(1.0);
+    let capacity = (cents(pool) / unit).floor() as usize;
+    // Rounding down can only overfill the pool when units are coarser than a
+    // cent; `fit_pool` repairs that below
+    let weights: Vec<usize> = ranked
+        .iter()
+        .map(|application| (cents(application.requested) / unit).floor() as usize)
+        .collect();
+
+    let mut best = vec![0.0_f64; capacity + 1];
+    let mut taken = vec![false; count * (capacity + 1)];
+    for (i, application) in ranked.iter().enumerate() {
+        if application.score <= 0.0 || weights[i] > capacity {
+            continue;
+        }
+        for c in (weights[i]..=capacity).rev() {
+            let candidate = best[c - weights[i]] + application.score;
+            if candidate > best[c] {
+                best[c] = candidate;
+                taken[i * (capacity + 1) + c] = true;
+            }
+        }
+    }
+
+    let mut amounts = vec![0.0; count];
+    let mut c = capacity;
+    for i in (0..count).rev() {
+        if taken[i * (capacity + 1) + c] {
+            amounts[i] = ranked[i].requested;
+            c -= weights[i];
+        }
+    }
+    fit_pool(pool, ranked, &mut amounts);
+    amounts
+}
+
+/// Drop the lowest-ranked funded requests until the awards fit the pool
+fn fit_pool(pool: f64, ranked: &[&ScoredApplication], amounts: &mut [f64]) {
+    let mut total: f64 = amounts.iter().sum();
+    for (i, _) in ranked.iter().enumerate().rev() {
+        if total <= pool + EPSILON {
+            break;
+        }
+        total -= amounts[i];
+        amounts[i] = 0.0;
+    }
+}
+
+fn floor_proportional(pool: f64, ranked: &[&ScoredApplication], floor: f64) -> Vec<f64> {
+    let mut remaining = pool;
+    let floors: Vec<f64> = ranked
+        .iter()
+        .map(|application| {
+            let base = floor.max(0.0).min(application.requested);
+            if application.score > 0.0 && base <= remaining + EPSILON {
+                remaining -= base;
+                base
+            } else {
+                0.0
+            }
+        })
+        .collect();
+
+    // Only applications that received their floor share in the rest
+    let participating: Vec<bool> = floors.iter().map(|&base| base > 0.0).collect();
+    proportional(remaining, ranked, floors, &participating)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scored(id: &str, requested: f64, score: f64) -> ScoredApplication {
+        ScoredApplication {
+            application_id: id.to_string(),
+            requested,
+            score,
+        }
+    }
+
+    fn awarded(allocation: &Allocation) -> Vec<(&str, f64)> {
+        allocation
+            .awards
+            .iter()
+            .map(|award| (award.application_id.as_str(), award.awarded))
+            .collect()
+    }
+
+    #[test]
+    fn greedy_funds_full_requests_in_score_order() {
+        let applications = [
+            scored("A", 60.0, 10.0),
+            scored("B", 50.0, 9.0),
+            scored("C", 30.0, 8.0),
+        ];
+        let allocation = allocate(100.0, &applications, Strategy::Greedy, 0.0).unwrap();
+        assert_eq!(awarded(&allocation), [("A", 60.0), ("B", 0.0), ("C", 30.0)]);
+        assert_eq!(allocation.total_awarded, 90.0);
+        assert_eq!(allocation.remainder, 10.0);
+        assert!(allocation.awards[1].rationale.contains("did not fit"));
+    }
+
+    #[test]
+    fn knapsack_beats_greedy_when_greedy_is_suboptimal() {
+        let applications = [
+            scored("A", 60.0, 10.0),
+            scored("B", 50.0, 9.0),
+            scored("C", 50.0, 9.0),
+        ];
+        let allocation = allocate(100.0, &applications, Strategy::Knapsack, 0.0).unwrap();
+        assert_eq!(awarded(&allocation), [("A", 0.0), ("B", 50.0), ("C", 50.0)]);
+    }
+
+    #[test]
+    fn knapsack_funds_requests_with_cents_that_fit_exactly() {
+        let applications = [scored("A", 100.50, 5.0)];
+        let allocation = allocate(100.50, &applications, Strategy::Knapsack, 0.0).unwrap();
+        assert_eq!(awarded(&allocation), [("A", 100.50)]);
+
+        let applications = [scored("A", 33.33, 5.0), scored("B", 66.67, 4.0)];
+        let allocation = allocate(100.0, &applications, Strategy::Knapsack, 0.0).unwrap();
+        assert_eq!(awarded(&allocation), [("A", 33.33), ("B", 66.67)]);
+        assert!(allocation.remainder.abs() < EPSILON);
+
+        let applications = [scored("A", 100.51, 5.0)];
+        let allocation = allocate(100.50, &applications, Strategy::Knapsack, 0.0).unwrap();
+        assert_eq!(awarded(&allocation), [("A", 0.0)]);
+    }
+
+    #[test]
+    fn coarse_knapsack_units_never_overfill_the_pool() {
+        // Large enough that cents are coarsened into bigger units
+        let applications: Vec<ScoredApplication> = (0..40)
+            .map(|i| {
+                scored(
+                    &format!("APP-{:02}", i),
+                    2_500_000.99 + i as f64 * 0.37,
+                    1.0,
+                )
+            })
+            .collect();
+        let pool = 10_000_003.0;
+        let allocation = allocate(pool, &applications, Strategy::Knapsack, 0.0).unwrap();
+        assert!(allocation.total_awarded <= pool + EPSILON);
+        let funded = allocation
+            .awards
+            .iter()
+            .filter(|award| award.awarded > 0.0)
+            .count();
+        assert_eq!(funded, 3);
+    }
+
+    #[test]
+    fn knapsack_matches_brute_force() {
+        // Deterministic pseudo-random instances
+        let mut seed: u64 = 0x2545_f491_4f6c_dd1d;
+        let mut next = |modulus: u64| {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            seed % modulus
+        };
+        for _ in 0..50 {
+            let applications: Vec<ScoredApplication> = (0..8)
+                .map(|i| {
+                    scored(
+                        &format!("APP-{}", i),
+                        (next(90) + 10) as f64,
+                        (next(100) + 1) as f64,
+                    )
+                })
+                .collect();
+            let pool = (next(300) + 50) as f64;
+
+            let best = (0..1u32 << applications.len())
+                .filter_map(|set| {
+                    let chosen = applications
+                        .iter()
+                        .enumerate()
+                        .filter(|(i, _)| set & (1 << i) != 0);
+                    let (cost, score) = chosen.fold((0.0, 0.0), |(cost, score), (_, a)| {
+                        (cost + a.requested, score + a.score)
+                    });
+                    (cost <= pool).then_some(score)
+                })
+                .fold(0.0, f64::max);
+
+            let allocation = allocate(pool, &applications, Strategy::Knapsack, 0.0).unwrap();
+            let funded: f64 = allocation
+                .awards
+                .iter()
+                .filter(|award| award.awarded > 0.0)
+                .map(|award| award.score)
+                .sum();
+            assert_eq!(funded, best, "pool {} over {:?}", pool, applications);
+            assert!(allocation.total_awarded <= pool);
+            assert!(allocation
+                .awards
+                .iter()
+                .all(|award| award.awarded == 0.0 || award.awarded == award.requested));
+        }
+    }
+
+    #[test]
+    fn proportional_caps_at_the_request_and_shares_the_rest() {
+        let applications = [scored("A", 20.0, 3.0), scored("B", 200.0, 1.0)];
+        let allocation = allocate(100.0, &applications, Strategy::Proportional, 0.0).unwrap();
+        assert_eq!(awarded(&allocation), [("A", 20.0), ("B", 80.0)]);
+        assert_eq!(allocation.remainder, 0.0);
+        assert!(allocation.awards[0].rationale.contains("funded in full"));
+        assert!(allocation.awards[1].rationale.contains("partially funded"));
+    }
+
+    #[test]
+    fn proportional_never_exceeds_requests_or_pool() {
+        let applications = [
+            scored("A", 10.0, 5.0),
+            scored("B", 15.0, 4.0),
+            scored("C", 500.0, 3.0),
+            scored("D", 40.0, 0.0),
+        ];
+        let allocation = allocate(100.0, &applications, Strategy::Proportional, 0.0).unwrap();
+        assert_eq!(
+            awarded(&allocation),
+            [("A", 10.0), ("B", 15.0), ("C", 75.0), ("D", 0.0)]
+        );
+        assert!(allocation.awards[3].rationale.contains("score is zero"));
+    }
+
+    #[test]
+    fn floor_proportional_gives_the_floor_before_sharing() {
+        let applications = [scored("A", 100.0, 3.0), scored("B", 100.0, 1.0)];
+        let allocation = allocate(100.0, &applications, Strategy::FloorProportional, 20.0).unwrap();
+        // 20 each, then the remaining 60 split 3:1
+        assert_eq!(awarded(&allocation), [("A", 65.0), ("B", 35.0)]);
+    }
+
+    #[test]
+    fn awards_are_rounded_down_to_cents() {
+        let applications = [
+            scored("A", 100.0, 1.0),
+            scored("B", 100.0, 1.0),
+            scored("C", 100.0, 1.0),
+        ];
+        let allocation = allocate(100.0, &applications, Strategy::Proportional, 0.0).unwrap();
+        assert!(allocation.awards.iter().all(|award| award.awarded == 33.33));
+        assert_eq!(allocation.total_awarded, 99.99);
+        assert_eq!(allocation.remainder, 0.01);
+    }
+
+    #[test]
+    fn round_down_cents_truncates_without_float_noise() {
+        assert_eq!(round_down_cents(33.339), 33.33);
+        assert_eq!(round_down_cents(2.675), 2.67);
+        assert_eq!(round_down_cents(0.1 + 0.2), 0.3);
+        assert_eq!(round_down_cents(19.99), 19.99);
+        assert_eq!(round_down_cents(0.0), 0.0);
+    }
+
+    #[test]
+    fn ties_are_ranked_by_application_id() {
+        let applications = [scored("B", 10.0, 5.0), scored("A", 10.0, 5.0)];
+        let allocation = allocate(10.0, &applications, Strategy::Greedy, 0.0).unwrap();
+        assert_eq!(awarded(&allocation), [("A", 10.0), ("B", 0.0)]);
+        assert!(allocation.awards[0].rationale.starts_with("rank 1:"));
+    }
+
+    #[test]
+    fn rejects_unusable_inputs() {
+        let good = [scored("A", 10.0, 1.0)];
+        for pool in [-1.0, f64::NAN, f64::INFINITY] {
+            let error = allocate(pool, &good, Strategy::Greedy, 0.0).unwrap_err();
+            assert!(matches!(error, GrantError::Allocation { .. }), "{}", pool);
+        }
+        assert!(allocate(10.0, &good, Strategy::FloorProportional, f64::NAN).is_err());
+        for bad in [scored("X", -5.0, 1.0), scored("X", 5.0, f64::NAN)] {
+            assert!(allocate(10.0, &[bad], Strategy::Greedy, 0.0).is_err());
+        }
+    }
+
+    #[test]
+    fn empty_input_allocates_nothing() {
+        for strategy in [
+            Strategy::Greedy,
+            Strategy::Proportional,
+            Strategy::Knapsack,
+            Strategy::FloorProportional,
+        ] {
+            let allocation = allocate(100.0, &[], strategy, 10.0).unwrap();
+            assert!(allocation.awards.is_empty());
+            assert_eq!(allocation.remainder, 100.0);
+        }
+    }
+}
@@ -0,0 +1,408 @@
+// src/async_processor.rs
+/*
+ * Async front end to the shared processor, enabled by the `async` feature
+ */
+
+use crate::{GrantApplication, ProcessResult, Result, SharedGrantProcessor};
+use log::warn;
+use std::collections::VecDeque;
+use std::future::Future;
+use std::io;
+use std::panic::{self, AssertUnwindSafe};
+use std::pin::Pin;
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::task::{Context, Poll, Waker};
+use std::thread;
+
+/// The `futures` stream trait, so tokio and futures streams can be processed
+/// directly and `ProcessStream` works with `StreamExt`
+pub use futures_core::Stream;
+
+/// Stream yielding the values of an iterator
+#[derive(Debug, Clone)]
+pub struct Iter<I> {
+    iter: I,
+}
+
+impl<I: Iterator + Unpin> Stream for Iter<I> {
+    type Item = I::Item;
+
+    fn poll_next(mut self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Option<I::Item>> {
+        Poll::Ready(self.iter.next())
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+/// Turn an iterator into a stream that is always ready
+pub fn iter<I: IntoIterator>(iter: I) -> Iter<I::IntoIter> {
+    Iter {
+        iter: iter.into_iter(),
+    }
+}
+
+type Job = Box<dyn FnOnce() + Send>;
+
+/// Value handed from a worker thread to the task awaiting it
+struct Slot<T> {
+    value: Option<thread::Result<T>>,
+    waker: Option<Waker>,
+}
+
+/// Future completing when a worker thread has finished a job
+struct Completion<T> {
+    slot: Arc<Mutex<Slot<T>>>,
+}
+
+impl<T> Future for Completion<T> {
+    type Output = T;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<T> {
+        let mut slot = self.slot.lock().unwrap_or_else(PoisonError::into_inner);
+        match slot.value.take() {
+            Some(Ok(value)) => Poll::Ready(value),
+            // Surface a panic in the job to the task awaiting it
+            Some(Err(payload)) => panic::resume_unwind(payload),
+            None => {
+                slot.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Inner {
+    processor: Arc<SharedGrantProcessor>,
+    jobs: Mutex<mpsc::Sender<Job>>,
+}
+
+/// Grant processor for async code
+///
+/// Every submission is handed to a small pool of worker threads running a
+/// `SharedGrantProcessor`, so validation, rule evaluation, scoring and the
+/// store and audit log writes never block the executor. It works with any
+/// executor, tokio included, and is cheap to clone: clones share the
+/// processor and the workers.
+#[derive(Debug, Clone)]
+pub struct AsyncGrantProcessor {
+    inner: Arc<Inner>,
+}
+
+impl AsyncGrantProcessor {
+    /// Wrap a shared processor
+    ///
+    /// # Arguments
+    ///
+    /// * `processor` - Processor doing the work
+    /// * `threads` - Worker threads; 0 means one per CPU
+    ///
+    /// # Returns
+    ///
+    /// A new `AsyncGrantProcessor` whose workers stop once every clone is
+    /// dropped. If no worker thread can be started, every submission fails
+    /// with an I/O error.
+    pub fn new(processor: SharedGrantProcessor, threads: usize) -> Self {
+        let (sender, receiver) = mpsc::channel::<Job>();
+        let receiver = Arc::new(Mutex::new(receiver));
+        for _ in 0..crate::pool::worker_count(threads) {
+            let receiver = Arc::clone(&receiver);
+            let spawned = thread::Builder::new().spawn(move || loop {
+                let job = receiver
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .recv();
+                match job {
+                    Ok(job) => job(),
+                    Err(_) => break,
+                }
+            });
+            if let Err(error) = spawned {
+                warn!("Could not start an async worker thread: {}", error);
+            }
+        }
+        Self {
+            inner: Arc::new(Inner {
+                processor: Arc::new(processor),
+                jobs: Mutex::new(sender),
+            }),
+        }
+    }
+
+    /// Processor doing the work, e.g. for `get_stats`
+    pub fn processor(&self) -> &SharedGrantProcessor {
+        &self.inner.processor
+    }
+
+    /// Parse a JSON encoded application and process it
+    ///
+    /// # Arguments
+    ///
+    /// * `data` - JSON document describing a single `GrantApplication`
+    ///
+    /// # Returns
+    ///
+    /// A `ProcessResult` instance describing the result of processing
+    pub async fn process(&self, data: &str) -> Result<ProcessResult> {
+        let data = data.to_string();
+        self.spawn(move |processor| processor.process(&data)).await
+    }
+
+    /// Evaluate a single application
+    ///
+    /// # Arguments
+    ///
+    /// * `application` - Application to evaluate
+    ///
+    /// # Returns
+    ///
+    /// A `ProcessResult` whose `data` summarises the evaluated application,
+    /// or the store's error if the outcome cannot be persisted
+    pub async fn process_application(
+        &self,
+        application: GrantApplication,
+    ) -> Result<ProcessResult> {
+        self.spawn(move |processor| processor.process_application(&application))
+            .await
+    }
+
+    /// Process a stream of applications as they arrive
+    ///
+    /// Up to `limit` applications are processed at once; results come out in
+    /// the order the applications went in. With more than one in flight,
+    /// item numbers follow the order in which processing finishes.
+    ///
+    /// # Arguments
+    ///
+    /// * `applications` - Applications to process
+    /// * `limit` - Applications processed at once; at least one
+    ///
+    /// # Returns
+    ///
+    /// A stream with one result per application
+    pub fn process_stream<S>(&self, applications: S, limit: usize) -> ProcessStream<S>
+    where
+        S: Stream<Item = GrantApplication> + Unpin,
+    {
+        ProcessStream {
+            processor: self.clone(),
+            applications: Some(applications),
+            in_flight: VecDeque::new(),
+            limit: limit.max(1),
+        }
+    }
+
+    /// Run `work` on a worker thread
+    ///
+    /// The completion resolves to an I/O error straight away if the workers
+    /// have stopped.
+    fn spawn<T, F>(&self, work: F) -> Completion<Result<T>>
+    where
+        T: Send + 'static,
+        F: FnOnce(&SharedGrantProcessor) -> Result<T> + Send + 'static,
+    {
+        let slot = Arc::new(Mutex::new(Slot {
+            value: None,
+            waker: None,
+        }));
+        let completion = Completion {
+            slot: Arc::clone(&slot),
+        };
+        let processor = Arc::clone(&self.inner.processor);
+        let job: Job = Box::new(move || {
+            let value = panic::catch_unwind(AssertUnwindSafe(|| work(&processor)));
+            let mut slot = slot.lock().unwrap_or_else(PoisonError::into_inner);
+            slot.value = Some(value);
+            if let Some(waker) = slot.waker.take() {
+                waker.wake();
+            }
+        });
+        let sent = self
+            .inner
+            .jobs
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .send(job);
+        if sent.is_err() {
+            let stopped = io::Error::other("the async processor's worker threads have stopped");
+            completion
+                .slot
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .value = Some(Ok(Err(stopped.into())));
+        }
+        completion
+    }
+}
+
+/// Stream of results returned by `AsyncGrantProcessor::process_stream`
+pub struct ProcessStream<S> {
+    processor: AsyncGrantProcessor,
+    /// Applications still to be read; `None` once the input has ended
+    applications: Option<S>,
+    in_flight: VecDeque<Completion<Result<ProcessResult>>>,
+    limit: usize,
+}
+
+impl<S> Stream for ProcessStream<S>
+where
+    S: Stream<Item = GrantApplication> + Unpin,
+{
+    type Item = Result<ProcessResult>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = &mut *self;
+        while this.in_flight.len() < this.limit {
+            let Some(applications) = this.applications.as_mut() else {
+                break;
+            };
+            match Pin::new(applications).poll_next(cx) {
+                Poll::Ready(Some(application)) => {
+                    let processor = &this.processor;
+                    this.in_flight.push_back(
+                        processor
+                            .spawn(move |processor| processor.process_application(&application)),
+                    );
+                }
+                Poll::Ready(None) => this.applications = None,
+                Poll::Pending => break,
+            }
+        }
+
+        match this.in_flight.front_mut() {
+            Some(next) => match Pin::new(next).poll(cx) {
+                Poll::Ready(result) => {
+                    this.in_flight.pop_front();
+                    Poll::Ready(Some(result))
+                }
+                Poll::Pending => Poll::Pending,
+            },
+            None if this.applications.is_none() => Poll::Ready(None),
+            None => Poll::Pending,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{application, program};
+    use crate::GrantError;
+    use futures::channel::mpsc as channel;
+    use futures::executor::block_on;
+    use futures::{stream, SinkExt, StreamExt};
+
+    fn processor(threads: usize) -> AsyncGrantProcessor {
+        AsyncGrantProcessor::new(
+            SharedGrantProcessor::new(false, Some(program(100_000.0))),
+            threads,
+        )
+    }
+
+    fn ids(results: &[Result<ProcessResult>]) -> Vec<String> {
+        results
+            .iter()
+            .map(|result| {
+                let data = result.as_ref().unwrap().data.as_ref().unwrap();
+                data["application_id"].as_str().unwrap().to_string()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn a_futures_stream_is_processed_in_order() {
+        let processor = processor(4);
+        let applications: Vec<GrantApplication> = (0..50)
+            .map(|index| application(&format!("APP-{:02}", index), 10_000.0))
+            .collect();
+        let expected: Vec<String> = applications.iter().map(|a| a.id.clone()).collect();
+
+        let results: Vec<Result<ProcessResult>> = block_on(
+            processor
+                .process_stream(stream::iter(applications), 8)
+                .collect(),
+        );
+        assert_eq!(ids(&results), expected);
+        assert_eq!(processor.processor().processed_count(), 50);
+    }
+
+    #[test]
+    fn applications_arriving_later_are_picked_up() {
+        let processor = processor(2);
+        let (mut sender, receiver) = channel::channel::<GrantApplication>(1);
+        let producer = thread::spawn(move || {
+            block_on(async {
+                for index in 0..10 {
+                    let application = application(&format!("APP-{}", index), 10_000.0);
+                    sender.send(application).await.unwrap();
+                }
+            })
+        });
+
+        let results: Vec<Result<ProcessResult>> =
+            block_on(processor.process_stream(receiver, 3).collect());
+        producer.join().unwrap();
+        let expected: Vec<String> = (0..10).map(|index| format!("APP-{}", index)).collect();
+        assert_eq!(ids(&results), expected);
+    }
+
+    #[test]
+    fn the_crate_iter_stream_works_with_stream_ext() {
+        let processor = processor(1);
+        let input = iter(vec![
+            application("A1", 10_000.0),
+            application("A1", 10_000.0),
+        ]);
+        assert_eq!(input.size_hint(), (2, Some(2)));
+
+        let results: Vec<bool> = block_on(
+            processor
+                .process_stream(input, 2)
+                .map(|result| result.unwrap().success)
+                .collect(),
+        );
+        // The second submission of the same application is refused
+        assert_eq!(results, [true, false]);
+    }
+
+    #[test]
+    fn single_submissions_resolve_on_the_workers() {
+        let processor = processor(2);
+        let body = serde_json::to_string(&application("A1", 10_000.0)).unwrap();
+
+        let result = block_on(processor.process(&body)).unwrap();
+        assert!(result.success);
+        let result = block_on(processor.process_application(application("A2", 10_000.0))).unwrap();
+        assert!(result.success);
+        assert!(matches!(
+            block_on(processor.process("{")),
+            Err(GrantError::Parse { .. })
+        ));
+        assert_eq!(processor.processor().processed_count(), 2);
+    }
+
+    #[test]
+    fn submissions_fail_once_the_workers_have_stopped() {
+        let (sender, receiver) = mpsc::channel::<Job>();
+        drop(receiver);
+        let processor = AsyncGrantProcessor {
+            inner: Arc::new(Inner {
+                processor: Arc::new(SharedGrantProcessor::new(false, None)),
+                jobs: Mutex::new(sender),
+            }),
+        };
+
+        let error =
+            block_on(processor.process_application(application("A1", 10_000.0))).unwrap_err();
+        assert!(matches!(error, GrantError::Io { .. }), "{error:?}");
+        let results: Vec<Result<ProcessResult>> = block_on(
+            processor
+                .process_stream(stream::iter(vec![application("A1", 10_000.0)]), 1)
+                .collect(),
+        );
+        assert!(matches!(results[..], [Err(GrantError::Io { .. })]));
+    }
+}
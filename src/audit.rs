@@ -0,0 +1,472 @@
+// src/audit.rs
+/*
+ * Append-only, hash-chained audit log of processing decisions
+ */
+
+use crate::{GrantError, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fmt::Write as _;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// Hash the first entry links to
+pub const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+/// What an audit entry records
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditAction {
+    /// An application went through validation and the eligibility checks
+    Processed,
+    /// A single eligibility rule was evaluated
+    RuleEvaluated,
+    /// An application received a score
+    Scored,
+    /// An application changed status
+    StatusChanged,
+    /// The program pool was allocated
+    Allocated,
+    /// An application's share of the pool was decided
+    Awarded,
+}
+
+/// One entry of the audit log
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuditEntry {
+    /// 1-based position in the log
+    pub sequence: u64,
+    /// When the entry was written
+    pub timestamp: DateTime<Utc>,
+    /// What happened
+    pub action: AuditAction,
+    /// Application concerned, if any
+    #[serde(default)]
+    pub application_id: Option<String>,
+    /// Action-specific details
+    pub details: serde_json::Value,
+    /// Hash of the previous entry, or `GENESIS_HASH` for the first one
+    pub prev_hash: String,
+    /// SHA-256 over the JSON text of every other field, as written to the log
+    pub hash: String,
+}
+
+/// Fields covered by an entry's hash
+#[derive(Serialize)]
+struct Sealed<'a> {
+    sequence: u64,
+    timestamp: &'a DateTime<Utc>,
+    action: AuditAction,
+    application_id: &'a Option<String>,
+    details: &'a serde_json::Value,
+    prev_hash: &'a str,
+}
+
+impl AuditEntry {
+    /// Hash the entry's contents as they are now
+    ///
+    /// The hash covers the JSON text of every field but `hash`, exactly as
+    /// `AuditLog::append` writes it; `verify` hashes the bytes read back from
+    /// the log rather than re-serializing parsed values, so floats in
+    /// `details` cannot drift between writing and checking.
+    pub fn compute_hash(&self) -> Result<String> {
+        Ok(digest(self.sealed()?.as_bytes()))
+    }
+
+    /// JSON text of the fields covered by the hash
+    fn sealed(&self) -> Result<String> {
+        let sealed = Sealed {
+            sequence: self.sequence,
+            timestamp: &self.timestamp,
+            action: self.action,
+            application_id: &self.application_id,
+            details: &self.details,
+            prev_hash: &self.prev_hash,
+        };
+        serde_json::to_string(&sealed).map_err(GrantError::output)
+    }
+}
+
+/// Key the hash is written under; it always closes the line
+const HASH_KEY: &str = ",\"hash\":\"";
+
+/// Hex-encoded SHA-256 of `bytes`
+fn digest(bytes: &[u8]) -> String {
+    let digest = Sha256::digest(bytes);
+    let mut hex = String::with_capacity(digest.len() * 2);
+    for byte in digest {
+        let _ = write!(hex, "{:02x}", byte);
+    }
+    hex
+}
+
+/// Append the hash to the sealed JSON text of an entry, giving its log line
+fn seal_line(sealed: &str, hash: &str) -> String {
+    let body = sealed.strip_suffix('}').unwrap_or(sealed);
+    format!("{}{}{}\"}}\n", body, HASH_KEY, hash)
+}
+
+/// Recover the sealed JSON text from a log line written by `seal_line`
+///
+/// Returns `None` when the line does not end with the hash `hash`.
+fn unseal_line(line: &str, hash: &str) -> Option<String> {
+    let body = line
+        .strip_suffix("\"}")?
+        .strip_suffix(hash)?
+        .strip_suffix(HASH_KEY)?;
+    Some(format!("{}}}", body))
+}
+
+/// Result of a successful `verify`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuditSummary {
+    /// Number of entries checked
+    pub entries: u64,
+    /// Hash of the last entry; keep it elsewhere to detect truncation later
+    pub head: String,
+}
+
+/// Writer appending entries to an audit log file
+///
+/// Each entry is one JSON line carrying the hash of the entry before it, so
+/// editing, removing or reordering any entry breaks the chain from that
+/// point on.
+#[derive(Debug)]
+pub struct AuditLog {
+    path: PathBuf,
+    file: File,
+    sequence: u64,
+    head: String,
+}
+
+impl AuditLog {
+    /// Open an audit log for appending, creating it if it does not exist yet
+    ///
+    /// The chain is continued from the last entry; use `verify` to check the
+    /// entries before it.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path of the log file
+    ///
+    /// # Returns
+    ///
+    /// The opened log, or a parse error if the last entry cannot be read
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let (mut sequence, mut head) = (0, GENESIS_HASH.to_string());
+
+        match fs::read_to_string(&path) {
+            Ok(contents) => {
+                let last = contents
+                    .lines()
+                    .enumerate()
+                    .filter(|(_, line)| !line.trim().is_empty())
+                    .last();
+                if let Some((number, line)) = last {
+                    let entry = parse_entry(&path, number + 1, line)?;
+                    sequence = entry.sequence;
+                    head = entry.hash;
+                }
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(GrantError::io(&path, e)),
+        }
+
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .map_err(|e| GrantError::io(&path, e))?;
+        Ok(Self {
+            path,
+            file,
+            sequence,
+            head,
+        })
+    }
+
+    /// Hash of the last entry written
+    pub fn head(&self) -> &str {
+        &self.head
+    }
+
+    /// Append an entry chained to the previous one
+    ///
+    /// # Arguments
+    ///
+    /// * `action` - What happened
+    /// * `application_id` - Application concerned, if any
+    /// * `details` - Action-specific details
+    ///
+    /// # Returns
+    ///
+    /// The entry as written
+    pub fn append(
+        &mut self,
+        action: AuditAction,
+        application_id: Option<&str>,
+        details: serde_json::Value,
+    ) -> Result<AuditEntry> {
+        let mut entry = AuditEntry {
+            sequence: self.sequence + 1,
+            timestamp: Utc::now(),
+            action,
+            application_id: application_id.map(str::to_string),
+            details,
+            prev_hash: self.head.clone(),
+            hash: String::new(),
+        };
+        let sealed = entry.sealed()?;
+        entry.hash = digest(sealed.as_bytes());
+        let line = seal_line(&sealed, &entry.hash);
+        self.file
+            .write_all(line.as_bytes())
+            .and_then(|()| self.file.flush())
+            .map_err(|e| GrantError::io(&self.path, e))?;
+
+        self.sequence = entry.sequence;
+        self.head = entry.hash.clone();
+        Ok(entry)
+    }
+}
+
+/// Check every link of an audit log
+///
+/// # Arguments
+///
+/// * `path` - Path of the log file
+///
+/// # Returns
+///
+/// The number of entries and the head hash, or `GrantError::Audit` naming the
+/// first entry that was edited, removed or moved
+pub fn verify<P: AsRef<Path>>(path: P) -> Result<AuditSummary> {
+    let path = path.as_ref();
+    let contents = fs::read_to_string(path).map_err(|e| GrantError::io(path, e))?;
+    let mut expected = 1;
+    let mut head = GENESIS_HASH.to_string();
+
+    for (number, line) in contents.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let broken = |message: String| GrantError::Audit {
+            line: number + 1,
+            sequence: expected,
+            message,
+        };
+
+        let entry: AuditEntry = serde_json::from_str(line)
+            .map_err(|e| broken(format!("entry cannot be read: {}", e)))?;
+        if entry.sequence != expected {
+            return Err(broken(format!(
+                "expected entry {} but found entry {}; entries were removed or reordered",
+                expected, entry.sequence
+            )));
+        }
+        if entry.prev_hash != head {
+            return Err(broken(
+                "does not link to the previous entry; an earlier entry was changed or removed"
+                    .to_string(),
+            ));
+        }
+        let hashed = unseal_line(line, &entry.hash).map(|sealed| digest(sealed.as_bytes()));
+        if hashed.as_deref() != Some(entry.hash.as_str()) {
+            return Err(broken(
+                "contents do not match the recorded hash; the entry was edited".to_string(),
+            ));
+        }
+
+        head = entry.hash;
+        expected += 1;
+    }
+
+    Ok(AuditSummary {
+        entries: expected - 1,
+        head,
+    })
+}
+
+fn parse_entry(path: &Path, line: usize, text: &str) -> Result<AuditEntry> {
+    serde_json::from_str(text).map_err(|e| GrantError::Parse {
+        path: Some(path.to_path_buf()),
+        record: None,
+        line: Some(line),
+        column: Some(e.column()).filter(|&column| column > 0),
+        message: e.to_string(),
+        source: Some(Box::new(e)),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    /// Deterministic xorshift generator of arbitrary finite floats
+    struct Floats(u64);
+
+    impl Iterator for Floats {
+        type Item = f64;
+
+        fn next(&mut self) -> Option<f64> {
+            loop {
+                self.0 ^= self.0 << 13;
+                self.0 ^= self.0 >> 7;
+                self.0 ^= self.0 << 17;
+                let value = f64::from_bits(self.0);
+                if value.is_finite() {
+                    return Some(value);
+                }
+            }
+        }
+    }
+
+    fn write_log(path: &Path, entries: usize) {
+        let mut log = AuditLog::open(path).unwrap();
+        for (index, score) in Floats(0x9e37_79b9_7f4a_7c15).take(entries).enumerate() {
+            log.append(
+                AuditAction::Scored,
+                Some(&format!("APP-{}", index)),
+                json!({ "score": score, "awarded": score / 3.0, "ratio": 1.0 / (index as f64 + 3.0) }),
+            )
+            .unwrap();
+        }
+    }
+
+    fn audit_error(result: Result<AuditSummary>) -> (usize, u64, String) {
+        match result.unwrap_err() {
+            GrantError::Audit {
+                line,
+                sequence,
+                message,
+            } => (line, sequence, message),
+            other => panic!("expected an audit error, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn an_untouched_log_with_arbitrary_floats_verifies() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("audit.jsonl");
+        write_log(&path, 2000);
+
+        let summary = verify(&path).unwrap();
+        assert_eq!(summary.entries, 2000);
+        assert_eq!(summary.head, AuditLog::open(&path).unwrap().head());
+    }
+
+    #[test]
+    fn written_lines_parse_back_to_the_appended_entry() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("audit.jsonl");
+        let mut log = AuditLog::open(&path).unwrap();
+        let first = log
+            .append(AuditAction::Processed, Some("A"), json!({ "passed": true }))
+            .unwrap();
+        let second = log
+            .append(AuditAction::Allocated, None, json!({ "pool": 100000 }))
+            .unwrap();
+
+        assert_eq!(first.prev_hash, GENESIS_HASH);
+        assert_eq!(second.prev_hash, first.hash);
+        assert_eq!(first.compute_hash().unwrap(), first.hash);
+        let contents = fs::read_to_string(&path).unwrap();
+        let entries: Vec<AuditEntry> = contents
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(entries, [first, second]);
+    }
+
+    #[test]
+    fn reopening_continues_the_chain() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("audit.jsonl");
+        write_log(&path, 3);
+
+        let mut log = AuditLog::open(&path).unwrap();
+        let head = log.head().to_string();
+        let entry = log
+            .append(AuditAction::StatusChanged, Some("A"), json!({}))
+            .unwrap();
+        assert_eq!(entry.sequence, 4);
+        assert_eq!(entry.prev_hash, head);
+        assert_eq!(verify(&path).unwrap().entries, 4);
+    }
+
+    #[test]
+    fn a_one_byte_edit_is_detected() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("audit.jsonl");
+        write_log(&path, 5);
+
+        let contents = fs::read_to_string(&path).unwrap();
+        let lines: Vec<&str> = contents.lines().collect();
+        let third = lines[2].replacen("APP-2", "APP-7", 1);
+        assert_eq!(third.len(), lines[2].len());
+        let edited = [lines[0], lines[1], &third, lines[3], lines[4]].join("\n");
+        fs::write(&path, edited).unwrap();
+
+        let (line, sequence, message) = audit_error(verify(&path));
+        assert_eq!((line, sequence), (3, 3));
+        assert!(message.contains("the entry was edited"), "{message}");
+    }
+
+    #[test]
+    fn rewriting_an_entry_with_its_hash_breaks_the_next_link() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("audit.jsonl");
+        write_log(&path, 3);
+
+        let contents = fs::read_to_string(&path).unwrap();
+        let mut lines: Vec<String> = contents.lines().map(str::to_string).collect();
+        let mut entry: AuditEntry = serde_json::from_str(&lines[1]).unwrap();
+        entry.details = json!({ "score": 100.0 });
+        let sealed = entry.sealed().unwrap();
+        entry.hash = digest(sealed.as_bytes());
+        lines[1] = seal_line(&sealed, &entry.hash).trim_end().to_string();
+        fs::write(&path, lines.join("\n")).unwrap();
+
+        let (line, sequence, message) = audit_error(verify(&path));
+        assert_eq!((line, sequence), (3, 3));
+        assert!(message.contains("does not link"), "{message}");
+    }
+
+    #[test]
+    fn removed_and_reordered_entries_are_detected() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("audit.jsonl");
+        write_log(&path, 3);
+        let contents = fs::read_to_string(&path).unwrap();
+        let lines: Vec<&str> = contents.lines().collect();
+
+        fs::write(&path, [lines[0], lines[2]].join("\n")).unwrap();
+        let (line, sequence, message) = audit_error(verify(&path));
+        assert_eq!((line, sequence), (2, 2));
+        assert!(message.contains("removed or reordered"), "{message}");
+
+        fs::write(&path, [lines[1], lines[0], lines[2]].join("\n")).unwrap();
+        let (line, sequence, _) = audit_error(verify(&path));
+        assert_eq!((line, sequence), (1, 1));
+    }
+
+    #[test]
+    fn a_reformatted_entry_counts_as_edited() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("audit.jsonl");
+        write_log(&path, 1);
+
+        let contents = fs::read_to_string(&path).unwrap();
+        fs::write(
+            &path,
+            contents.replacen("\"sequence\":1", "\"sequence\": 1", 1),
+        )
+        .unwrap();
+        let (_, _, message) = audit_error(verify(&path));
+        assert!(message.contains("the entry was edited"), "{message}");
+    }
+}
@@ -0,0 +1,625 @@
+// src/batch.rs
+/*
+ * Batch ingestion of applications from JSON arrays and NDJSON
+ */
+
+use crate::schema::{self, SchemaViolation};
+use crate::{GrantApplication, GrantError, ProcessResult, Progress, Result};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::io::BufRead;
+use std::path::Path;
+
+/// Layout of the application input
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum InputFormat {
+    /// Pick from the file extension, or from the start of stdin
+    Auto,
+    /// A single JSON application or a JSON array of applications
+    Json,
+    /// One JSON application per line, processed as it is read
+    Ndjson,
+    /// CSV with a header row, mapped through a `ColumnMapping`
+    Csv,
+}
+
+impl InputFormat {
+    /// Resolve `Auto` for a file from its extension
+    ///
+    /// `.csv` selects CSV and `.ndjson`/`.jsonl` select NDJSON; anything else
+    /// is read as JSON, which also accepts NDJSON content.
+    pub fn for_path(self, path: &Path) -> Self {
+        if self != InputFormat::Auto {
+            return self;
+        }
+        let extension = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(str::to_ascii_lowercase);
+        match extension.as_deref() {
+            Some("csv") => InputFormat::Csv,
+            Some("ndjson") | Some("jsonl") => InputFormat::Ndjson,
+            _ => InputFormat::Json,
+        }
+    }
+
+    /// Resolve `Auto` for a stream by peeking at its first non-blank byte
+    ///
+    /// A leading `[` selects JSON. A leading `{` selects NDJSON only when the
+    /// first line already held in the reader's buffer is a complete JSON value;
+    /// otherwise the input may be a single pretty-printed document and is read
+    /// as JSON, which also accepts NDJSON content. Only leading whitespace is
+    /// consumed from the reader.
+    pub fn for_reader<R: BufRead>(self, reader: &mut R) -> std::io::Result<Self> {
+        if self != InputFormat::Auto {
+            return Ok(self);
+        }
+        loop {
+            let buffer = reader.fill_buf()?;
+            if buffer.is_empty() {
+                return Ok(InputFormat::Ndjson);
+            }
+            let blank = buffer
+                .iter()
+                .take_while(|byte| byte.is_ascii_whitespace())
+                .count();
+            if blank < buffer.len() {
+                let format = match buffer[blank] {
+                    b'[' => InputFormat::Json,
+                    b'{' => sniff_object(&buffer[blank..]),
+                    _ => InputFormat::Ndjson,
+                };
+                reader.consume(blank);
+                return Ok(format);
+            }
+            reader.consume(blank);
+        }
+    }
+}
+
+/// NDJSON when the first buffered line is a complete JSON value, JSON otherwise
+fn sniff_object(buffer: &[u8]) -> InputFormat {
+    let first_line = match buffer.iter().position(|&byte| byte == b'\n') {
+        Some(end) => &buffer[..end],
+        None => return InputFormat::Json,
+    };
+    match serde_json::from_slice::<serde_json::Value>(first_line) {
+        Ok(_) => InputFormat::Ndjson,
+        Err(_) => InputFormat::Json,
+    }
+}
+
+/// An application read from a batch together with its position
+#[derive(Debug, Clone)]
+pub struct Record {
+    /// 1-based position of the record in the batch
+    pub index: usize,
+    /// 1-based line the record starts on
+    pub line: usize,
+    /// The parsed application
+    pub application: GrantApplication,
+}
+
+/// A record that could not be turned into an application
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecordError {
+    /// 1-based position of the record in the batch
+    pub index: usize,
+    /// 1-based line the record starts on
+    pub line: usize,
+    /// 1-based column of the offending field, when known
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub column: Option<usize>,
+    /// What was wrong with the record
+    pub message: String,
+    /// Every place the record breaks the application schema
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub violations: Vec<SchemaViolation>,
+}
+
+impl fmt::Display for RecordError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.column {
+            Some(column) => write!(
+                f,
+                "record {} (line {}, column {}): {}",
+                self.index, self.line, column, self.message
+            ),
+            None => write!(
+                f,
+                "record {} (line {}): {}",
+                self.index, self.line, self.message
+            ),
+        }
+    }
+}
+
+impl std::error::Error for RecordError {}
+
+/// Result of reading one record
+pub type RecordResult = std::result::Result<Record, RecordError>;
+
+/// Processing result of one record, tagged with its position
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RecordOutcome {
+    /// 1-based position of the record in the batch
+    pub index: usize,
+    /// 1-based line the record starts on
+    pub line: usize,
+    /// Result of processing the record
+    #[serde(flatten)]
+    pub result: ProcessResult,
+}
+
+/// Receives outcomes as a batch is processed
+///
+/// Implemented by `BatchReport` to collect everything in memory; streaming
+/// callers implement it to forward each outcome as soon as it is available.
+pub trait BatchSink {
+    /// Called for every record that was processed
+    fn on_result(&mut self, record: &Record, outcome: RecordOutcome) -> Result<()>;
+
+    /// Called for every record that could not be parsed
+    fn on_error(&mut self, error: RecordError) -> Result<()>;
+
+    /// Called after every record with how far the batch has got
+    fn on_progress(&mut self, _progress: &Progress) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Aggregate counts for a batch
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct BatchSummary {
+    /// Records found in the input
+    pub total: usize,
+    /// Records that were processed
+    pub processed: usize,
+    /// Processed records that passed validation and every rule
+    pub succeeded: usize,
+    /// Processed records that were rejected
+    pub rejected: usize,
+    /// Records that could not be parsed
+    pub malformed: usize,
+}
+
+/// Everything produced by processing a batch
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct BatchReport {
+    /// Aggregate counts
+    pub summary: BatchSummary,
+    /// One entry per processed record
+    pub results: Vec<RecordOutcome>,
+    /// One entry per malformed record
+    pub errors: Vec<RecordError>,
+}
+
+impl BatchSink for BatchReport {
+    fn on_result(&mut self, _record: &Record, outcome: RecordOutcome) -> Result<()> {
+        self.results.push(outcome);
+        Ok(())
+    }
+
+    fn on_error(&mut self, error: RecordError) -> Result<()> {
+        self.errors.push(error);
+        Ok(())
+    }
+}
+
+/// Lazily reads NDJSON records from a buffered reader
+///
+/// Only one line is held in memory at a time, so arbitrarily large inputs
+/// can be processed as they arrive.
+pub struct NdjsonReader<R> {
+    reader: R,
+    line: usize,
+    index: usize,
+}
+
+impl<R: BufRead> NdjsonReader<R> {
+    /// Create a reader over NDJSON input
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            line: 0,
+            index: 0,
+        }
+    }
+}
+
+impl<R: BufRead> Iterator for NdjsonReader<R> {
+    type Item = Result<RecordResult>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut text = String::new();
+        loop {
+            text.clear();
+            match self.reader.read_line(&mut text) {
+                Ok(0) => return None,
+                Ok(_) => {
+                    self.line += 1;
+                    if text.trim().is_empty() {
+                        continue;
+                    }
+                    self.index += 1;
+                    return Some(Ok(parse_line(&text, self.index, self.line)));
+                }
+                Err(e) => return Some(Err(e.into())),
+            }
+        }
+    }
+}
+
+/// Split batch input into application records
+///
+/// The input may be a single JSON application, a JSON array of applications,
+/// or NDJSON with one application per line (blank lines are ignored).
+///
+/// # Arguments
+///
+/// * `input` - Raw batch contents
+///
+/// # Returns
+///
+/// One entry per record, either the parsed application or the reason it was
+/// rejected. Errors that make the rest of the input unreadable (such as a
+/// broken JSON array) are returned as `Err`.
+pub fn read_records(input: &str) -> Result<Vec<RecordResult>> {
+    let trimmed = input.trim_start();
+    if trimmed.starts_with('[') {
+        return read_array(input);
+    }
+
+    if let Ok(value) = serde_json::from_str::<serde_json::Value>(input) {
+        let line = line_at(input, input.len() - trimmed.len());
+        return Ok(vec![to_record(value, 1, line)]);
+    }
+
+    Ok(read_ndjson(input))
+}
+
+/// Parse NDJSON input line by line
+pub fn read_ndjson(input: &str) -> Vec<RecordResult> {
+    input
+        .lines()
+        .enumerate()
+        .filter(|(_, text)| !text.trim().is_empty())
+        .enumerate()
+        .map(|(position, (line, text))| parse_line(text, position + 1, line + 1))
+        .collect()
+}
+
+/// Parse a single NDJSON line into a record
+///
+/// # Arguments
+///
+/// * `text` - Contents of the line
+/// * `index` - 1-based record position
+/// * `line` - 1-based line number
+pub fn parse_line(text: &str, index: usize, line: usize) -> RecordResult {
+    match serde_json::from_str::<serde_json::Value>(text) {
+        Ok(value) => to_record(value, index, line),
+        Err(e) => Err(RecordError {
+            index,
+            line,
+            column: Some(e.column()).filter(|&column| column > 0),
+            message: e.to_string(),
+            violations: Vec::new(),
+        }),
+    }
+}
+
+fn read_array(input: &str) -> Result<Vec<RecordResult>> {
+    let mut records = Vec::new();
+    let mut offset = input.find('[').map_or(0, |start| start + 1);
+
+    loop {
+        offset = skip_whitespace(input, offset);
+        if input[offset..].starts_with(']') && records.is_empty() {
+            break;
+        }
+
+        let mut stream =
+            serde_json::Deserializer::from_str(&input[offset..]).into_iter::<serde_json::Value>();
+        let value = match stream.next() {
+            Some(Ok(value)) => value,
+            Some(Err(e)) => {
+                return Err(GrantError::Parse {
+                    path: None,
+                    record: Some(records.len() + 1),
+                    line: Some(line_at(input, offset) + e.line().saturating_sub(1)),
+                    column: None,
+                    message: format!("malformed JSON array: {}", e),
+                    source: Some(Box::new(e)),
+                })
+            }
+            None => return Err(GrantError::parse("unterminated JSON array")),
+        };
+
+        let index = records.len() + 1;
+        records.push(to_record(value, index, line_at(input, offset)));
+
+        offset = skip_whitespace(input, offset + stream.byte_offset());
+        match input[offset..].chars().next() {
+            Some(',') => offset += 1,
+            Some(']') => break,
+            _ => {
+                return Err(GrantError::Parse {
+                    path: None,
+                    record: Some(records.len()),
+                    line: Some(line_at(input, offset)),
+                    column: None,
+                    message: "malformed JSON array: expected ',' or ']'".to_string(),
+                    source: None,
+                })
+            }
+        }
+    }
+
+    Ok(records)
+}
+
+fn to_record(value: serde_json::Value, index: usize, line: usize) -> RecordResult {
+    match schema::parse_application(value) {
+        Ok(application) => Ok(Record {
+            index,
+            line,
+            application,
+        }),
+        Err(violations) => Err(RecordError {
+            index,
+            line,
+            column: None,
+            message: schema::describe(&violations),
+            violations,
+        }),
+    }
+}
+
+fn skip_whitespace(input: &str, offset: usize) -> usize {
+    let rest = &input[offset..];
+    offset + (rest.len() - rest.trim_start().len())
+}
+
+fn line_at(input: &str, offset: usize) -> usize {
+    input[..offset].matches('\n').count() + 1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::application;
+
+    /// One application as compact JSON
+    fn compact(id: &str) -> String {
+        serde_json::to_string(&application(id, 10_000.0)).unwrap()
+    }
+
+    /// One application as indented JSON spanning several lines
+    fn pretty(id: &str) -> String {
+        serde_json::to_string_pretty(&application(id, 10_000.0)).unwrap()
+    }
+
+    /// Index, line and id or error of every record
+    fn positions(records: &[RecordResult]) -> Vec<(usize, usize, String)> {
+        records
+            .iter()
+            .map(|record| match record {
+                Ok(record) => (record.index, record.line, record.application.id.clone()),
+                Err(error) => (error.index, error.line, "error".to_string()),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn array_records_carry_their_index_and_starting_line() {
+        let input = format!("[\n{},\n{}\n]", pretty("A1"), pretty("A2"));
+        let second_line = 2 + pretty("A1").lines().count();
+
+        let records = read_records(&input).unwrap();
+        assert_eq!(
+            positions(&records),
+            [(1, 2, "A1".to_string()), (2, second_line, "A2".to_string())]
+        );
+        assert_eq!(read_records("  [ ]").unwrap().len(), 0);
+    }
+
+    #[test]
+    fn a_record_breaking_the_schema_mid_array_keeps_its_position() {
+        let mut invalid = serde_json::to_value(application("A2", 10_000.0)).unwrap();
+        invalid["funding"]["amount"] = serde_json::json!(-1);
+        let input = format!("[\n{},\n{},\n{}\n]", compact("A1"), invalid, compact("A3"));
+
+        let records = read_records(&input).unwrap();
+        assert_eq!(
+            positions(&records),
+            [
+                (1, 2, "A1".to_string()),
+                (2, 3, "error".to_string()),
+                (3, 4, "A3".to_string())
+            ]
+        );
+        let error = records[1].as_ref().unwrap_err();
+        assert_eq!(error.violations[0].field(), "funding.amount");
+    }
+
+    #[test]
+    fn malformed_json_mid_array_reports_record_and_line() {
+        let input = format!(
+            "[\n{},\n{{\n  \"id\": \"A2\",\n  \"title\": }}\n]",
+            compact("A1")
+        );
+
+        match read_records(&input).unwrap_err() {
+            GrantError::Parse { record, line, .. } => {
+                assert_eq!(record, Some(2));
+                assert_eq!(line, Some(5));
+            }
+            other => panic!("expected a parse error, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn a_missing_separator_names_the_last_record_read() {
+        let input = format!("[\n{}\n{}\n]", compact("A1"), compact("A2"));
+
+        match read_records(&input).unwrap_err() {
+            GrantError::Parse {
+                record,
+                line,
+                message,
+                ..
+            } => {
+                assert_eq!(record, Some(1));
+                assert_eq!(line, Some(3));
+                assert!(message.contains("expected ',' or ']'"), "{message}");
+            }
+            other => panic!("expected a parse error, got {other:?}"),
+        }
+        assert!(matches!(
+            read_records(&format!("[{}", compact("A1"))),
+            Err(GrantError::Parse { .. })
+        ));
+    }
+
+    #[test]
+    fn a_malformed_ndjson_line_is_reported_and_reading_goes_on() {
+        let input = format!(
+            "{}\n\n{{\"id\": \"A2\",\n{}\n",
+            compact("A1"),
+            compact("A3")
+        );
+
+        let records = read_ndjson(&input);
+        assert_eq!(
+            positions(&records),
+            [
+                (1, 1, "A1".to_string()),
+                (2, 3, "error".to_string()),
+                (3, 4, "A3".to_string())
+            ]
+        );
+        let error = records[1].as_ref().unwrap_err();
+        assert!(error.column.is_some());
+        assert_eq!(
+            error.to_string(),
+            format!(
+                "record 2 (line 3, column {}): {}",
+                error.column.unwrap(),
+                error.message
+            )
+        );
+    }
+
+    #[test]
+    fn the_streaming_reader_numbers_records_like_read_ndjson() {
+        let input = format!("\n{}\nnot json\n\n{}\n", compact("A1"), compact("A2"));
+
+        let streamed: Vec<RecordResult> = NdjsonReader::new(input.as_bytes())
+            .collect::<Result<_>>()
+            .unwrap();
+        assert_eq!(positions(&streamed), positions(&read_ndjson(&input)));
+        assert_eq!(
+            positions(&streamed),
+            [
+                (1, 2, "A1".to_string()),
+                (2, 3, "error".to_string()),
+                (3, 5, "A2".to_string())
+            ]
+        );
+    }
+
+    /// Resolve `Auto` for `input` and return the bytes left in the reader
+    fn sniff(input: &str) -> (InputFormat, String) {
+        let mut reader = input.as_bytes();
+        let format = InputFormat::Auto.for_reader(&mut reader).unwrap();
+        (format, String::from_utf8(reader.to_vec()).unwrap())
+    }
+
+    #[test]
+    fn file_extensions_pick_the_format() {
+        let auto = InputFormat::Auto;
+        assert_eq!(auto.for_path(Path::new("apps.csv")), InputFormat::Csv);
+        assert_eq!(auto.for_path(Path::new("apps.NDJSON")), InputFormat::Ndjson);
+        assert_eq!(auto.for_path(Path::new("apps.jsonl")), InputFormat::Ndjson);
+        assert_eq!(auto.for_path(Path::new("apps.json")), InputFormat::Json);
+        assert_eq!(auto.for_path(Path::new("apps")), InputFormat::Json);
+        assert_eq!(
+            InputFormat::Csv.for_path(Path::new("apps.json")),
+            InputFormat::Csv
+        );
+    }
+
+    #[test]
+    fn stream_sniffing_consumes_only_leading_whitespace() {
+        let ndjson = format!("{}\n{}\n", compact("A1"), compact("A2"));
+        let document = pretty("A1");
+        let array = format!("[{}]", compact("A1"));
+
+        assert_eq!(sniff(&format!(" \n{array}")), (InputFormat::Json, array));
+        assert_eq!(
+            sniff(&format!("\n\n{ndjson}")),
+            (InputFormat::Ndjson, ndjson)
+        );
+        assert_eq!(
+            sniff(&format!("\t{document}")),
+            (InputFormat::Json, document)
+        );
+        assert_eq!(sniff("  \n"), (InputFormat::Ndjson, String::new()));
+        assert_eq!(sniff("name,amount\n").0, InputFormat::Ndjson);
+
+        let mut reader = "[]".as_bytes();
+        assert_eq!(
+            InputFormat::Ndjson.for_reader(&mut reader).unwrap(),
+            InputFormat::Ndjson
+        );
+        assert_eq!(reader, b"[]");
+    }
+
+    #[test]
+    fn a_pretty_printed_document_on_a_stream_is_read_whole() {
+        let (format, rest) = sniff(&pretty("A1"));
+        assert_eq!(format, InputFormat::Json);
+
+        let records = read_records(&rest).unwrap();
+        assert_eq!(positions(&records), [(1, 1, "A1".to_string())]);
+    }
+
+    #[test]
+    fn a_broken_first_ndjson_line_still_reports_every_record() {
+        let input = format!("{{\"id\": \n{}\n{}\n", compact("A2"), compact("A3"));
+
+        let (format, rest) = sniff(&input);
+        assert_eq!(format, InputFormat::Json);
+        assert_eq!(
+            positions(&read_records(&rest).unwrap()),
+            [
+                (1, 1, "error".to_string()),
+                (2, 2, "A2".to_string()),
+                (3, 3, "A3".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn ndjson_lines_are_numbered_from_one_with_blank_lines_counted() {
+        let input = format!(
+            "{}\r\n\n  \n{}\n\n{}",
+            compact("A1"),
+            compact("A2"),
+            compact("A3")
+        );
+
+        let expected = [
+            (1, 1, "A1".to_string()),
+            (2, 4, "A2".to_string()),
+            (3, 6, "A3".to_string()),
+        ];
+        assert_eq!(positions(&read_records(&input).unwrap()), expected);
+        let streamed: Vec<RecordResult> = NdjsonReader::new(input.as_bytes())
+            .collect::<Result<_>>()
+            .unwrap();
+        assert_eq!(positions(&streamed), expected);
+    }
+}
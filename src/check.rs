@@ -0,0 +1,528 @@
+// src/check.rs
+/*
+ * Dry-run checks that report every problem in a batch without processing it
+ */
+
+use crate::batch::{Record, RecordError, RecordResult};
+use crate::output::{ndjson_line, render_rows};
+use crate::pool;
+use crate::{GrantError, OutputFormat, ProgramDefinition, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::fmt;
+
+/// How serious a diagnostic is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    /// The application would be rejected or cannot be read
+    Error,
+    /// The application would be accepted, but something looks wrong
+    Warning,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        f.write_str(name)
+    }
+}
+
+/// A problem found in one record
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Diagnostic {
+    /// Error or warning
+    pub severity: Severity,
+    /// 1-based position of the record in the batch
+    pub index: usize,
+    /// 1-based line the record starts on
+    pub line: usize,
+    /// 1-based column of the offending cell or character, when known
+    pub column: Option<usize>,
+    /// Application concerned, once the record could be read
+    pub application_id: Option<String>,
+    /// Dotted path of the offending field, when known
+    pub field: Option<String>,
+    /// Eligibility rule that raised the problem, if any
+    pub rule_id: Option<String>,
+    /// Schema keyword the record breaks, for records that do not match the
+    /// application schema
+    pub schema_path: Option<String>,
+    /// What is wrong
+    pub message: String,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} in record {} (line {}",
+            self.severity, self.index, self.line
+        )?;
+        if let Some(column) = self.column {
+            write!(f, ", column {}", column)?;
+        }
+        f.write_str(")")?;
+        if let Some(field) = &self.field {
+            write!(f, " at {}", field)?;
+        }
+        write!(f, ": {}", self.message)
+    }
+}
+
+/// Aggregate counts of a dry run
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CheckSummary {
+    /// Records found in the input
+    pub records: usize,
+    /// Records without errors
+    pub valid: usize,
+    /// Records with at least one error
+    pub invalid: usize,
+    /// Errors found
+    pub errors: usize,
+    /// Warnings found
+    pub warnings: usize,
+}
+
+/// Everything a dry run found
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CheckReport {
+    /// Aggregate counts
+    pub summary: CheckSummary,
+    /// Every problem, in input order
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+impl CheckReport {
+    /// Whether any record has an error
+    pub fn has_errors(&self) -> bool {
+        self.summary.errors > 0
+    }
+
+    /// Render the report in the given format
+    ///
+    /// JSON formats carry the summary and the diagnostics; NDJSON writes a
+    /// `diagnostic` line per problem followed by a `summary` line; the table
+    /// formats list the diagnostics.
+    ///
+    /// # Arguments
+    ///
+    /// * `format` - Output format
+    ///
+    /// # Returns
+    ///
+    /// The rendered report
+    pub fn render(&self, format: OutputFormat) -> Result<String> {
+        match format {
+            OutputFormat::Json => serde_json::to_string(self).map_err(GrantError::output),
+            OutputFormat::JsonPretty => {
+                serde_json::to_string_pretty(self).map_err(GrantError::output)
+            }
+            OutputFormat::Ndjson => {
+                let mut out = String::new();
+                for diagnostic in &self.diagnostics {
+                    out.push_str(&ndjson_line(
+                        "diagnostic",
+                        serde_json::to_value(diagnostic).map_err(GrantError::output)?,
+                    ));
+                }
+                out.push_str(&ndjson_line(
+                    "summary",
+                    serde_json::to_value(&self.summary).map_err(GrantError::output)?,
+                ));
+                Ok(out)
+            }
+            OutputFormat::Csv | OutputFormat::Markdown | OutputFormat::Xml => {
+                render_rows(&self.diagnostics, format)
+            }
+        }
+    }
+}
+
+/// Check every record of a batch without processing it
+///
+/// Nothing is recorded anywhere: malformed records, structural problems,
+/// failed eligibility rules and duplicate ids are errors; unrated rubric
+/// criteria, missing budgets and missing submission dates are warnings.
+///
+/// # Arguments
+///
+/// * `program` - Program whose rules and rubric apply, if any
+/// * `records` - Records as read from the input
+/// * `threads` - Worker threads checking records; 0 means one per CPU. The
+///   diagnostics come out in input order whatever the number.
+///
+/// # Returns
+///
+/// A `CheckReport` listing every problem found
+pub fn check_records<I>(
+    program: Option<&ProgramDefinition>,
+    records: I,
+    threads: usize,
+) -> CheckReport
+where
+    I: IntoIterator<Item = RecordResult>,
+{
+    let mut report = CheckReport::default();
+    let mut first_seen: HashMap<String, usize> = HashMap::new();
+
+    let Ok(()) = pool::map_ordered(
+        records,
+        threads,
+        |record| {
+            let diagnostics = match &record {
+                Ok(record) => check_record(program, record),
+                Err(error) => malformed(error),
+            };
+            (record, diagnostics)
+        },
+        |(record, mut diagnostics)| -> std::result::Result<(), Infallible> {
+            // Duplicates depend on the records before, so they are found in order
+            if let Ok(record) = &record {
+                let id = &record.application.id;
+                match first_seen.get(id) {
+                    Some(first) => diagnostics.push(Diagnostic {
+                        field: Some("id".to_string()),
+                        message: format!("'{}' was already used by record {}", id, first),
+                        ..diagnostic(Severity::Error, record)
+                    }),
+                    None => {
+                        first_seen.insert(id.clone(), record.index);
+                    }
+                }
+            }
+
+            let errors = diagnostics
+                .iter()
+                .filter(|diagnostic| diagnostic.severity == Severity::Error)
+                .count();
+            report.summary.records += 1;
+            report.summary.errors += errors;
+            report.summary.warnings += diagnostics.len() - errors;
+            if errors > 0 {
+                report.summary.invalid += 1;
+            } else {
+                report.summary.valid += 1;
+            }
+            report.diagnostics.extend(diagnostics);
+            Ok(())
+        },
+    );
+    report
+}
+
+/// One diagnostic per problem with a record that could not be read
+fn malformed(error: &RecordError) -> Vec<Diagnostic> {
+    if error.violations.is_empty() {
+        return vec![Diagnostic {
+            severity: Severity::Error,
+            index: error.index,
+            line: error.line,
+            column: error.column,
+            application_id: None,
+            field: None,
+            rule_id: None,
+            schema_path: None,
+            message: error.message.clone(),
+        }];
+    }
+    error
+        .violations
+        .iter()
+        .enumerate()
+        .map(|(position, violation)| Diagnostic {
+            severity: Severity::Error,
+            index: error.index,
+            line: error.line,
+            // The column is only known for the first violation
+            column: error.column.filter(|_| position == 0),
+            application_id: None,
+            field: Some(violation.field()).filter(|field| !field.is_empty()),
+            rule_id: None,
+            schema_path: Some(violation.schema_path.clone()),
+            message: violation.message.clone(),
+        })
+        .collect()
+}
+
+/// Every problem with a single well-formed record
+fn check_record(program: Option<&ProgramDefinition>, record: &Record) -> Vec<Diagnostic> {
+    let application = &record.application;
+    let mut diagnostics: Vec<Diagnostic> = application
+        .validate()
+        .into_iter()
+        .map(|issue| Diagnostic {
+            field: Some(issue.field),
+            message: issue.message,
+            ..diagnostic(Severity::Error, record)
+        })
+        .collect();
+
+    if application.funding.budget.is_empty() {
+        diagnostics.push(Diagnostic {
+            field: Some("funding.budget".to_string()),
+            message: "no budget lines back the requested amount".to_string(),
+            ..diagnostic(Severity::Warning, record)
+        });
+    }
+
+    let Some(program) = program else {
+        return diagnostics;
+    };
+
+    for outcome in program.evaluate(application) {
+        if outcome.is_failure() {
+            diagnostics.push(Diagnostic {
+                field: program.rule_field(&outcome.rule_id),
+                rule_id: Some(outcome.rule_id),
+                message: outcome.reason,
+                ..diagnostic(Severity::Error, record)
+            });
+        }
+    }
+
+    if program.window.is_some() && application.submitted_at.is_none() {
+        diagnostics.push(Diagnostic {
+            field: Some("submitted_at".to_string()),
+            message: "no submission date; the funding window is checked against today".to_string(),
+            ..diagnostic(Severity::Warning, record)
+        });
+    }
+
+    if let Some(rubric) = &program.rubric {
+        for criterion_id in rubric.score(application).missing {
+            let field = rubric
+                .criteria
+                .iter()
+                .find(|criterion| criterion.id == criterion_id)
+                .and_then(|criterion| criterion.field.clone())
+                .unwrap_or_else(|| format!("ratings.{}", criterion_id));
+            diagnostics.push(Diagnostic {
+                field: Some(field),
+                message: format!("criterion '{}' has no value and scores 0", criterion_id),
+                ..diagnostic(Severity::Warning, record)
+            });
+        }
+    }
+
+    diagnostics
+}
+
+/// A diagnostic located at `record`, to be completed by the caller
+fn diagnostic(severity: Severity, record: &Record) -> Diagnostic {
+    Diagnostic {
+        severity,
+        index: record.index,
+        line: record.line,
+        column: None,
+        application_id: Some(record.application.id.clone()),
+        field: None,
+        rule_id: None,
+        schema_path: None,
+        message: String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::batch::read_ndjson;
+    use crate::program::FundingWindow;
+    use crate::scoring::{Criterion, Rubric, Scale};
+    use crate::test_support::{application, program};
+    use crate::{GrantApplication, GrantProgramProcessor, JournalStore, Store};
+
+    fn json(application: &GrantApplication) -> String {
+        serde_json::to_string(application).unwrap()
+    }
+
+    /// Diagnostics reduced to (severity, index, line, field)
+    fn located(report: &CheckReport) -> Vec<(Severity, usize, usize, Option<&str>)> {
+        report
+            .diagnostics
+            .iter()
+            .map(|d| (d.severity, d.index, d.line, d.field.as_deref()))
+            .collect()
+    }
+
+    #[test]
+    fn every_error_of_every_record_is_reported() {
+        // Breaks the schema twice
+        let mut malformed = application("APP-1", 10_000.0);
+        malformed.title = String::new();
+        malformed.applicant.email = "nobody".to_string();
+        // Fails three eligibility rules
+        let mut ineligible = application("APP-2", 60_000.0);
+        ineligible.program.id = "P2".to_string();
+        ineligible.documents = vec!["budget".to_string()];
+        // Budget lines do not add up
+        let mut unbalanced = application("APP-3", 10_000.0);
+        unbalanced.funding.budget[0].amount = 5.0;
+        let input = [&malformed, &ineligible, &unbalanced].map(json).join("\n");
+
+        let report = check_records(Some(&program(100_000.0)), read_ndjson(&input), 2);
+        let errors: Vec<(usize, Option<&str>, Option<&str>)> = report
+            .diagnostics
+            .iter()
+            .map(|d| (d.index, d.field.as_deref(), d.rule_id.as_deref()))
+            .collect();
+        assert_eq!(
+            errors,
+            [
+                (1, Some("title"), None),
+                (1, Some("applicant.email"), None),
+                (2, Some("program.id"), Some("program.match")),
+                (2, Some("funding.amount"), Some("program.max_award")),
+                (2, Some("documents"), Some("program.documents")),
+                (3, Some("funding.budget"), None),
+            ]
+        );
+        assert_eq!(
+            report.summary,
+            CheckSummary {
+                records: 3,
+                valid: 0,
+                invalid: 3,
+                errors: 6,
+                warnings: 0,
+            }
+        );
+        assert!(report.has_errors());
+    }
+
+    #[test]
+    fn diagnostics_carry_record_line_and_column() {
+        let mut invalid = serde_json::to_value(application("APP-3", 10_000.0)).unwrap();
+        invalid["funding"]["amount"] = serde_json::json!("lots");
+        invalid["title"] = serde_json::json!(7);
+        let input = format!(
+            "{}\n\n{{\"id\": \n{}\n\n{}\n",
+            json(&application("APP-1", 10_000.0)),
+            invalid,
+            json(&application("", 10_000.0))
+        );
+
+        let report = check_records(None, read_ndjson(&input), 1);
+        assert_eq!(
+            located(&report),
+            [
+                (Severity::Error, 2, 3, None),
+                (Severity::Error, 3, 4, Some("title")),
+                (Severity::Error, 3, 4, Some("funding.amount")),
+                (Severity::Error, 4, 6, Some("id")),
+            ]
+        );
+        let columns: Vec<Option<usize>> = report.diagnostics.iter().map(|d| d.column).collect();
+        assert!(columns[0].is_some());
+        assert_eq!(columns[2], None, "only the first violation has a column");
+        assert!(report.diagnostics[1].schema_path.is_some());
+        assert_eq!(report.diagnostics[3].application_id, None);
+        assert_eq!(report.summary.valid, 1);
+        assert_eq!(report.summary.invalid, 3);
+    }
+
+    #[test]
+    fn duplicate_ids_point_at_the_first_use() {
+        let input = [
+            json(&application("APP-1", 10_000.0)),
+            json(&application("APP-2", 10_000.0)),
+            json(&application("APP-1", 20_000.0)),
+        ]
+        .join("\n");
+
+        let report = check_records(None, read_ndjson(&input), 4);
+        assert_eq!(located(&report), [(Severity::Error, 3, 3, Some("id"))]);
+        assert_eq!(
+            report.diagnostics[0].message,
+            "'APP-1' was already used by record 1"
+        );
+        assert_eq!(report.summary.invalid, 1);
+    }
+
+    #[test]
+    fn warnings_do_not_make_a_record_invalid() {
+        let mut definition = program(100_000.0);
+        definition.window = Some(FundingWindow {
+            opens: "2000-01-01".parse().unwrap(),
+            closes: "2999-12-31".parse().unwrap(),
+        });
+        definition.rubric = Some(Rubric {
+            criteria: vec![Criterion {
+                id: "reach".to_string(),
+                name: String::new(),
+                weight: 1.0,
+                scale: Scale::default(),
+                field: None,
+                higher_is_better: true,
+            }],
+        });
+        let mut unbudgeted = application("APP-1", 10_000.0);
+        unbudgeted.funding.budget.clear();
+
+        let report = check_records(Some(&definition), read_ndjson(&json(&unbudgeted)), 1);
+        assert_eq!(
+            located(&report),
+            [
+                (Severity::Warning, 1, 1, Some("funding.budget")),
+                (Severity::Warning, 1, 1, Some("submitted_at")),
+                (Severity::Warning, 1, 1, Some("ratings.reach")),
+            ]
+        );
+        assert!(!report.has_errors());
+        assert_eq!(report.summary.valid, 1);
+        assert_eq!(report.summary.errors, 0);
+        assert_eq!(report.summary.warnings, 3);
+    }
+
+    #[test]
+    fn checking_leaves_the_store_untouched() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("store.jsonl");
+        let mut store = JournalStore::open(&path).unwrap();
+        store.set_processed_count(3).unwrap();
+        let journal = std::fs::read(&path).unwrap();
+
+        let processor =
+            GrantProgramProcessor::with_store(false, Some(program(100_000.0)), Box::new(store))
+                .unwrap();
+        let input = [
+            json(&application("APP-1", 10_000.0)),
+            json(&application("APP-2", 90_000.0)),
+        ]
+        .join("\n");
+        let report = processor.check(read_ndjson(&input));
+
+        assert_eq!(report.summary.records, 2);
+        assert_eq!(processor.stats().processed_count, 3);
+        assert_eq!(std::fs::read(&path).unwrap(), journal);
+        drop(processor);
+        let store = JournalStore::open(&path).unwrap();
+        assert!(store.applications().unwrap().is_empty());
+        assert_eq!(store.processed_count().unwrap(), 3);
+    }
+
+    #[test]
+    fn rendered_reports_keep_every_diagnostic() {
+        let mut invalid = application("APP-1", 10_000.0);
+        invalid.funding.budget[0].amount = 5.0;
+        let report = check_records(None, read_ndjson(&json(&invalid)), 1);
+
+        let ndjson = report.render(OutputFormat::Ndjson).unwrap();
+        let kinds: Vec<String> = ndjson
+            .lines()
+            .map(|line| {
+                serde_json::from_str::<serde_json::Value>(line).unwrap()["kind"].to_string()
+            })
+            .collect();
+        assert_eq!(kinds, ["\"diagnostic\"", "\"summary\""]);
+        assert_eq!(
+            report.diagnostics[0].to_string(),
+            "error in record 1 (line 1) at funding.budget: budget lines total 5.00 but 10000.00 was requested"
+        );
+    }
+}
@@ -0,0 +1,520 @@
+// src/concurrent.rs
+/*
+ * Processor that can be shared between threads and accept submissions in parallel
+ */
+
+use crate::stats::Tally;
+use crate::store::Store;
+use crate::{
+    refused, schema, ApplicationStatus, AuditAction, AuditLog, Evaluation, GrantApplication,
+    GrantError, GrantProgramProcessor, Lifecycle, ProcessResult, ProcessorStats, ProgramDefinition,
+    Result, StatusChange,
+};
+use log::{debug, warn};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+/// Number of independently locked parts of the lifecycle registry
+const SHARDS: usize = 16;
+
+/// Grant program processor that can be shared between threads
+///
+/// Unlike `GrantProgramProcessor`, every method takes `&self`, so the
+/// processor can sit in an `Arc` and take submissions from many threads at
+/// once. Item numbers come from an atomic counter, so each processed
+/// application gets a distinct number and `processed_count` never skips or
+/// repeats one. Lifecycles live in a registry split into shards keyed by
+/// application id: submissions of different applications rarely wait for
+/// each other, while two submissions of the same application are serialized
+/// and the second is refused as a resubmission.
+///
+/// Validation, rule evaluation and scoring run without holding any lock;
+/// writes to the store and audit log are serialized.
+#[derive(Debug)]
+pub struct SharedGrantProcessor {
+    /// Flag for verbose mode
+    pub verbose: bool,
+    /// Program every application is checked against, if any
+    pub program: Option<ProgramDefinition>,
+    /// Count of processed items
+    processed_count: AtomicUsize,
+    /// Lifecycle of every application seen, sharded by application id
+    shards: Box<[Mutex<HashMap<String, Lifecycle>>]>,
+    /// Where applications and status changes are persisted, if anywhere
+    store: Option<Mutex<Box<dyn Store>>>,
+    /// Log every decision is appended to, if any
+    audit_log: Option<Mutex<AuditLog>>,
+    /// What `stats` is built from besides the lifecycles
+    tally: Mutex<Tally>,
+}
+
+impl SharedGrantProcessor {
+    /// Create a new shared processor instance
+    ///
+    /// # Arguments
+    ///
+    /// * `verbose` - Flag to enable verbose mode
+    /// * `program` - Program definition applications are checked against
+    ///
+    /// # Returns
+    ///
+    /// A new `SharedGrantProcessor` instance
+    pub fn new(verbose: bool, program: Option<ProgramDefinition>) -> Self {
+        Self {
+            verbose,
+            program,
+            processed_count: AtomicUsize::new(0),
+            shards: (0..SHARDS).map(|_| Mutex::default()).collect(),
+            store: None,
+            audit_log: None,
+            tally: Mutex::default(),
+        }
+    }
+
+    /// Create a shared processor that persists its state in a store
+    ///
+    /// # Arguments
+    ///
+    /// * `verbose` - Flag to enable verbose mode
+    /// * `program` - Program definition applications are checked against
+    /// * `store` - Store to load from and write to
+    ///
+    /// # Returns
+    ///
+    /// A new `SharedGrantProcessor`, or the store's error if it cannot be read
+    pub fn with_store(
+        verbose: bool,
+        program: Option<ProgramDefinition>,
+        store: Box<dyn Store>,
+    ) -> Result<Self> {
+        let processor = Self::new(verbose, program);
+        processor
+            .processed_count
+            .store(store.processed_count()?, Ordering::SeqCst);
+        for lifecycle in store.lifecycles()? {
+            processor
+                .shard(&lifecycle.application_id)
+                .insert(lifecycle.application_id.clone(), lifecycle);
+        }
+        Ok(Self {
+            tally: Mutex::new(Tally::load(processor.program.as_ref(), store.as_ref())?),
+            store: Some(Mutex::new(store)),
+            ..processor
+        })
+    }
+
+    /// Append every processing step, rule evaluation, score and status
+    /// change from now on to `audit_log`
+    pub fn set_audit_log(&mut self, audit_log: AuditLog) {
+        self.audit_log = Some(Mutex::new(audit_log));
+    }
+
+    /// Number of applications processed so far
+    pub fn processed_count(&self) -> usize {
+        self.processed_count.load(Ordering::SeqCst)
+    }
+
+    /// Parse a JSON encoded application and process it
+    ///
+    /// # Arguments
+    ///
+    /// * `data` - JSON document describing a single `GrantApplication`
+    ///
+    /// # Returns
+    ///
+    /// A `ProcessResult` instance describing the result of processing
+    pub fn process(&self, data: &str) -> Result<ProcessResult> {
+        if self.verbose {
+            debug!("Processing data of length: {}", data.len());
+        }
+
+        let application = schema::parse_application(serde_json::from_str(data)?)
+            .map_err(|violations| schema::violation_error(&violations))?;
+        self.process_application(&application)
+    }
+
+    /// Evaluate a single application
+    ///
+    /// Behaves like `GrantProgramProcessor::process_application`, and may be
+    /// called from several threads at once.
+    ///
+    /// # Arguments
+    ///
+    /// * `application` - Application to evaluate
+    ///
+    /// # Returns
+    ///
+    /// A `ProcessResult` whose `data` summarises the evaluated application,
+    /// or the store's error if the outcome cannot be persisted
+    pub fn process_application(&self, application: &GrantApplication) -> Result<ProcessResult> {
+        if self.verbose {
+            debug!("Processing application {}", application.id);
+        }
+
+        {
+            let mut lifecycles = self.shard(&application.id);
+            if !lifecycles.contains_key(&application.id) {
+                let lifecycle = Lifecycle::new(&application.id);
+                self.record_change(&application.id, &lifecycle.history[0])?;
+                lifecycles.insert(application.id.clone(), lifecycle);
+            }
+            match self.transition_in(
+                &mut lifecycles,
+                &application.id,
+                ApplicationStatus::Submitted,
+                "received",
+            ) {
+                Ok(_) => {}
+                Err(error @ GrantError::Transition { from, .. }) => {
+                    warn!("{}", error);
+                    let (result, details) = refused(&application.id, &error, from);
+                    self.audit(vec![(AuditAction::Processed, details)], &application.id)?;
+                    return Ok(result);
+                }
+                Err(error) => return Err(error),
+            }
+        }
+
+        let item_number = self.processed_count.fetch_add(1, Ordering::SeqCst) + 1;
+        if let Some(store) = &self.store {
+            let mut store = lock(store);
+            store.save_application(application)?;
+            // Items finishing out of order must not move the count backwards
+            if store.processed_count()? < item_number {
+                store.set_processed_count(item_number)?;
+            }
+        }
+
+        let evaluation = Evaluation::new(self.program.as_ref(), application);
+        self.audit(evaluation.audit_entries(item_number)?, &application.id)?;
+        let (status, reason) = evaluation.verdict(item_number);
+        self.transition(&application.id, status, reason)?;
+        lock(&self.tally).record(application, &evaluation);
+        Ok(evaluation.into_result(application, status, item_number))
+    }
+
+    /// Move an application the processor has seen to a new status
+    ///
+    /// # Arguments
+    ///
+    /// * `application_id` - Application to move
+    /// * `to` - Status to move to
+    /// * `reason` - Why the status changes
+    ///
+    /// # Returns
+    ///
+    /// The new status, a validation error for an unknown application, or
+    /// `GrantError::Transition` if the move is not allowed
+    pub fn transition(
+        &self,
+        application_id: &str,
+        to: ApplicationStatus,
+        reason: impl Into<String>,
+    ) -> Result<ApplicationStatus> {
+        let mut lifecycles = self.shard(application_id);
+        self.transition_in(&mut lifecycles, application_id, to, reason)
+    }
+
+    /// Lifecycle of an application the processor has seen
+    pub fn lifecycle(&self, application_id: &str) -> Option<Lifecycle> {
+        self.shard(application_id).get(application_id).cloned()
+    }
+
+    /// Lifecycles of every application seen, ordered by application id
+    pub fn lifecycles(&self) -> Vec<Lifecycle> {
+        let mut lifecycles: Vec<Lifecycle> = self
+            .shards
+            .iter()
+            .flat_map(|shard| lock(shard).values().cloned().collect::<Vec<_>>())
+            .collect();
+        lifecycles.sort_by(|a, b| a.application_id.cmp(&b.application_id));
+        lifecycles
+    }
+
+    /// Statistics about the applications the processor has seen
+    ///
+    /// # Returns
+    ///
+    /// The same figures as `GrantProgramProcessor::stats`
+    pub fn stats(&self) -> ProcessorStats {
+        let lifecycles = self.lifecycles();
+        lock(&self.tally).stats(self.processed_count(), self.verbose, &lifecycles)
+    }
+
+    /// Get statistics about the processor
+    ///
+    /// # Returns
+    ///
+    /// A JSON value with the same fields as `GrantProgramProcessor::get_stats`
+    pub fn get_stats(&self) -> serde_json::Value {
+        serde_json::to_value(self.stats())
+            .expect("ProcessorStats has string keys and always serializes")
+    }
+
+    /// Registry shard holding the lifecycle of `application_id`
+    fn shard(&self, application_id: &str) -> MutexGuard<'_, HashMap<String, Lifecycle>> {
+        let mut hasher = DefaultHasher::new();
+        application_id.hash(&mut hasher);
+        lock(&self.shards[hasher.finish() as usize % self.shards.len()])
+    }
+
+    /// Apply a status change under the shard lock and record it before the
+    /// lock is released, so changes to one application are persisted in order
+    fn transition_in(
+        &self,
+        lifecycles: &mut HashMap<String, Lifecycle>,
+        application_id: &str,
+        to: ApplicationStatus,
+        reason: impl Into<String>,
+    ) -> Result<ApplicationStatus> {
+        let lifecycle = lifecycles.get_mut(application_id).ok_or_else(|| {
+            GrantError::validation(
+                "application_id",
+                format!("unknown application '{}'", application_id),
+            )
+        })?;
+        let change = lifecycle.transition(to, reason)?.clone();
+        self.record_change(application_id, &change)?;
+        Ok(change.to)
+    }
+
+    /// Persist and audit a status change that has been applied in memory
+    fn record_change(&self, application_id: &str, change: &StatusChange) -> Result<()> {
+        if let Some(store) = &self.store {
+            lock(store).record_transition(application_id, change)?;
+        }
+        self.audit(
+            vec![(
+                AuditAction::StatusChanged,
+                serde_json::to_value(change).map_err(GrantError::output)?,
+            )],
+            application_id,
+        )
+    }
+
+    /// Append entries for one application without other entries in between
+    fn audit(
+        &self,
+        entries: Vec<(AuditAction, serde_json::Value)>,
+        application_id: &str,
+    ) -> Result<()> {
+        if let Some(audit_log) = &self.audit_log {
+            let mut audit_log = lock(audit_log);
+            for (action, details) in entries {
+                audit_log.append(action, Some(application_id), details)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl From<GrantProgramProcessor> for SharedGrantProcessor {
+    fn from(processor: GrantProgramProcessor) -> Self {
+        let shared = Self::new(processor.verbose, processor.program);
+        shared
+            .processed_count
+            .store(processor.processed_count, Ordering::SeqCst);
+        for (application_id, lifecycle) in processor.lifecycles {
+            shared
+                .shard(&application_id)
+                .insert(application_id, lifecycle);
+        }
+        Self {
+            store: processor.store.map(Mutex::new),
+            audit_log: processor.audit_log.map(Mutex::new),
+            tally: Mutex::new(processor.tally),
+            ..shared
+        }
+    }
+}
+
+/// Lock a mutex, carrying on with the data if another thread panicked
+/// while holding it
+pub(crate) fn lock<T: ?Sized>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audit;
+    use crate::store::MemoryStore;
+    use crate::test_support::{application, program};
+    use std::sync::{Arc, Barrier};
+    use std::thread;
+
+    const THREADS: usize = 8;
+
+    /// Submit `ids` from `THREADS` threads released together; each thread
+    /// submits every id, starting at a different one
+    fn submit_from_threads(
+        processor: &Arc<SharedGrantProcessor>,
+        ids: &[String],
+    ) -> Vec<ProcessResult> {
+        let barrier = Arc::new(Barrier::new(THREADS));
+        let handles: Vec<_> = (0..THREADS)
+            .map(|thread| {
+                let (processor, barrier) = (Arc::clone(processor), Arc::clone(&barrier));
+                let ids = ids.to_vec();
+                thread::spawn(move || {
+                    barrier.wait();
+                    (0..ids.len())
+                        .map(|offset| {
+                            let id = &ids[(thread + offset) % ids.len()];
+                            processor
+                                .process_application(&application(id, 10_000.0))
+                                .unwrap()
+                        })
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+        handles
+            .into_iter()
+            .flat_map(|handle| handle.join().unwrap())
+            .collect()
+    }
+
+    fn item_number(result: &ProcessResult) -> Option<u64> {
+        result.data.as_ref()?.get("item_number")?.as_u64()
+    }
+
+    fn ids(count: usize) -> Vec<String> {
+        (0..count)
+            .map(|index| format!("APP-{:03}", index))
+            .collect()
+    }
+
+    #[test]
+    fn the_same_application_from_many_threads_is_accepted_once() {
+        let processor = Arc::new(SharedGrantProcessor::new(false, Some(program(100_000.0))));
+        let results = submit_from_threads(&processor, &ids(1));
+
+        let accepted: Vec<&ProcessResult> = results
+            .iter()
+            .filter(|result| item_number(result).is_some())
+            .collect();
+        assert_eq!(accepted.len(), 1);
+        assert_eq!(item_number(accepted[0]), Some(1));
+        assert_eq!(results.len() - accepted.len(), THREADS - 1);
+        assert!(results
+            .iter()
+            .filter(|result| item_number(result).is_none())
+            .all(|result| !result.success));
+        assert_eq!(processor.processed_count(), 1);
+
+        let lifecycle = processor.lifecycle("APP-000").unwrap();
+        let statuses: Vec<ApplicationStatus> =
+            lifecycle.history.iter().map(|change| change.to).collect();
+        assert_eq!(
+            statuses,
+            [
+                ApplicationStatus::Draft,
+                ApplicationStatus::Submitted,
+                ApplicationStatus::UnderReview
+            ]
+        );
+    }
+
+    #[test]
+    fn different_applications_get_distinct_item_numbers() {
+        let processor = Arc::new(SharedGrantProcessor::new(false, Some(program(100_000.0))));
+        let handles: Vec<_> = ids(64)
+            .chunks(64 / THREADS)
+            .map(|chunk| {
+                let (processor, chunk) = (Arc::clone(&processor), chunk.to_vec());
+                thread::spawn(move || {
+                    chunk
+                        .iter()
+                        .map(|id| {
+                            processor
+                                .process_application(&application(id, 10_000.0))
+                                .unwrap()
+                        })
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+        let mut numbers: Vec<u64> = handles
+            .into_iter()
+            .flat_map(|handle| handle.join().unwrap())
+            .map(|result| item_number(&result).unwrap())
+            .collect();
+
+        numbers.sort_unstable();
+        assert_eq!(numbers, (1..=64).collect::<Vec<_>>());
+        assert_eq!(processor.processed_count(), 64);
+        let lifecycles = processor.lifecycles();
+        assert_eq!(lifecycles.len(), 64);
+        assert!(lifecycles
+            .iter()
+            .all(|lifecycle| lifecycle.status == ApplicationStatus::UnderReview));
+        assert!(lifecycles
+            .windows(2)
+            .all(|pair| pair[0].application_id < pair[1].application_id));
+    }
+
+    #[test]
+    fn overlapping_submissions_keep_store_and_audit_log_consistent() {
+        let dir = tempfile::tempdir().unwrap();
+        let audit_path = dir.path().join("audit.jsonl");
+        let mut processor = SharedGrantProcessor::with_store(
+            false,
+            Some(program(100_000.0)),
+            Box::new(MemoryStore::new()),
+        )
+        .unwrap();
+        processor.set_audit_log(AuditLog::open(&audit_path).unwrap());
+        let processor = Arc::new(processor);
+
+        let results = submit_from_threads(&processor, &ids(20));
+        let mut numbers: Vec<u64> = results.iter().filter_map(item_number).collect();
+        numbers.sort_unstable();
+        assert_eq!(numbers, (1..=20).collect::<Vec<_>>());
+        assert_eq!(results.len(), THREADS * 20);
+        assert_eq!(processor.processed_count(), 20);
+
+        let store = lock(processor.store.as_ref().unwrap());
+        assert_eq!(store.processed_count().unwrap(), 20);
+        assert_eq!(store.applications().unwrap().len(), 20);
+        assert_eq!(store.lifecycles().unwrap(), processor.lifecycles());
+        drop(store);
+        assert!(audit::verify(&audit_path).unwrap().entries > 20);
+
+        let stats = processor.stats();
+        assert_eq!(stats.processed_count, 20);
+        assert_eq!(stats.statuses.get("under_review"), Some(&20));
+    }
+
+    #[test]
+    fn converting_a_processor_keeps_its_state() {
+        let mut single = GrantProgramProcessor::new(false, Some(program(100_000.0)));
+        single
+            .process_application(&application("A1", 10_000.0))
+            .unwrap();
+        let shared = SharedGrantProcessor::from(single);
+
+        assert_eq!(shared.processed_count(), 1);
+        let repeated = shared
+            .process_application(&application("A1", 10_000.0))
+            .unwrap();
+        assert!(!repeated.success);
+        let next = shared
+            .process_application(&application("A2", 10_000.0))
+            .unwrap();
+        assert_eq!(item_number(&next), Some(2));
+        assert_eq!(shared.get_stats()["processed_count"], 2);
+    }
+
+    #[test]
+    fn unknown_applications_cannot_move() {
+        let processor = SharedGrantProcessor::new(false, None);
+        let error = processor
+            .transition("missing", ApplicationStatus::Withdrawn, "test")
+            .unwrap_err();
+        assert!(matches!(error, GrantError::Validation { .. }));
+    }
+}
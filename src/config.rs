@@ -0,0 +1,555 @@
+// src/config.rs
+/*
+ * Run settings resolved from defaults, a config file, environment variables
+ * and command-line flags
+ */
+
+use crate::review::Aggregation;
+use crate::{GrantError, InputFormat, OutputFormat, Result, Stage, Strategy};
+use serde::{Deserialize, Serialize, Serializer};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// Prefix of the environment variables that override configuration values
+pub const ENV_PREFIX: &str = "GRANTPROGRAM_";
+
+/// Environment variable naming the config file
+pub const CONFIG_ENV: &str = "GRANTPROGRAM_CONFIG";
+
+/// Config file picked up from the working directory when none is named
+pub const DEFAULT_CONFIG_FILE: &str = "grantprogram.toml";
+
+/// Keys that can be set in a config file, the environment or on the command
+/// line, in the order `config show` lists them
+pub const CONFIG_KEYS: &[&str] = &[
+    "verbose",
+    "program",
+    "store",
+    "audit_log",
+    "format",
+    "input_format",
+    "mapping",
+    "continue_on_error",
+    "strategy",
+    "panel",
+    "aggregation",
+    "threads",
+];
+
+/// Settings for a single `run`
+///
+/// Every field except `stage` and `input` can be layered with `Config::resolve`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Config {
+    /// Flag for verbose mode
+    pub verbose: bool,
+    /// How far the run goes
+    #[serde(skip)]
+    pub stage: Stage,
+    /// Input file path; when `None`, stdin is read unless a store is
+    /// configured and the stage is past `Ingest`, in which case only the
+    /// stored applications are ranked
+    #[serde(skip)]
+    pub input: Option<String>,
+    /// Layout of the input
+    pub input_format: InputFormat,
+    /// Program definition file (JSON or TOML)
+    pub program: Option<String>,
+    /// Column mapping file (JSON or TOML) for CSV input
+    pub mapping: Option<String>,
+    /// Strategy used to allocate the program pool
+    pub strategy: Strategy,
+    /// Skip malformed records instead of failing the run
+    pub continue_on_error: bool,
+    /// Review panel file (JSON or TOML) whose scores rank the applications
+    pub panel: Option<String>,
+    /// How the panel's reviews are combined
+    pub aggregation: Aggregation,
+    /// Journal file persisting applications, status changes and awards across
+    /// runs; applications already submitted there cannot be submitted again
+    pub store: Option<String>,
+    /// Hash-chained audit log every decision is appended to
+    pub audit_log: Option<String>,
+    /// Format reports are rendered in
+    pub format: OutputFormat,
+    /// Worker threads evaluating the input; 0 means one per CPU
+    pub threads: usize,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            verbose: false,
+            stage: Stage::Allocate,
+            input: None,
+            input_format: InputFormat::Auto,
+            program: None,
+            mapping: None,
+            strategy: Strategy::Greedy,
+            continue_on_error: false,
+            panel: None,
+            aggregation: Aggregation::Mean,
+            store: None,
+            audit_log: None,
+            format: OutputFormat::Json,
+            threads: 1,
+        }
+    }
+}
+
+/// A partial set of settings from one source; unset fields leave the value
+/// of the layers below in place
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigLayer {
+    /// Flag for verbose mode
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub verbose: Option<bool>,
+    /// Program definition file
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub program: Option<String>,
+    /// Store journal file
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub store: Option<String>,
+    /// Audit log file
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub audit_log: Option<String>,
+    /// Report format
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub format: Option<OutputFormat>,
+    /// Input layout
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub input_format: Option<InputFormat>,
+    /// Column mapping file
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mapping: Option<String>,
+    /// Skip malformed records
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub continue_on_error: Option<bool>,
+    /// Allocation strategy
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub strategy: Option<Strategy>,
+    /// Review panel file
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub panel: Option<String>,
+    /// Review aggregation method
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub aggregation: Option<Aggregation>,
+    /// Worker threads
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub threads: Option<usize>,
+}
+
+impl ConfigLayer {
+    /// Load a layer from a JSON or TOML config file
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path of the config file
+    ///
+    /// # Returns
+    ///
+    /// The parsed layer; unknown keys are rejected
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        crate::load_document(path.as_ref())
+    }
+
+    /// Build a layer from `GRANTPROGRAM_*` environment variables
+    ///
+    /// Each key maps to the upper-cased key with the prefix, e.g.
+    /// `GRANTPROGRAM_AUDIT_LOG`. Flags accept `true`/`false`, `1`/`0`,
+    /// `yes`/`no` and `on`/`off`; `threads` takes a whole number; other values
+    /// are taken as written.
+    ///
+    /// # Arguments
+    ///
+    /// * `vars` - Variables to read, usually `std::env::vars()`
+    ///
+    /// # Returns
+    ///
+    /// The layer, or a validation error naming the variable whose value is invalid
+    pub fn from_env_vars<I>(vars: I) -> Result<Self>
+    where
+        I: IntoIterator<Item = (String, String)>,
+    {
+        let mut fields = serde_json::Map::new();
+        for (name, raw) in vars {
+            let Some(key) = name
+                .strip_prefix(ENV_PREFIX)
+                .map(str::to_ascii_lowercase)
+                .filter(|key| CONFIG_KEYS.contains(&key.as_str()))
+            else {
+                continue;
+            };
+            let value = match key.as_str() {
+                "verbose" | "continue_on_error" => {
+                    serde_json::Value::Bool(parse_flag(&raw).ok_or_else(|| {
+                        GrantError::validation(&name, format!("'{}' is not true or false", raw))
+                    })?)
+                }
+                "threads" => {
+                    serde_json::Value::from(raw.trim().parse::<usize>().map_err(|_| {
+                        GrantError::validation(&name, format!("'{}' is not a whole number", raw))
+                    })?)
+                }
+                _ => serde_json::Value::String(raw.trim().to_string()),
+            };
+
+            // Check each variable on its own so the error names it
+            let single = serde_json::json!({ key.as_str(): value.clone() });
+            serde_json::from_value::<ConfigLayer>(single)
+                .map_err(|e| GrantError::validation(&name, e.to_string()))?;
+            fields.insert(key, value);
+        }
+        serde_json::from_value(serde_json::Value::Object(fields))
+            .map_err(|e| GrantError::validation("environment", e.to_string()))
+    }
+}
+
+fn parse_flag(raw: &str) -> Option<bool> {
+    match raw.trim().to_ascii_lowercase().as_str() {
+        "1" | "true" | "yes" | "on" => Some(true),
+        "0" | "false" | "no" | "off" | "" => Some(false),
+        _ => None,
+    }
+}
+
+/// Where a configuration value came from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigSource {
+    /// Built-in default
+    Default,
+    /// Config file
+    File(PathBuf),
+    /// Environment variable
+    Env(String),
+    /// Command-line flag
+    Cli,
+}
+
+impl fmt::Display for ConfigSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigSource::Default => f.write_str("default"),
+            ConfigSource::File(path) => write!(f, "file {}", path.display()),
+            ConfigSource::Env(name) => write!(f, "env {}", name),
+            ConfigSource::Cli => f.write_str("command line"),
+        }
+    }
+}
+
+impl Serialize for ConfigSource {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+/// One effective setting and where it came from
+#[derive(Debug, Clone, Serialize)]
+pub struct ConfigEntry {
+    /// Config key
+    pub key: String,
+    /// Effective value
+    pub value: serde_json::Value,
+    /// Layer the value was taken from
+    pub source: ConfigSource,
+}
+
+/// Settings after layering, with the source of each value
+#[derive(Debug, Clone)]
+pub struct ResolvedConfig {
+    /// Effective settings
+    pub config: Config,
+    /// Config file that was read, if any
+    pub file: Option<PathBuf>,
+    sources: BTreeMap<String, ConfigSource>,
+}
+
+impl ResolvedConfig {
+    /// Source of the value of `key`
+    pub fn source(&self, key: &str) -> ConfigSource {
+        self.sources
+            .get(key)
+            .cloned()
+            .unwrap_or(ConfigSource::Default)
+    }
+
+    /// Every layered setting with its effective value and source
+    pub fn entries(&self) -> Result<Vec<ConfigEntry>> {
+        let values = serde_json::to_value(&self.config).map_err(GrantError::output)?;
+        Ok(CONFIG_KEYS
+            .iter()
+            .map(|key| ConfigEntry {
+                key: key.to_string(),
+                value: values.get(key).cloned().unwrap_or_default(),
+                source: self.source(key),
+            })
+            .collect())
+    }
+}
+
+impl Config {
+    /// Resolve settings from every layer
+    ///
+    /// Later layers win: built-in defaults, then the config file, then
+    /// `GRANTPROGRAM_*` environment variables, then `cli`. The config file is
+    /// `file` if given, else the one named by `GRANTPROGRAM_CONFIG`, else
+    /// `grantprogram.toml` in the working directory if it exists.
+    ///
+    /// # Arguments
+    ///
+    /// * `file` - Config file named on the command line
+    /// * `cli` - Settings given as command-line flags
+    ///
+    /// # Returns
+    ///
+    /// The effective settings with the source of each value
+    pub fn resolve(file: Option<&Path>, cli: ConfigLayer) -> Result<ResolvedConfig> {
+        Self::resolve_with_vars(file, std::env::vars().collect(), cli)
+    }
+
+    /// Resolve settings with `vars` standing in for the process environment
+    fn resolve_with_vars(
+        file: Option<&Path>,
+        vars: Vec<(String, String)>,
+        cli: ConfigLayer,
+    ) -> Result<ResolvedConfig> {
+        let file = match file {
+            Some(path) => Some(path.to_path_buf()),
+            None => match vars.iter().find(|(name, _)| name == CONFIG_ENV) {
+                Some((_, path)) => Some(PathBuf::from(path)),
+                None => Some(PathBuf::from(DEFAULT_CONFIG_FILE)).filter(|path| path.is_file()),
+            },
+        };
+
+        let mut layers = Vec::new();
+        if let Some(path) = &file {
+            layers.push((ConfigSource::File(path.clone()), ConfigLayer::load(path)?));
+        }
+        let env = ConfigLayer::from_env_vars(vars)?;
+        layers.push((ConfigSource::Env(ENV_PREFIX.to_string()), env));
+        layers.push((ConfigSource::Cli, cli));
+
+        let mut merged =
+            match serde_json::to_value(Config::default()).map_err(GrantError::output)? {
+                serde_json::Value::Object(fields) => fields,
+                _ => serde_json::Map::new(),
+            };
+        let mut sources = BTreeMap::new();
+        for (source, layer) in layers {
+            let serde_json::Value::Object(fields) =
+                serde_json::to_value(&layer).map_err(GrantError::output)?
+            else {
+                continue;
+            };
+            for (key, value) in fields {
+                let source = match &source {
+                    ConfigSource::Env(prefix) => {
+                        ConfigSource::Env(format!("{}{}", prefix, key.to_ascii_uppercase()))
+                    }
+                    other => other.clone(),
+                };
+                sources.insert(key.clone(), source);
+                merged.insert(key, value);
+            }
+        }
+
+        let config = serde_json::from_value(serde_json::Value::Object(merged))
+            .map_err(|e| GrantError::validation("config", e.to_string()))?;
+        Ok(ResolvedConfig {
+            config,
+            file,
+            sources,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn vars(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect()
+    }
+
+    fn invalid_field(error: GrantError) -> String {
+        match error {
+            GrantError::Validation { field, .. } => field,
+            other => panic!("expected a validation error, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn env_vars_map_to_their_keys() {
+        let layer = ConfigLayer::from_env_vars(vars(&[
+            ("GRANTPROGRAM_VERBOSE", "yes"),
+            ("GRANTPROGRAM_CONTINUE_ON_ERROR", "0"),
+            ("GRANTPROGRAM_THREADS", " 4 "),
+            ("GRANTPROGRAM_AUDIT_LOG", " audit.jsonl "),
+            ("GRANTPROGRAM_STRATEGY", "floor-proportional"),
+            ("GRANTPROGRAM_FORMAT", "json-pretty"),
+        ]))
+        .unwrap();
+
+        assert_eq!(
+            layer,
+            ConfigLayer {
+                verbose: Some(true),
+                continue_on_error: Some(false),
+                threads: Some(4),
+                audit_log: Some("audit.jsonl".to_string()),
+                strategy: Some(Strategy::FloorProportional),
+                format: Some(OutputFormat::JsonPretty),
+                ..ConfigLayer::default()
+            }
+        );
+    }
+
+    #[test]
+    fn unrelated_and_unknown_env_vars_are_ignored() {
+        let layer = ConfigLayer::from_env_vars(vars(&[
+            ("PATH", "/usr/bin"),
+            ("VERBOSE", "true"),
+            ("GRANTPROGRAM_CONFIG", "other.toml"),
+            ("GRANTPROGRAM_COLOUR", "never"),
+        ]))
+        .unwrap();
+        assert_eq!(layer, ConfigLayer::default());
+    }
+
+    #[test]
+    fn flag_spellings_are_accepted() {
+        for (raw, expected) in [
+            ("1", true),
+            ("TRUE", true),
+            ("on", true),
+            ("no", false),
+            ("Off", false),
+            ("", false),
+        ] {
+            let layer = ConfigLayer::from_env_vars(vars(&[("GRANTPROGRAM_VERBOSE", raw)])).unwrap();
+            assert_eq!(layer.verbose, Some(expected), "{raw:?}");
+        }
+    }
+
+    #[test]
+    fn invalid_env_values_name_their_variable() {
+        for (name, raw) in [
+            ("GRANTPROGRAM_VERBOSE", "maybe"),
+            ("GRANTPROGRAM_THREADS", "-2"),
+            ("GRANTPROGRAM_STRATEGY", "lottery"),
+            ("GRANTPROGRAM_AGGREGATION", "vote"),
+        ] {
+            let error =
+                ConfigLayer::from_env_vars(vars(&[("GRANTPROGRAM_VERBOSE", "true"), (name, raw)]))
+                    .unwrap_err();
+            assert_eq!(invalid_field(error), name);
+        }
+    }
+
+    #[test]
+    fn defaults_apply_when_no_layer_sets_a_key() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("empty.toml");
+        fs::write(&file, "").unwrap();
+        let resolved =
+            Config::resolve_with_vars(Some(&file), Vec::new(), ConfigLayer::default()).unwrap();
+
+        for key in CONFIG_KEYS {
+            assert_eq!(resolved.source(key), ConfigSource::Default, "{key}");
+        }
+        let (config, default) = (&resolved.config, Config::default());
+        assert_eq!(config.threads, default.threads);
+        assert_eq!(config.strategy, default.strategy);
+        assert_eq!(config.format, default.format);
+        assert_eq!(config.program, None);
+    }
+
+    #[test]
+    fn later_layers_override_earlier_ones() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("grantprogram.toml");
+        fs::write(
+            &file,
+            "verbose = true\nthreads = 2\nstrategy = \"knapsack\"\nprogram = \"file.json\"\n",
+        )
+        .unwrap();
+        let env = vars(&[
+            ("GRANTPROGRAM_THREADS", "6"),
+            ("GRANTPROGRAM_PROGRAM", "env.json"),
+        ]);
+        let cli = ConfigLayer {
+            program: Some("cli.json".to_string()),
+            ..ConfigLayer::default()
+        };
+
+        let resolved = Config::resolve_with_vars(Some(&file), env, cli).unwrap();
+        let config = &resolved.config;
+        assert!(config.verbose);
+        assert_eq!(config.strategy, Strategy::Knapsack);
+        assert_eq!(config.threads, 6);
+        assert_eq!(config.program.as_deref(), Some("cli.json"));
+        assert_eq!(config.format, OutputFormat::Json);
+
+        assert_eq!(resolved.file.as_deref(), Some(file.as_path()));
+        assert_eq!(resolved.source("verbose"), ConfigSource::File(file.clone()));
+        assert_eq!(
+            resolved.source("strategy"),
+            ConfigSource::File(file.clone())
+        );
+        assert_eq!(
+            resolved.source("threads"),
+            ConfigSource::Env("GRANTPROGRAM_THREADS".to_string())
+        );
+        assert_eq!(resolved.source("program"), ConfigSource::Cli);
+        assert_eq!(resolved.source("format"), ConfigSource::Default);
+    }
+
+    #[test]
+    fn the_config_file_can_be_named_in_the_environment() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("settings.json");
+        fs::write(&file, r#"{ "aggregation": "median" }"#).unwrap();
+        let env = vars(&[(CONFIG_ENV, file.to_str().unwrap())]);
+
+        let resolved = Config::resolve_with_vars(None, env, ConfigLayer::default()).unwrap();
+        assert_eq!(resolved.file.as_deref(), Some(file.as_path()));
+        assert_eq!(resolved.config.aggregation, Aggregation::Median);
+        assert_eq!(resolved.source("aggregation"), ConfigSource::File(file));
+    }
+
+    #[test]
+    fn unknown_keys_in_the_config_file_are_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("grantprogram.toml");
+        fs::write(&file, "verbos = true\n").unwrap();
+
+        assert!(
+            Config::resolve_with_vars(Some(&file), Vec::new(), ConfigLayer::default()).is_err()
+        );
+    }
+
+    #[test]
+    fn entries_follow_the_key_order() {
+        let cli = ConfigLayer {
+            threads: Some(3),
+            ..ConfigLayer::default()
+        };
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("empty.toml");
+        fs::write(&file, "").unwrap();
+        let resolved = Config::resolve_with_vars(Some(&file), Vec::new(), cli).unwrap();
+
+        let entries = resolved.entries().unwrap();
+        let keys: Vec<&str> = entries.iter().map(|entry| entry.key.as_str()).collect();
+        assert_eq!(keys, CONFIG_KEYS);
+        let threads = entries.iter().find(|entry| entry.key == "threads").unwrap();
+        assert_eq!(threads.value, serde_json::json!(3));
+        assert_eq!(threads.source, ConfigSource::Cli);
+    }
+}
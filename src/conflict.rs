@@ -0,0 +1,278 @@
+// src/conflict.rs
+/*
+ * Conflict-of-interest detection between reviewers and applications
+ */
+
+use crate::review::Reviewer;
+use crate::GrantApplication;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Email providers shared by unrelated people; a common domain here is no conflict
+const PUBLIC_EMAIL_DOMAINS: &[&str] = &[
+    "gmail.com",
+    "googlemail.com",
+    "outlook.com",
+    "hotmail.com",
+    "live.com",
+    "yahoo.com",
+    "icloud.com",
+    "me.com",
+    "aol.com",
+    "proton.me",
+    "protonmail.com",
+    "gmx.com",
+    "mail.com",
+];
+
+/// Why a reviewer may not review an application
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictKind {
+    /// Reviewer and applicant belong to the same institution
+    SameInstitution,
+    /// Reviewer has applied together with the applicant before
+    PriorCoApplicant,
+    /// Reviewer declared the conflict themselves
+    Declared,
+    /// Reviewer and applicant use the same (non-public) email domain
+    SharedEmailDomain,
+}
+
+impl fmt::Display for ConflictKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ConflictKind::SameInstitution => "same_institution",
+            ConflictKind::PriorCoApplicant => "prior_co_applicant",
+            ConflictKind::Declared => "declared",
+            ConflictKind::SharedEmailDomain => "shared_email_domain",
+        };
+        f.write_str(name)
+    }
+}
+
+/// A conflict of interest found between a reviewer and an application
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Conflict {
+    /// Application concerned
+    pub application_id: String,
+    /// Reviewer concerned
+    pub reviewer_id: String,
+    /// Kind of conflict
+    pub reason: ConflictKind,
+    /// Human readable explanation
+    pub detail: String,
+}
+
+/// Find every conflict of interest between a reviewer and an application
+///
+/// Names, institutions and email domains are compared case-insensitively.
+///
+/// # Arguments
+///
+/// * `reviewer` - Prospective reviewer
+/// * `application` - Application to be reviewed
+///
+/// # Returns
+///
+/// One entry per kind of conflict found; empty when the pairing is clean
+pub fn detect(reviewer: &Reviewer, application: &GrantApplication) -> Vec<Conflict> {
+    let applicant = &application.applicant;
+    let conflict = |reason: ConflictKind, detail: String| Conflict {
+        application_id: application.id.clone(),
+        reviewer_id: reviewer.id.clone(),
+        reason,
+        detail,
+    };
+    let mut conflicts = Vec::new();
+
+    if let (Some(ours), Some(theirs)) = (&reviewer.institution, &applicant.institution) {
+        if same(ours, theirs) {
+            conflicts.push(conflict(
+                ConflictKind::SameInstitution,
+                format!("reviewer and applicant are both at {}", theirs.trim()),
+            ));
+        }
+    }
+
+    if let Some(partner) = reviewer
+        .co_applicants
+        .iter()
+        .find(|partner| same(partner, &applicant.id) || same(partner, &applicant.name))
+    {
+        conflicts.push(conflict(
+            ConflictKind::PriorCoApplicant,
+            format!(
+                "reviewer previously applied together with {}",
+                partner.trim()
+            ),
+        ));
+    }
+
+    let declared_targets = [
+        Some(application.id.as_str()),
+        Some(applicant.id.as_str()),
+        Some(applicant.name.as_str()),
+        applicant.institution.as_deref(),
+    ];
+    if let Some(declared) = reviewer.conflicts.iter().find(|declared| {
+        declared_targets
+            .iter()
+            .flatten()
+            .any(|target| same(declared, target))
+    }) {
+        conflicts.push(conflict(
+            ConflictKind::Declared,
+            format!("reviewer declared a conflict with {}", declared.trim()),
+        ));
+    }
+
+    if let (Some(ours), Some(theirs)) = (
+        email_domain(&reviewer.email),
+        email_domain(&applicant.email),
+    ) {
+        if ours == theirs && !PUBLIC_EMAIL_DOMAINS.contains(&ours.as_str()) {
+            conflicts.push(conflict(
+                ConflictKind::SharedEmailDomain,
+                format!("reviewer and applicant share the email domain {}", ours),
+            ));
+        }
+    }
+
+    conflicts
+}
+
+fn same(a: &str, b: &str) -> bool {
+    let (a, b) = (a.trim(), b.trim());
+    !a.is_empty() && a.to_lowercase() == b.to_lowercase()
+}
+
+fn email_domain(email: &str) -> Option<String> {
+    let (_, domain) = email.trim().rsplit_once('@')?;
+    (!domain.is_empty()).then(|| domain.to_ascii_lowercase())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::application;
+
+    /// A reviewer built from `fields`, with an unrelated email by default
+    fn reviewer(fields: serde_json::Value) -> Reviewer {
+        let mut value = serde_json::json!({ "id": "R1", "email": "r@panel.example" });
+        value
+            .as_object_mut()
+            .unwrap()
+            .extend(fields.as_object().unwrap().clone());
+        serde_json::from_value(value).unwrap()
+    }
+
+    /// Kinds of conflict between `reviewer` and the fixture application
+    fn kinds(reviewer: &Reviewer) -> Vec<ConflictKind> {
+        detect(reviewer, &application("APP-1", 10_000.0))
+            .into_iter()
+            .map(|conflict| conflict.reason)
+            .collect()
+    }
+
+    #[test]
+    fn an_unrelated_reviewer_has_no_conflict() {
+        let reviewer = reviewer(serde_json::json!({
+            "institution": "Blue",
+            "co_applicants": ["B2"],
+            "conflicts": ["APP-2", ""]
+        }));
+        assert!(kinds(&reviewer).is_empty());
+    }
+
+    #[test]
+    fn same_institution_is_matched_case_insensitively() {
+        let conflicts = detect(
+            &reviewer(serde_json::json!({ "institution": "  GREEN " })),
+            &application("APP-1", 10_000.0),
+        );
+        assert_eq!(
+            conflicts,
+            [Conflict {
+                application_id: "APP-1".to_string(),
+                reviewer_id: "R1".to_string(),
+                reason: ConflictKind::SameInstitution,
+                detail: "reviewer and applicant are both at Green".to_string(),
+            }]
+        );
+        assert!(kinds(&reviewer(serde_json::json!({ "institution": " " }))).is_empty());
+    }
+
+    #[test]
+    fn prior_co_applicants_match_applicant_id_or_name() {
+        for partner in ["a1", "green ORG"] {
+            let found = detect(
+                &reviewer(serde_json::json!({ "co_applicants": ["B2", partner] })),
+                &application("APP-1", 10_000.0),
+            );
+            assert_eq!(found.len(), 1, "{partner}");
+            assert_eq!(found[0].reason, ConflictKind::PriorCoApplicant);
+            assert_eq!(
+                found[0].detail,
+                format!("reviewer previously applied together with {partner}")
+            );
+        }
+    }
+
+    #[test]
+    fn declared_conflicts_match_application_applicant_or_institution() {
+        for declared in ["app-1", "A1", "Green Org", "green"] {
+            let reviewer = reviewer(serde_json::json!({ "conflicts": [declared] }));
+            assert_eq!(kinds(&reviewer), [ConflictKind::Declared], "{declared}");
+        }
+    }
+
+    #[test]
+    fn a_shared_private_email_domain_is_a_conflict() {
+        let colleague = reviewer(serde_json::json!({ "email": " Jo@GREEN.org " }));
+        let found = detect(&colleague, &application("APP-1", 10_000.0));
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].reason, ConflictKind::SharedEmailDomain);
+        assert_eq!(
+            found[0].detail,
+            "reviewer and applicant share the email domain green.org"
+        );
+
+        let other = reviewer(serde_json::json!({ "email": "jo@lab.green.org" }));
+        assert!(kinds(&other).is_empty());
+        let malformed = reviewer(serde_json::json!({ "email": "green.org" }));
+        assert!(kinds(&malformed).is_empty());
+    }
+
+    #[test]
+    fn public_email_domains_are_exempt() {
+        let mut application = application("APP-1", 10_000.0);
+        application.applicant.email = "applicant@gmail.com".to_string();
+
+        let reviewer = reviewer(serde_json::json!({ "email": "reviewer@Gmail.COM" }));
+        assert!(detect(&reviewer, &application).is_empty());
+    }
+
+    #[test]
+    fn every_kind_found_is_reported_in_order() {
+        let reviewer = reviewer(serde_json::json!({
+            "institution": "Green",
+            "co_applicants": ["A1"],
+            "conflicts": ["APP-1"],
+            "email": "jo@green.org"
+        }));
+        assert_eq!(
+            kinds(&reviewer),
+            [
+                ConflictKind::SameInstitution,
+                ConflictKind::PriorCoApplicant,
+                ConflictKind::Declared,
+                ConflictKind::SharedEmailDomain
+            ]
+        );
+        assert_eq!(
+            ConflictKind::SharedEmailDomain.to_string(),
+            "shared_email_domain"
+        );
+    }
+}
@@ -0,0 +1,442 @@
+// src/csv_import.rs
+/*
+ * CSV import of grant applications through a column mapping
+ */
+
+use crate::batch::{Record, RecordError, RecordResult};
+use crate::schema::{self, SchemaViolation};
+use crate::Result;
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// Prefix of mapping targets that add a budget line for the named category
+const BUDGET_PREFIX: &str = "funding.budget.";
+
+/// Date layouts tried, in order, when no explicit `date_format` is set
+const DATE_FORMATS: &[&str] = &["%Y-%m-%d", "%m/%d/%Y", "%d.%m.%Y"];
+
+/// How CSV columns map onto `GrantApplication` fields
+///
+/// Targets are dotted field paths such as `applicant.name` or
+/// `funding.amount`. A target of `funding.budget.<category>` turns the column
+/// into a budget line for that category.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ColumnMapping {
+    /// Header name to field path; headers not listed are used as field paths directly
+    #[serde(default)]
+    pub columns: BTreeMap<String, String>,
+    /// Explicit chrono format for date columns
+    #[serde(default)]
+    pub date_format: Option<String>,
+    /// Separator for list columns such as `documents`
+    #[serde(default = "default_list_separator")]
+    pub list_separator: char,
+    /// Columns with these headers are skipped
+    #[serde(default)]
+    pub ignore: Vec<String>,
+}
+
+fn default_list_separator() -> char {
+    ';'
+}
+
+impl ColumnMapping {
+    /// Load a column mapping from a JSON or TOML file
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path of the mapping file
+    ///
+    /// # Returns
+    ///
+    /// The parsed `ColumnMapping`
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        crate::load_document(path.as_ref())
+    }
+
+    fn target<'a>(&'a self, header: &'a str) -> Option<&'a str> {
+        if self.ignore.iter().any(|ignored| ignored == header) {
+            return None;
+        }
+        Some(
+            self.columns
+                .get(header)
+                .map_or(header.trim(), String::as_str),
+        )
+    }
+}
+
+/// Field types that need coercion from CSV text
+enum FieldKind {
+    Amount,
+    Integer,
+    Timestamp,
+    List,
+    Enum,
+    Text,
+}
+
+fn field_kind(target: &str) -> FieldKind {
+    match target {
+        "funding.amount" => FieldKind::Amount,
+        "funding.duration_months" => FieldKind::Integer,
+        "submitted_at" => FieldKind::Timestamp,
+        "documents" | "keywords" => FieldKind::List,
+        "applicant.org_type" => FieldKind::Enum,
+        _ if target.starts_with(BUDGET_PREFIX) => FieldKind::Amount,
+        _ if target.starts_with("ratings.") => FieldKind::Amount,
+        _ => FieldKind::Text,
+    }
+}
+
+/// Read applications from CSV text
+///
+/// # Arguments
+///
+/// * `input` - CSV contents; the first row must hold the headers
+/// * `mapping` - How headers map onto application fields
+///
+/// # Returns
+///
+/// One entry per data row. Rows that cannot be converted are reported with
+/// their row number (1-based, excluding the header), line, and the 1-based
+/// column of the offending cell where known.
+pub fn read_csv(input: &str, mapping: &ColumnMapping) -> Result<Vec<RecordResult>> {
+    let mut reader = csv::ReaderBuilder::new()
+        .flexible(true)
+        .from_reader(input.as_bytes());
+    let headers = reader.headers()?.clone();
+
+    let mut records = Vec::new();
+    for (position, row) in reader.records().enumerate() {
+        let index = position + 1;
+        let row = match row {
+            Ok(row) => row,
+            Err(e) => {
+                let line = e.position().map_or(0, |p| p.line() as usize);
+                records.push(Err(RecordError {
+                    index,
+                    line,
+                    column: None,
+                    message: e.to_string(),
+                    violations: Vec::new(),
+                }));
+                continue;
+            }
+        };
+        let line = row.position().map_or(0, |p| p.line() as usize);
+        records.push(convert_row(&headers, &row, mapping, index, line));
+    }
+
+    Ok(records)
+}
+
+fn convert_row(
+    headers: &csv::StringRecord,
+    row: &csv::StringRecord,
+    mapping: &ColumnMapping,
+    index: usize,
+    line: usize,
+) -> RecordResult {
+    let fail = |column: Option<usize>, message: String| RecordError {
+        index,
+        line,
+        column,
+        message,
+        violations: Vec::new(),
+    };
+
+    let mut document = serde_json::json!({});
+    let mut budget = Vec::new();
+    let mut columns = Vec::new();
+
+    for (column, (header, cell)) in headers.iter().zip(row.iter()).enumerate() {
+        let cell = cell.trim();
+        let Some(target) = mapping.target(header) else {
+            continue;
+        };
+        if cell.is_empty() {
+            continue;
+        }
+
+        let value = coerce(target, cell, mapping)
+            .map_err(|message| fail(Some(column + 1), format!("{}: {}", header, message)))?;
+
+        if let Some(category) = target.strip_prefix(BUDGET_PREFIX) {
+            columns.push((format!("funding.budget[{}]", budget.len()), column + 1));
+            budget.push(serde_json::json!({ "category": category, "amount": value }));
+        } else {
+            set_path(&mut document, target, value)
+                .map_err(|message| fail(Some(column + 1), format!("{}: {}", header, message)))?;
+            columns.push((target.to_string(), column + 1));
+        }
+    }
+
+    if !budget.is_empty() {
+        set_path(
+            &mut document,
+            "funding.budget",
+            serde_json::Value::Array(budget),
+        )
+        .map_err(|message| fail(None, message))?;
+    }
+
+    schema::parse_application(document)
+        .map(|application| Record {
+            index,
+            line,
+            application,
+        })
+        .map_err(|violations| {
+            // Point at the cell the first violation came from, if any
+            let field = violations.first().map(SchemaViolation::field);
+            let column = columns
+                .iter()
+                .find(|(target, _)| field.as_deref().is_some_and(|field| within(field, target)))
+                .map(|&(_, column)| column);
+            RecordError {
+                column,
+                message: schema::describe(&violations),
+                violations,
+                ..fail(None, String::new())
+            }
+        })
+}
+
+/// Whether `field` is `target` or a field nested inside it
+fn within(field: &str, target: &str) -> bool {
+    field
+        .strip_prefix(target)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with(['.', '[']))
+}
+
+fn coerce(
+    target: &str,
+    cell: &str,
+    mapping: &ColumnMapping,
+) -> std::result::Result<serde_json::Value, String> {
+    match field_kind(target) {
+        FieldKind::Amount => parse_amount(cell).map(|amount| serde_json::json!(amount)),
+        FieldKind::Integer => cell
+            .parse::<u64>()
+            .map(|number| serde_json::json!(number))
+            .map_err(|_| format!("'{}' is not a whole number", cell)),
+        FieldKind::Timestamp => parse_timestamp(cell, mapping.date_format.as_deref())
+            .map(|timestamp| serde_json::json!(timestamp)),
+        FieldKind::List => Ok(serde_json::json!(cell
+            .split(mapping.list_separator)
+            .map(str::trim)
+            .filter(|item| !item.is_empty())
+            .collect::<Vec<_>>())),
+        FieldKind::Enum => Ok(serde_json::json!(cell
+            .to_lowercase()
+            .replace([' ', '-'], "_"))),
+        FieldKind::Text => Ok(serde_json::json!(cell)),
+    }
+}
+
+/// Parse amounts such as `30000`, `$30,000.00` or `30 000`
+fn parse_amount(cell: &str) -> std::result::Result<f64, String> {
+    let cleaned: String = cell
+        .chars()
+        .filter(|c| !matches!(c, '$' | '€' | '£' | ',' | ' ' | '_'))
+        .collect();
+    cleaned
+        .parse::<f64>()
+        .ok()
+        .filter(|amount| amount.is_finite())
+        .ok_or_else(|| format!("'{}' is not an amount", cell))
+}
+
+fn parse_timestamp(cell: &str, format: Option<&str>) -> std::result::Result<DateTime<Utc>, String> {
+    if let Ok(timestamp) = DateTime::parse_from_rfc3339(cell) {
+        return Ok(timestamp.with_timezone(&Utc));
+    }
+
+    let formats: Vec<&str> = match format {
+        Some(format) => vec![format],
+        None => DATE_FORMATS.to_vec(),
+    };
+    for format in formats {
+        if let Ok(timestamp) = NaiveDateTime::parse_from_str(cell, format) {
+            return Ok(timestamp.and_utc());
+        }
+        if let Ok(date) = NaiveDate::parse_from_str(cell, format) {
+            return Ok(date.and_hms_opt(0, 0, 0).unwrap_or_default().and_utc());
+        }
+    }
+    Err(format!("'{}' is not a recognised date", cell))
+}
+
+fn set_path(
+    document: &mut serde_json::Value,
+    path: &str,
+    value: serde_json::Value,
+) -> std::result::Result<(), String> {
+    let mut current = document;
+    let mut parts = path.split('.').peekable();
+    while let Some(part) = parts.next() {
+        let object = current
+            .as_object_mut()
+            .ok_or_else(|| format!("field path '{}' conflicts with another column", path))?;
+        if parts.peek().is_none() {
+            object.insert(part.to_string(), value);
+            return Ok(());
+        }
+        current = object
+            .entry(part.to_string())
+            .or_insert_with(|| serde_json::json!({}));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HEADER: &str = "App ID,Title,Applicant ID,Org,Type,Email,Country,Program,Amount,Personnel,Equipment,Submitted,Docs";
+
+    fn mapping() -> ColumnMapping {
+        let columns = [
+            ("App ID", "id"),
+            ("Title", "title"),
+            ("Applicant ID", "applicant.id"),
+            ("Org", "applicant.name"),
+            ("Type", "applicant.org_type"),
+            ("Email", "applicant.email"),
+            ("Country", "applicant.country"),
+            ("Program", "program.id"),
+            ("Amount", "funding.amount"),
+            ("Personnel", "funding.budget.personnel"),
+            ("Equipment", "funding.budget.equipment"),
+            ("Submitted", "submitted_at"),
+            ("Docs", "documents"),
+        ];
+        ColumnMapping {
+            columns: columns
+                .iter()
+                .map(|(header, target)| (header.to_string(), target.to_string()))
+                .collect(),
+            list_separator: ';',
+            ..ColumnMapping::default()
+        }
+    }
+
+    fn read(rows: &[&str]) -> Vec<RecordResult> {
+        let input = std::iter::once(HEADER)
+            .chain(rows.iter().copied())
+            .collect::<Vec<_>>()
+            .join("\n");
+        read_csv(&input, &mapping()).unwrap()
+    }
+
+    fn error(record: &RecordResult) -> &RecordError {
+        record.as_ref().expect_err("row should be rejected")
+    }
+
+    #[test]
+    fn converts_rows_through_the_mapping() {
+        let records = read(&[
+            r#"APP-1,River cleanup,ORG-1,Green Org,Nonprofit,a@g.org,US,P1,"$30,000.00","20,000","10,000",2026-03-01,proposal; budget"#,
+            "APP-2,Lake study,ORG-2,Uni,For-Profit,b@u.edu,US,P1,20000,20000,,03/15/2026,",
+        ]);
+        assert_eq!(records.len(), 2);
+
+        let first = records[0].as_ref().unwrap();
+        assert_eq!((first.index, first.line), (1, 2));
+        let application = &first.application;
+        assert_eq!(application.id, "APP-1");
+        assert_eq!(application.funding.amount, 30000.0);
+        assert_eq!(application.funding.budget.len(), 2);
+        assert_eq!(application.funding.budget[1].category, "equipment");
+        assert_eq!(application.documents, ["proposal", "budget"]);
+        assert_eq!(
+            application.submitted_at.unwrap().to_rfc3339(),
+            "2026-03-01T00:00:00+00:00"
+        );
+
+        let second = &records[1].as_ref().unwrap().application;
+        assert_eq!(second.applicant.org_type, crate::ApplicantType::ForProfit);
+        assert_eq!(second.funding.budget.len(), 1);
+        assert!(second.documents.is_empty());
+    }
+
+    #[test]
+    fn reports_row_and_column_of_a_bad_cell() {
+        let records = read(&[
+            "APP-1,T,O1,Org,nonprofit,a@g.org,US,P1,100,100,,,",
+            "APP-2,T,O2,Org,nonprofit,a@g.org,US,P1,lots,,,,",
+            "APP-3,T,O3,Org,nonprofit,a@g.org,US,P1,100,100,,someday,",
+        ]);
+        assert!(records[0].is_ok());
+
+        let bad_amount = error(&records[1]);
+        assert_eq!((bad_amount.index, bad_amount.line), (2, 3));
+        assert_eq!(bad_amount.column, Some(9));
+        assert_eq!(bad_amount.message, "Amount: 'lots' is not an amount");
+
+        let bad_date = error(&records[2]);
+        assert_eq!((bad_date.index, bad_date.line), (3, 4));
+        assert_eq!(bad_date.column, Some(12));
+        assert!(bad_date.message.contains("not a recognised date"));
+    }
+
+    #[test]
+    fn points_schema_violations_at_their_column() {
+        let records = read(&["APP-1,T,O1,Org,charity,a@g.org,US,P1,100,100,,,"]);
+        let invalid = error(&records[0]);
+        assert_eq!(invalid.column, Some(5));
+        assert_eq!(invalid.violations[0].field(), "applicant.org_type");
+
+        let records = read(&["APP-1,T,O1,Org,nonprofit,a@g.org,US,,100,100,,,"]);
+        let missing = error(&records[0]);
+        assert_eq!(missing.column, None);
+        assert!(!missing.violations.is_empty());
+    }
+
+    #[test]
+    fn reports_conflicting_targets_with_their_column() {
+        let mut mapping = mapping();
+        mapping
+            .columns
+            .insert("Org".to_string(), "applicant".to_string());
+        mapping
+            .columns
+            .insert("Email".to_string(), "applicant.email".to_string());
+        let input = format!("{}\nAPP-1,T,O1,Org,nonprofit,a@g.org,US,P1,100,,,,", HEADER);
+        let records = read_csv(&input, &mapping).unwrap();
+        let conflict = error(&records[0]);
+        assert_eq!(conflict.column, Some(5));
+        assert!(conflict.message.contains("conflicts with another column"));
+    }
+
+    #[test]
+    fn skips_ignored_columns() {
+        let mut mapping = mapping();
+        mapping.ignore.push("Docs".to_string());
+        let input = format!(
+            "{}\nAPP-1,T,O1,Org,nonprofit,a@g.org,US,P1,100,100,,,proposal",
+            HEADER
+        );
+        let records = read_csv(&input, &mapping).unwrap();
+        assert!(records[0]
+            .as_ref()
+            .unwrap()
+            .application
+            .documents
+            .is_empty());
+    }
+
+    #[test]
+    fn parses_amounts_and_dates() {
+        assert_eq!(parse_amount("$30,000.50"), Ok(30000.5));
+        assert_eq!(parse_amount("€ 1 200"), Ok(1200.0));
+        assert!(parse_amount("NaN").is_err());
+        assert!(parse_timestamp("15.03.2026", None).is_ok());
+        assert!(parse_timestamp("2026-03-15T10:00:00+02:00", None).is_ok());
+        assert!(parse_timestamp("2026/03/15", Some("%Y/%m/%d")).is_ok());
+        assert!(parse_timestamp("2026/03/15", None).is_err());
+    }
+}
@@ -0,0 +1,406 @@
+// src/error.rs
+/*
+ * Error type shared by the whole library
+ */
+
+use crate::batch::RecordError;
+use crate::{schema, ApplicationStatus};
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Boxed underlying error kept as the `source` of a `GrantError`
+pub type BoxedSource = Box<dyn Error + Send + Sync>;
+
+/// Everything that can go wrong while processing grant applications
+#[derive(Debug)]
+pub enum GrantError {
+    /// Reading or writing a file or stream failed
+    Io {
+        /// File involved, if the failure was not on stdin/stdout
+        path: Option<PathBuf>,
+        /// Underlying I/O error
+        source: io::Error,
+    },
+    /// Input or a definition file could not be parsed
+    Parse {
+        /// File being parsed, if known
+        path: Option<PathBuf>,
+        /// 1-based index of the offending record within a batch
+        record: Option<usize>,
+        /// 1-based line of the problem
+        line: Option<usize>,
+        /// 1-based column of the problem
+        column: Option<usize>,
+        /// Description of the problem
+        message: String,
+        /// Underlying parser error
+        source: Option<BoxedSource>,
+    },
+    /// A value is well formed but not acceptable
+    Validation {
+        /// 1-based index of the offending record, when validating a batch
+        record: Option<usize>,
+        /// Dotted path of the offending field
+        field: String,
+        /// Description of the problem
+        message: String,
+    },
+    /// An eligibility rule is malformed or cannot be applied
+    Rule {
+        /// Identifier of the rule
+        rule_id: String,
+        /// Description of the problem
+        message: String,
+    },
+    /// The pool cannot be allocated as requested
+    Allocation {
+        /// Description of the problem
+        message: String,
+    },
+    /// An application cannot move from its current status to the requested one
+    Transition {
+        /// Application concerned
+        application_id: String,
+        /// Current status
+        from: ApplicationStatus,
+        /// Status that was requested
+        to: ApplicationStatus,
+    },
+    /// An audit log entry was edited, removed or moved
+    Audit {
+        /// 1-based line of the first broken entry
+        line: usize,
+        /// Sequence number the entry should have had
+        sequence: u64,
+        /// What is wrong with the entry
+        message: String,
+    },
+    /// Results could not be rendered in the requested format
+    Output {
+        /// Description of the problem
+        message: String,
+        /// Underlying writer error
+        source: Option<BoxedSource>,
+    },
+}
+
+impl GrantError {
+    /// Build an I/O error for the given file
+    pub fn io(path: impl AsRef<Path>, source: io::Error) -> Self {
+        GrantError::Io {
+            path: Some(path.as_ref().to_path_buf()),
+            source,
+        }
+    }
+
+    /// Build a parse error that only carries a message
+    pub fn parse(message: impl Into<String>) -> Self {
+        GrantError::Parse {
+            path: None,
+            record: None,
+            line: None,
+            column: None,
+            message: message.into(),
+            source: None,
+        }
+    }
+
+    /// Build a parse error for a file from the parser's own error
+    pub fn parse_file(path: impl AsRef<Path>, source: impl Into<BoxedSource>) -> Self {
+        let source = source.into();
+        GrantError::Parse {
+            path: Some(path.as_ref().to_path_buf()),
+            record: None,
+            line: None,
+            column: None,
+            message: source.to_string(),
+            source: Some(source),
+        }
+    }
+
+    /// Build a validation error for a field
+    pub fn validation(field: impl Into<String>, message: impl Into<String>) -> Self {
+        GrantError::Validation {
+            record: None,
+            field: field.into(),
+            message: message.into(),
+        }
+    }
+
+    /// Build an error for a rule
+    pub fn rule(rule_id: impl Into<String>, message: impl Into<String>) -> Self {
+        GrantError::Rule {
+            rule_id: rule_id.into(),
+            message: message.into(),
+        }
+    }
+
+    /// Build an allocation error
+    pub fn allocation(message: impl Into<String>) -> Self {
+        GrantError::Allocation {
+            message: message.into(),
+        }
+    }
+
+    /// Build an output error from the writer's own error
+    pub fn output(source: impl Into<BoxedSource>) -> Self {
+        let source = source.into();
+        GrantError::Output {
+            message: source.to_string(),
+            source: Some(source),
+        }
+    }
+
+    /// Attach the 1-based index of the batch record the error concerns
+    ///
+    /// Only parse and validation errors carry a record; others are returned unchanged.
+    pub fn with_record(mut self, index: usize) -> Self {
+        if let GrantError::Parse { record, .. } | GrantError::Validation { record, .. } = &mut self
+        {
+            *record = Some(index);
+        }
+        self
+    }
+
+    /// Whether the error is a write to a closed pipe (e.g. output piped to `head`)
+    pub fn is_broken_pipe(&self) -> bool {
+        matches!(self, GrantError::Io { source, .. } if source.kind() == io::ErrorKind::BrokenPipe)
+    }
+}
+
+impl fmt::Display for GrantError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GrantError::Io {
+                path: Some(path),
+                source,
+            } => write!(f, "I/O error on {}: {}", path.display(), source),
+            GrantError::Io { path: None, source } => write!(f, "I/O error: {}", source),
+            GrantError::Parse {
+                path,
+                record,
+                line,
+                column,
+                message,
+                ..
+            } => {
+                f.write_str("parse error")?;
+                if let Some(path) = path {
+                    write!(f, " in {}", path.display())?;
+                }
+                if let Some(record) = record {
+                    write!(f, " at record {}", record)?;
+                }
+                match (line, column) {
+                    (Some(line), Some(column)) => write!(f, " (line {}, column {})", line, column)?,
+                    (Some(line), None) => write!(f, " (line {})", line)?,
+                    _ => {}
+                }
+                write!(f, ": {}", message)
+            }
+            GrantError::Validation {
+                record,
+                field,
+                message,
+            } => match record {
+                Some(record) => write!(f, "invalid {} in record {}: {}", field, record, message),
+                None => write!(f, "invalid {}: {}", field, message),
+            },
+            GrantError::Rule { rule_id, message } => write!(f, "rule '{}': {}", rule_id, message),
+            GrantError::Allocation { message } => write!(f, "allocation failed: {}", message),
+            GrantError::Transition {
+                application_id,
+                from,
+                to,
+            } => {
+                write!(
+                    f,
+                    "application {} cannot move from {} to {}",
+                    application_id, from, to
+                )?;
+                let allowed = from.next_statuses();
+                if allowed.is_empty() {
+                    f.write_str(" (no further changes allowed)")
+                } else {
+                    let names: Vec<String> = allowed.iter().map(|s| s.to_string()).collect();
+                    write!(f, " (allowed: {})", names.join(", "))
+                }
+            }
+            GrantError::Audit {
+                line,
+                sequence,
+                message,
+            } => write!(
+                f,
+                "audit log broken at line {} (entry {}): {}",
+                line, sequence, message
+            ),
+            GrantError::Output { message, .. } => write!(f, "cannot write output: {}", message),
+        }
+    }
+}
+
+impl Error for GrantError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            GrantError::Io { source, .. } => Some(source),
+            GrantError::Parse {
+                source: Some(source),
+                ..
+            }
+            | GrantError::Output {
+                source: Some(source),
+                ..
+            } => Some(source.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for GrantError {
+    fn from(source: io::Error) -> Self {
+        GrantError::Io { path: None, source }
+    }
+}
+
+impl From<RecordError> for GrantError {
+    fn from(error: RecordError) -> Self {
+        // A record that was read but breaks the schema is invalid, not malformed
+        if !error.violations.is_empty() {
+            return schema::violation_error(&error.violations).with_record(error.index);
+        }
+        GrantError::Parse {
+            path: None,
+            record: Some(error.index),
+            line: Some(error.line),
+            column: error.column,
+            message: error.message,
+            source: None,
+        }
+    }
+}
+
+/// Failures reading JSON; serialization failures are mapped to
+/// `GrantError::output` where they happen
+impl From<serde_json::Error> for GrantError {
+    fn from(source: serde_json::Error) -> Self {
+        if source.is_io() {
+            return GrantError::Io {
+                path: None,
+                source: source.into(),
+            };
+        }
+        let (line, column) = (source.line(), source.column());
+        GrantError::Parse {
+            path: None,
+            record: None,
+            line: (line > 0).then_some(line),
+            column: (column > 0).then_some(column),
+            message: source.to_string(),
+            source: Some(Box::new(source)),
+        }
+    }
+}
+
+impl From<csv::Error> for GrantError {
+    fn from(source: csv::Error) -> Self {
+        let line = source.position().map(|p| p.line() as usize);
+        GrantError::Parse {
+            path: None,
+            record: None,
+            line,
+            column: None,
+            message: source.to_string(),
+            source: Some(Box::new(source)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn record_error(violations: Vec<schema::SchemaViolation>) -> RecordError {
+        RecordError {
+            index: 2,
+            line: 3,
+            column: Some(9),
+            message: "Amount: 'lots' is not an amount".to_string(),
+            violations,
+        }
+    }
+
+    #[test]
+    fn malformed_record_is_a_parse_error_with_its_position() {
+        let error = GrantError::from(record_error(Vec::new()));
+        assert!(matches!(
+            error,
+            GrantError::Parse {
+                record: Some(2),
+                line: Some(3),
+                column: Some(9),
+                ..
+            }
+        ));
+        assert_eq!(
+            error.to_string(),
+            "parse error at record 2 (line 3, column 9): Amount: 'lots' is not an amount"
+        );
+    }
+
+    #[test]
+    fn record_breaking_the_schema_is_a_validation_error_for_that_record() {
+        let mut document = serde_json::to_value(crate::test_support::application("A", 10.0))
+            .expect("fixture serializes");
+        document["applicant"]["org_type"] = "charity".into();
+        let violations = schema::parse_application(document).unwrap_err();
+
+        let error = GrantError::from(record_error(violations));
+        let GrantError::Validation { record, field, .. } = &error else {
+            panic!("expected a validation error, got {:?}", error);
+        };
+        assert_eq!(*record, Some(2));
+        assert_eq!(field, "applicant.org_type");
+        assert!(error
+            .to_string()
+            .starts_with("invalid applicant.org_type in record 2: "));
+    }
+
+    #[test]
+    fn with_record_only_touches_parse_and_validation_errors() {
+        let error = GrantError::validation("id", "must not be empty").with_record(4);
+        assert_eq!(
+            error.to_string(),
+            "invalid id in record 4: must not be empty"
+        );
+
+        let error = GrantError::allocation("no pool").with_record(4);
+        assert_eq!(error.to_string(), "allocation failed: no pool");
+    }
+
+    #[test]
+    fn json_syntax_errors_keep_their_position() {
+        let source = serde_json::from_str::<serde_json::Value>("{\n  \"a\": }").unwrap_err();
+        let error = GrantError::from(source);
+        assert!(matches!(
+            error,
+            GrantError::Parse {
+                line: Some(2),
+                column: Some(8),
+                ..
+            }
+        ));
+        assert!(error.source().is_some());
+    }
+
+    #[test]
+    fn serialization_failures_are_output_errors() {
+        // JSON object keys must be strings
+        let unserializable: HashMap<(u8, u8), u8> = [((1, 2), 3)].into_iter().collect();
+        let error = crate::server::Response::json(200, &unserializable).unwrap_err();
+        assert!(matches!(error, GrantError::Output { .. }), "{:?}", error);
+    }
+}
@@ -3,13 +3,456 @@
  * Core library for GrantProgram
  */
 
-use log::{info, error, debug};
-use serde::{Serialize, Deserialize};
+use chrono::{DateTime, Utc};
+use log::{debug, info, warn};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
 use std::fs;
+use std::io::{self, BufRead, BufReader};
 use std::path::Path;
+use std::time::{Duration, Instant};
+
+pub mod allocator;
+#[cfg(feature = "async")]
+pub mod async_processor;
+pub mod audit;
+pub mod batch;
+pub mod check;
+pub mod concurrent;
+pub mod config;
+pub mod conflict;
+pub mod csv_import;
+pub mod error;
+pub mod output;
+pub mod pool;
+pub mod program;
+pub mod review;
+pub mod rules;
+pub mod schema;
+pub mod scoring;
+pub mod server;
+pub mod stats;
+pub mod store;
+#[cfg(test)]
+mod test_support;
+
+pub use allocator::{Allocation, Award, ScoredApplication, Strategy};
+#[cfg(feature = "async")]
+pub use async_processor::{AsyncGrantProcessor, ProcessStream, Stream};
+pub use audit::{AuditAction, AuditEntry, AuditLog, AuditSummary};
+pub use batch::{
+    BatchReport, BatchSink, BatchSummary, InputFormat, Record, RecordError, RecordOutcome,
+};
+pub use check::{CheckReport, CheckSummary, Diagnostic, Severity};
+pub use concurrent::SharedGrantProcessor;
+pub use config::{Config, ConfigEntry, ConfigLayer, ConfigSource, ResolvedConfig};
+pub use conflict::{Conflict, ConflictKind};
+pub use csv_import::ColumnMapping;
+pub use error::GrantError;
+pub use output::{NdjsonSink, OutputFormat, RunReport, Standing};
+pub use pool::Progress;
+pub use program::{FundingWindow, ProgramDefinition};
+pub use review::{
+    Aggregation, Assignment, AssignmentStrategy, PanelScore, Review, ReviewPanel, Reviewer,
+};
+pub use rules::{Condition, Operator, Rule, RuleOutcome, Verdict};
+pub use schema::{JsonSchema, SchemaViolation};
+pub use scoring::{Criterion, CriterionScore, Rubric, Scale, ScoreCard};
+pub use server::{Request, Response, Server};
+pub use stats::{FundingTotals, Latency, ProcessorStats, ProgramStats, ScoreDistribution};
+pub use store::{JournalStore, MemoryStore, Store, StoreEvent};
 
 /// Custom result type for the library
-pub type Result<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+pub type Result<T> = std::result::Result<T, GrantError>;
+
+/// Read a JSON or TOML document, choosing the parser by file extension
+pub(crate) fn load_document<T: DeserializeOwned>(path: &Path) -> Result<T> {
+    let contents = fs::read_to_string(path).map_err(|e| GrantError::io(path, e))?;
+    let is_toml = path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("toml"));
+
+    if is_toml {
+        toml::from_str(&contents).map_err(|e| GrantError::parse_file(path, e))
+    } else {
+        serde_json::from_str(&contents).map_err(|e| GrantError::parse_file(path, e))
+    }
+}
+
+/// Write a JSON or TOML document, choosing the format by file extension
+pub(crate) fn save_document<T: Serialize>(path: &Path, document: &T) -> Result<()> {
+    let is_toml = path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("toml"));
+
+    let mut contents = if is_toml {
+        toml::to_string_pretty(document).map_err(GrantError::output)?
+    } else {
+        serde_json::to_string_pretty(document).map_err(GrantError::output)?
+    };
+    if !contents.ends_with('\n') {
+        contents.push('\n');
+    }
+    fs::write(path, contents).map_err(|e| GrantError::io(path, e))
+}
+
+/// Kind of organisation (or person) applying for a grant
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ApplicantType {
+    /// Registered nonprofit organisation
+    Nonprofit,
+    /// University or research institute
+    University,
+    /// Commercial company
+    ForProfit,
+    /// Government body or public agency
+    Government,
+    /// Individual applicant
+    Individual,
+    /// Anything not covered above
+    Other,
+}
+
+impl fmt::Display for ApplicantType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ApplicantType::Nonprofit => "nonprofit",
+            ApplicantType::University => "university",
+            ApplicantType::ForProfit => "for_profit",
+            ApplicantType::Government => "government",
+            ApplicantType::Individual => "individual",
+            ApplicantType::Other => "other",
+        };
+        f.write_str(name)
+    }
+}
+
+/// The person or organisation submitting an application
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Applicant {
+    /// Stable applicant identifier
+    pub id: String,
+    /// Display name of the applicant
+    pub name: String,
+    /// Kind of applicant
+    pub org_type: ApplicantType,
+    /// Contact email address
+    pub email: String,
+    /// Institution the applicant belongs to, if any
+    #[serde(default)]
+    pub institution: Option<String>,
+    /// ISO 3166 country code
+    pub country: String,
+}
+
+/// Reference to the program an application is submitted to
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Program {
+    /// Program identifier
+    pub id: String,
+    /// Program display name
+    #[serde(default)]
+    pub name: String,
+}
+
+/// A single line of a proposed budget
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BudgetLine {
+    /// Budget category (e.g. "personnel", "equipment")
+    pub category: String,
+    /// Free-form description of the expense
+    #[serde(default)]
+    pub description: String,
+    /// Amount requested for this line
+    pub amount: f64,
+}
+
+/// The money being asked for and how it will be spent
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FundingRequest {
+    /// Total amount requested
+    pub amount: f64,
+    /// ISO 4217 currency code
+    #[serde(default = "default_currency")]
+    pub currency: String,
+    /// Project duration in months
+    #[serde(default)]
+    pub duration_months: u32,
+    /// Itemised budget backing the request
+    #[serde(default)]
+    pub budget: Vec<BudgetLine>,
+}
+
+fn default_currency() -> String {
+    "USD".to_string()
+}
+
+impl FundingRequest {
+    /// Sum of all budget lines
+    pub fn budget_total(&self) -> f64 {
+        self.budget.iter().map(|line| line.amount).sum()
+    }
+}
+
+/// A grant application as submitted by an applicant
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GrantApplication {
+    /// Application identifier
+    pub id: String,
+    /// Project title
+    pub title: String,
+    /// Short project summary
+    #[serde(default)]
+    pub summary: String,
+    /// Who is applying
+    pub applicant: Applicant,
+    /// Program the application targets
+    pub program: Program,
+    /// Requested funding
+    pub funding: FundingRequest,
+    /// Names of the supporting documents attached
+    #[serde(default)]
+    pub documents: Vec<String>,
+    /// Subject keywords, matched against reviewer expertise
+    #[serde(default)]
+    pub keywords: Vec<String>,
+    /// Submission timestamp
+    #[serde(default)]
+    pub submitted_at: Option<DateTime<Utc>>,
+    /// Ratings per scoring criterion, keyed by criterion id
+    #[serde(default)]
+    pub ratings: BTreeMap<String, f64>,
+}
+
+/// A single problem found while validating an application
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ValidationIssue {
+    /// Dotted path of the offending field
+    pub field: String,
+    /// Human readable description of the problem
+    pub message: String,
+}
+
+impl ValidationIssue {
+    fn new(field: &str, message: impl Into<String>) -> Self {
+        Self {
+            field: field.to_string(),
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for ValidationIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.field, self.message)
+    }
+}
+
+impl GrantApplication {
+    /// Check the application for structural problems
+    ///
+    /// # Returns
+    ///
+    /// Every issue found; an empty list means the application is well formed
+    pub fn validate(&self) -> Vec<ValidationIssue> {
+        let mut issues = Vec::new();
+
+        if self.id.trim().is_empty() {
+            issues.push(ValidationIssue::new("id", "must not be empty"));
+        }
+        if self.title.trim().is_empty() {
+            issues.push(ValidationIssue::new("title", "must not be empty"));
+        }
+        if self.applicant.id.trim().is_empty() {
+            issues.push(ValidationIssue::new("applicant.id", "must not be empty"));
+        }
+        if self.applicant.name.trim().is_empty() {
+            issues.push(ValidationIssue::new("applicant.name", "must not be empty"));
+        }
+        if !self.applicant.email.contains('@') {
+            issues.push(ValidationIssue::new(
+                "applicant.email",
+                format!("'{}' is not a valid email address", self.applicant.email),
+            ));
+        }
+        if self.applicant.country.trim().is_empty() {
+            issues.push(ValidationIssue::new(
+                "applicant.country",
+                "must not be empty",
+            ));
+        }
+        if self.program.id.trim().is_empty() {
+            issues.push(ValidationIssue::new("program.id", "must not be empty"));
+        }
+        if !self.funding.amount.is_finite() || self.funding.amount <= 0.0 {
+            issues.push(ValidationIssue::new(
+                "funding.amount",
+                format!("must be a positive amount, got {}", self.funding.amount),
+            ));
+        }
+        for (index, line) in self.funding.budget.iter().enumerate() {
+            if !line.amount.is_finite() || line.amount < 0.0 {
+                issues.push(ValidationIssue::new(
+                    &format!("funding.budget[{}].amount", index),
+                    format!("must not be negative, got {}", line.amount),
+                ));
+            }
+        }
+        if !self.funding.budget.is_empty() {
+            let total = self.funding.budget_total();
+            if (total - self.funding.amount).abs() > 0.005 {
+                issues.push(ValidationIssue::new(
+                    "funding.budget",
+                    format!(
+                        "budget lines total {:.2} but {:.2} was requested",
+                        total, self.funding.amount
+                    ),
+                ));
+            }
+        }
+
+        issues
+    }
+}
+
+/// Stage an application has reached
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ApplicationStatus {
+    /// Being prepared by the applicant
+    Draft,
+    /// Received and awaiting checks
+    Submitted,
+    /// Passed the checks and is being assessed
+    UnderReview,
+    /// Selected for funding
+    Shortlisted,
+    /// Granted money
+    Awarded,
+    /// Turned down
+    Declined,
+    /// Pulled by the applicant
+    Withdrawn,
+    /// Archived; no further changes
+    Closed,
+}
+
+impl ApplicationStatus {
+    /// Statuses an application may move to next
+    pub fn next_statuses(self) -> &'static [ApplicationStatus] {
+        use ApplicationStatus::*;
+        match self {
+            Draft => &[Submitted, Withdrawn],
+            Submitted => &[UnderReview, Declined, Withdrawn],
+            UnderReview => &[Shortlisted, Declined, Withdrawn],
+            Shortlisted => &[Awarded, Declined, Withdrawn],
+            Awarded | Declined | Withdrawn => &[Closed],
+            Closed => &[],
+        }
+    }
+
+    /// Whether moving to `next` is allowed
+    pub fn can_transition_to(self, next: ApplicationStatus) -> bool {
+        self.next_statuses().contains(&next)
+    }
+}
+
+impl fmt::Display for ApplicationStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            ApplicationStatus::Draft => "draft",
+            ApplicationStatus::Submitted => "submitted",
+            ApplicationStatus::UnderReview => "under_review",
+            ApplicationStatus::Shortlisted => "shortlisted",
+            ApplicationStatus::Awarded => "awarded",
+            ApplicationStatus::Declined => "declined",
+            ApplicationStatus::Withdrawn => "withdrawn",
+            ApplicationStatus::Closed => "closed",
+        };
+        f.write_str(name)
+    }
+}
+
+/// A recorded change of status
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StatusChange {
+    /// Previous status; `None` when the lifecycle started
+    pub from: Option<ApplicationStatus>,
+    /// New status
+    pub to: ApplicationStatus,
+    /// When the change happened
+    pub at: DateTime<Utc>,
+    /// Why the status changed
+    #[serde(default)]
+    pub reason: String,
+}
+
+/// Status of one application together with every change that led to it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Lifecycle {
+    /// Application the lifecycle belongs to
+    pub application_id: String,
+    /// Current status
+    pub status: ApplicationStatus,
+    /// Status changes, oldest first
+    pub history: Vec<StatusChange>,
+}
+
+impl Lifecycle {
+    /// Start the lifecycle of an application in `Draft`
+    pub fn new(application_id: impl Into<String>) -> Self {
+        Self {
+            application_id: application_id.into(),
+            status: ApplicationStatus::Draft,
+            history: vec![StatusChange {
+                from: None,
+                to: ApplicationStatus::Draft,
+                at: Utc::now(),
+                reason: "created".to_string(),
+            }],
+        }
+    }
+
+    /// Move the application to a new status
+    ///
+    /// # Arguments
+    ///
+    /// * `to` - Status to move to
+    /// * `reason` - Why the status changes
+    ///
+    /// # Returns
+    ///
+    /// The recorded change, or `GrantError::Transition` if the current status
+    /// does not allow moving to `to`
+    pub fn transition(
+        &mut self,
+        to: ApplicationStatus,
+        reason: impl Into<String>,
+    ) -> Result<&StatusChange> {
+        if !self.status.can_transition_to(to) {
+            return Err(GrantError::Transition {
+                application_id: self.application_id.clone(),
+                from: self.status,
+                to,
+            });
+        }
+        debug!(
+            "Application {}: {} -> {}",
+            self.application_id, self.status, to
+        );
+        self.history.push(StatusChange {
+            from: Some(self.status),
+            to,
+            at: Utc::now(),
+            reason: reason.into(),
+        });
+        self.status = to;
+        Ok(&self.history[self.history.len() - 1])
+    }
+}
 
 /// Process result struct
 #[derive(Debug, Serialize, Deserialize)]
@@ -20,6 +463,169 @@ pub struct ProcessResult {
     pub message: String,
     /// Optional data associated with the result
     pub data: Option<serde_json::Value>,
+    /// Outcome of every eligibility rule evaluated
+    #[serde(default)]
+    pub rules: Vec<RuleOutcome>,
+}
+
+impl ProcessResult {
+    /// Composite score attached to `data`, if the application was scored
+    pub fn score(&self) -> Option<f64> {
+        self.data.as_ref()?.get("score")?.get("total")?.as_f64()
+    }
+}
+
+/// What checking an application against a program found
+///
+/// Shared by the processors so a submission is judged, audited and reported
+/// the same way whichever one handles it. Building one touches no processor
+/// state, so batches can be evaluated on several threads and committed in
+/// input order afterwards.
+pub(crate) struct Evaluation {
+    application_id: String,
+    issues: Vec<ValidationIssue>,
+    outcomes: Vec<RuleOutcome>,
+    score: Option<ScoreCard>,
+    failed: usize,
+    /// Time taken to validate, check and score the application
+    elapsed: Duration,
+}
+
+impl Evaluation {
+    /// Validate, check and score `application`, timing how long it takes
+    pub(crate) fn new(program: Option<&ProgramDefinition>, application: &GrantApplication) -> Self {
+        let started = Instant::now();
+        let outcomes = program
+            .map(|program| program.evaluate(application))
+            .unwrap_or_default();
+        Self {
+            application_id: application.id.clone(),
+            issues: application.validate(),
+            failed: outcomes.iter().filter(|o| o.is_failure()).count(),
+            score: program
+                .and_then(|program| program.rubric.as_ref())
+                .map(|rubric| rubric.score(application)),
+            outcomes,
+            elapsed: started.elapsed(),
+        }
+    }
+
+    /// Whether the application passed validation and every rule
+    pub(crate) fn success(&self) -> bool {
+        self.issues.is_empty() && self.failed == 0
+    }
+
+    /// Outcome message for the application counted as `item_number`
+    fn message(&self, item_number: usize) -> String {
+        if !self.issues.is_empty() {
+            format!(
+                "Application {} has {} validation issue(s)",
+                self.application_id,
+                self.issues.len()
+            )
+        } else if self.failed > 0 {
+            format!(
+                "Application {} failed {} of {} eligibility rule(s)",
+                self.application_id,
+                self.failed,
+                self.outcomes.len()
+            )
+        } else {
+            format!(
+                "Application {} processed as item #{}",
+                self.application_id, item_number
+            )
+        }
+    }
+
+    /// Audit entries recording the evaluation, in the order they are written
+    pub(crate) fn audit_entries(
+        &self,
+        item_number: usize,
+    ) -> Result<Vec<(AuditAction, serde_json::Value)>> {
+        let mut entries = Vec::with_capacity(self.outcomes.len() + 2);
+        for outcome in &self.outcomes {
+            entries.push((
+                AuditAction::RuleEvaluated,
+                serde_json::to_value(outcome).map_err(GrantError::output)?,
+            ));
+        }
+        if let Some(score) = &self.score {
+            entries.push((
+                AuditAction::Scored,
+                serde_json::json!({ "total": score.total, "missing": score.missing }),
+            ));
+        }
+        entries.push((
+            AuditAction::Processed,
+            serde_json::json!({
+                "success": self.success(),
+                "message": self.message(item_number),
+                "issues": self.issues,
+                "item_number": item_number
+            }),
+        ));
+        Ok(entries)
+    }
+
+    /// Status the application moves to, and why
+    pub(crate) fn verdict(&self, item_number: usize) -> (ApplicationStatus, String) {
+        if self.success() {
+            (
+                ApplicationStatus::UnderReview,
+                "passed validation and eligibility checks".to_string(),
+            )
+        } else {
+            (ApplicationStatus::Declined, self.message(item_number))
+        }
+    }
+
+    /// The `ProcessResult` reported for the evaluated application
+    pub(crate) fn into_result(
+        self,
+        application: &GrantApplication,
+        status: ApplicationStatus,
+        item_number: usize,
+    ) -> ProcessResult {
+        ProcessResult {
+            success: self.success(),
+            message: self.message(item_number),
+            data: Some(serde_json::json!({
+                "application_id": application.id,
+                "applicant_id": application.applicant.id,
+                "org_type": application.applicant.org_type,
+                "program_id": application.program.id,
+                "requested_amount": application.funding.amount,
+                "budget_total": application.funding.budget_total(),
+                "currency": application.funding.currency,
+                "issues": self.issues,
+                "score": self.score,
+                "status": status,
+                "processed_at": Utc::now().to_rfc3339(),
+                "item_number": item_number
+            })),
+            rules: self.outcomes,
+        }
+    }
+}
+
+/// Result and audit details for a submission its lifecycle does not allow
+pub(crate) fn refused(
+    application_id: &str,
+    error: &GrantError,
+    from: ApplicationStatus,
+) -> (ProcessResult, serde_json::Value) {
+    let result = ProcessResult {
+        success: false,
+        message: error.to_string(),
+        data: Some(serde_json::json!({
+            "application_id": application_id,
+            "status": from
+        })),
+        rules: Vec::new(),
+    };
+    let details = serde_json::json!({ "success": false, "message": error.to_string() });
+    (result, details)
 }
 
 /// Grant program processor
@@ -29,105 +635,1254 @@ pub struct GrantProgramProcessor {
     pub verbose: bool,
     /// Count of processed items
     pub processed_count: usize,
+    /// Program every application is checked against, if any
+    pub program: Option<ProgramDefinition>,
+    /// Worker threads evaluating batches; 0 means one per CPU. Results are
+    /// committed in input order whatever the number.
+    pub threads: usize,
+    /// Lifecycle of every application seen, keyed by application id
+    lifecycles: BTreeMap<String, Lifecycle>,
+    /// Where applications, status changes and awards are persisted, if anywhere
+    store: Option<Box<dyn Store>>,
+    /// Log every decision is appended to, if any
+    audit_log: Option<AuditLog>,
+    /// What `stats` is built from besides the lifecycles
+    tally: stats::Tally,
 }
 
 impl GrantProgramProcessor {
     /// Create a new processor instance
-    /// 
+    ///
     /// # Arguments
-    /// 
+    ///
     /// * `verbose` - Flag to enable verbose mode
-    /// 
+    /// * `program` - Program definition applications are checked against
+    ///
     /// # Returns
-    /// 
+    ///
     /// A new `GrantProgramProcessor` instance
-    pub fn new(verbose: bool) -> Self {
+    pub fn new(verbose: bool, program: Option<ProgramDefinition>) -> Self {
         Self {
             verbose,
             processed_count: 0,
+            program,
+            threads: 1,
+            lifecycles: BTreeMap::new(),
+            store: None,
+            audit_log: None,
+            tally: stats::Tally::default(),
         }
     }
 
-    /// Process the given data
-    /// 
+    /// Create a processor that persists its state in a store
+    ///
+    /// The processed count and every lifecycle recorded in the store are
+    /// loaded, so applications submitted in earlier runs keep their status.
+    ///
     /// # Arguments
-    /// 
-    /// * `data` - Data to process
-    /// 
+    ///
+    /// * `verbose` - Flag to enable verbose mode
+    /// * `program` - Program definition applications are checked against
+    /// * `store` - Store to load from and write to
+    ///
     /// # Returns
-    /// 
+    ///
+    /// A new `GrantProgramProcessor`, or the store's error if it cannot be read
+    pub fn with_store(
+        verbose: bool,
+        program: Option<ProgramDefinition>,
+        store: Box<dyn Store>,
+    ) -> Result<Self> {
+        let mut processor = Self::new(verbose, program);
+        processor.processed_count = store.processed_count()?;
+        processor.lifecycles = store
+            .lifecycles()?
+            .into_iter()
+            .map(|lifecycle| (lifecycle.application_id.clone(), lifecycle))
+            .collect();
+        processor.tally = stats::Tally::load(processor.program.as_ref(), store.as_ref())?;
+        processor.store = Some(store);
+        Ok(processor)
+    }
+
+    /// Store the processor writes to, if any
+    pub fn store(&self) -> Option<&dyn Store> {
+        self.store.as_deref()
+    }
+
+    /// Append every processing step, rule evaluation, score, status change
+    /// and allocation from now on to `audit_log`
+    pub fn set_audit_log(&mut self, audit_log: AuditLog) {
+        self.audit_log = Some(audit_log);
+    }
+
+    /// Audit log the processor writes to, if any
+    pub fn audit_log(&self) -> Option<&AuditLog> {
+        self.audit_log.as_ref()
+    }
+
+    fn audit(
+        &mut self,
+        action: AuditAction,
+        application_id: Option<&str>,
+        details: serde_json::Value,
+    ) -> Result<()> {
+        if let Some(audit_log) = self.audit_log.as_mut() {
+            audit_log.append(action, application_id, details)?;
+        }
+        Ok(())
+    }
+
+    /// Persist and audit a status change that has been applied in memory
+    fn record_change(&mut self, application_id: &str, change: &StatusChange) -> Result<()> {
+        if let Some(store) = self.store.as_mut() {
+            store.record_transition(application_id, change)?;
+        }
+        self.audit(
+            AuditAction::StatusChanged,
+            Some(application_id),
+            serde_json::to_value(change).map_err(GrantError::output)?,
+        )
+    }
+
+    /// Parse a JSON encoded application and process it
+    ///
+    /// # Arguments
+    ///
+    /// * `data` - JSON document describing a single `GrantApplication`
+    ///
+    /// # Returns
+    ///
     /// A `ProcessResult` instance describing the result of processing
     pub fn process(&mut self, data: &str) -> Result<ProcessResult> {
         if self.verbose {
             debug!("Processing data of length: {}", data.len());
         }
 
-        // Simulate processing
+        let application = schema::parse_application(serde_json::from_str(data)?)
+            .map_err(|violations| schema::violation_error(&violations))?;
+        self.process_application(&application)
+    }
+
+    /// Evaluate a single application
+    ///
+    /// The application is moved to `Submitted`, then to `UnderReview` if it
+    /// passes validation and every rule, or to `Declined` otherwise. An
+    /// application whose lifecycle does not allow resubmission is rejected
+    /// without being evaluated.
+    ///
+    /// # Arguments
+    ///
+    /// * `application` - Application to evaluate
+    ///
+    /// # Returns
+    ///
+    /// A `ProcessResult` whose `data` summarises the evaluated application,
+    /// or the store's error if the outcome cannot be persisted
+    pub fn process_application(&mut self, application: &GrantApplication) -> Result<ProcessResult> {
+        if self.verbose {
+            debug!("Processing application {}", application.id);
+        }
+        let evaluation = Evaluation::new(self.program.as_ref(), application);
+        self.commit(application, evaluation)
+    }
+
+    /// Record an evaluated submission: move it through its lifecycle, count,
+    /// store and audit it
+    fn commit(
+        &mut self,
+        application: &GrantApplication,
+        evaluation: Evaluation,
+    ) -> Result<ProcessResult> {
+        if !self.lifecycles.contains_key(&application.id) {
+            let lifecycle = Lifecycle::new(&application.id);
+            self.record_change(&application.id, &lifecycle.history[0])?;
+            self.lifecycles.insert(application.id.clone(), lifecycle);
+        }
+        match self.transition(&application.id, ApplicationStatus::Submitted, "received") {
+            Ok(_) => {}
+            Err(error @ GrantError::Transition { from, .. }) => {
+                warn!("{}", error);
+                let (result, details) = refused(&application.id, &error, from);
+                self.audit(AuditAction::Processed, Some(&application.id), details)?;
+                return Ok(result);
+            }
+            Err(error) => return Err(error),
+        }
+
         self.processed_count += 1;
-        
-        let result = ProcessResult {
-            success: true,
-            message: format!("Successfully processed item #{}", self.processed_count),
-            data: Some(serde_json::json!({
-                "length": data.len(),
-                "processed_at": chrono::Utc::now().to_rfc3339(),
-                "item_number": self.processed_count
-            })),
+        let item_number = self.processed_count;
+        if let Some(store) = self.store.as_mut() {
+            store.save_application(application)?;
+            store.set_processed_count(item_number)?;
+        }
+
+        for (action, details) in evaluation.audit_entries(item_number)? {
+            self.audit(action, Some(&application.id), details)?;
+        }
+        let (status, reason) = evaluation.verdict(item_number);
+        self.transition(&application.id, status, reason)?;
+        self.tally.record(application, &evaluation);
+        Ok(evaluation.into_result(application, status, item_number))
+    }
+
+    /// Check records against the program without processing them
+    ///
+    /// Nothing is saved, audited or counted, and no lifecycle changes. The
+    /// records are checked on `threads` workers.
+    ///
+    /// # Arguments
+    ///
+    /// * `records` - Records as read from the input, including malformed ones
+    ///
+    /// # Returns
+    ///
+    /// A `CheckReport` listing every error and warning
+    pub fn check<I>(&self, records: I) -> CheckReport
+    where
+        I: IntoIterator<Item = batch::RecordResult>,
+    {
+        check::check_records(self.program.as_ref(), records, self.threads)
+    }
+
+    /// Process every record of a batch
+    ///
+    /// # Arguments
+    ///
+    /// * `records` - Records as returned by `batch::read_records`
+    /// * `continue_on_error` - Keep going past malformed records instead of failing
+    ///
+    /// # Returns
+    ///
+    /// A `BatchReport` with one result per processed record, the malformed
+    /// records and a summary
+    pub fn process_batch(
+        &mut self,
+        records: &[batch::RecordResult],
+        continue_on_error: bool,
+    ) -> Result<BatchReport> {
+        if !continue_on_error {
+            reject_malformed(records)?;
+        }
+
+        let mut report = BatchReport::default();
+        report.summary = self.process_stream(
+            records.iter().cloned().map(Ok),
+            continue_on_error,
+            &mut report,
+        )?;
+        Ok(report)
+    }
+
+    /// Process records one at a time as they are read
+    ///
+    /// Unlike `process_batch`, records before the first malformed one have
+    /// already been processed when `continue_on_error` is off and the stream
+    /// is aborted.
+    ///
+    /// # Arguments
+    ///
+    /// * `records` - Records in input order, e.g. from a `batch::NdjsonReader`
+    /// * `continue_on_error` - Keep going past malformed records instead of failing
+    /// * `sink` - Receives every outcome as soon as it is available
+    ///
+    /// # Returns
+    ///
+    /// Aggregate counts for the records seen
+    pub fn process_stream<I>(
+        &mut self,
+        records: I,
+        continue_on_error: bool,
+        sink: &mut dyn BatchSink,
+    ) -> Result<BatchSummary>
+    where
+        I: IntoIterator<Item = Result<batch::RecordResult>>,
+    {
+        let records = records.into_iter();
+        let total = match records.size_hint() {
+            (lower, Some(upper)) if lower == upper => Some(upper),
+            _ => None,
         };
+        let started = Instant::now();
+        let mut summary = BatchSummary::default();
 
-        Ok(result)
+        // Records are evaluated on the workers and committed here in input order
+        let program = self.program.clone();
+        pool::map_ordered(
+            records,
+            self.threads,
+            |record| {
+                record.map(|record| {
+                    record.map(|record| {
+                        let evaluation = Evaluation::new(program.as_ref(), &record.application);
+                        (record, evaluation)
+                    })
+                })
+            },
+            |evaluated| {
+                summary.total += 1;
+                match evaluated? {
+                    Ok((record, evaluation)) => {
+                        if self.verbose {
+                            debug!("Processing application {}", record.application.id);
+                        }
+                        let result = self.commit(&record.application, evaluation)?;
+                        if result.success {
+                            summary.succeeded += 1;
+                        } else {
+                            summary.rejected += 1;
+                        }
+                        summary.processed += 1;
+                        let outcome = RecordOutcome {
+                            index: record.index,
+                            line: record.line,
+                            result,
+                        };
+                        sink.on_result(&record, outcome)?;
+                    }
+                    Err(error) => {
+                        if !continue_on_error {
+                            return Err(error.into());
+                        }
+                        warn!("Skipping malformed {}", error);
+                        summary.malformed += 1;
+                        sink.on_error(error)?;
+                    }
+                }
+                sink.on_progress(&Progress {
+                    done: summary.total,
+                    total,
+                    elapsed: started.elapsed(),
+                })
+            },
+        )?;
+        Ok(summary)
+    }
+
+    /// Move an application the processor has seen to a new status
+    ///
+    /// # Arguments
+    ///
+    /// * `application_id` - Application to move
+    /// * `to` - Status to move to
+    /// * `reason` - Why the status changes
+    ///
+    /// # Returns
+    ///
+    /// The new status, a validation error for an unknown application, or
+    /// `GrantError::Transition` if the move is not allowed
+    pub fn transition(
+        &mut self,
+        application_id: &str,
+        to: ApplicationStatus,
+        reason: impl Into<String>,
+    ) -> Result<ApplicationStatus> {
+        let lifecycle = self.lifecycles.get_mut(application_id).ok_or_else(|| {
+            GrantError::validation(
+                "application_id",
+                format!("unknown application '{}'", application_id),
+            )
+        })?;
+        let change = lifecycle.transition(to, reason)?.clone();
+        self.record_change(application_id, &change)?;
+        Ok(change.to)
+    }
+
+    /// Record the outcome of an allocation
+    ///
+    /// Funded applications move through `Shortlisted` to `Awarded`; the rest
+    /// are `Declined`. Every award is saved to the store and audited.
+    ///
+    /// # Arguments
+    ///
+    /// * `allocation` - Allocation over applications this processor has seen
+    ///
+    /// # Returns
+    ///
+    /// `Ok(())`, or the first transition or store error
+    pub fn apply_allocation(&mut self, allocation: &Allocation) -> Result<()> {
+        self.audit(
+            AuditAction::Allocated,
+            None,
+            serde_json::json!({
+                "strategy": allocation.strategy,
+                "pool": allocation.pool,
+                "total_awarded": allocation.total_awarded,
+                "remainder": allocation.remainder
+            }),
+        )?;
+        for award in &allocation.awards {
+            let id = award.application_id.as_str();
+            self.audit(
+                AuditAction::Awarded,
+                Some(id),
+                serde_json::to_value(award).map_err(GrantError::output)?,
+            )?;
+            if award.awarded > 0.0 {
+                self.transition(id, ApplicationStatus::Shortlisted, "ranked for funding")?;
+                self.transition(
+                    id,
+                    ApplicationStatus::Awarded,
+                    format!("awarded {:.2}", award.awarded),
+                )?;
+            } else {
+                self.transition(id, ApplicationStatus::Declined, award.rationale.clone())?;
+            }
+            if let Some(store) = self.store.as_mut() {
+                store.save_award(award)?;
+            }
+            self.tally.record_award(award);
+        }
+        Ok(())
+    }
+
+    /// Lifecycle of an application the processor has seen
+    pub fn lifecycle(&self, application_id: &str) -> Option<&Lifecycle> {
+        self.lifecycles.get(application_id)
+    }
+
+    /// Lifecycles of every application seen, ordered by application id
+    pub fn lifecycles(&self) -> impl Iterator<Item = &Lifecycle> {
+        self.lifecycles.values()
+    }
+
+    /// Statistics about the applications the processor has seen
+    ///
+    /// A processor opened on a store includes everything in the store.
+    ///
+    /// # Returns
+    ///
+    /// Counts by status and program, acceptance and rejection rates,
+    /// failed-rule frequencies, funding totals, the score distribution and
+    /// evaluation latency percentiles
+    pub fn stats(&self) -> ProcessorStats {
+        self.tally
+            .stats(self.processed_count, self.verbose, self.lifecycles.values())
     }
 
     /// Get statistics about the processor
-    /// 
+    ///
     /// # Returns
-    /// 
-    /// A JSON value containing the processor statistics
+    ///
+    /// A JSON value containing the processor statistics, as returned by `stats`
     pub fn get_stats(&self) -> serde_json::Value {
-        serde_json::json!({
-            "processed_count": self.processed_count,
-            "verbose": self.verbose
-        })
+        serde_json::to_value(self.stats())
+            .expect("ProcessorStats has string keys and always serializes")
+    }
+}
+
+/// How far a `run` takes the applications it handles
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Stage {
+    /// Validate, check and score the input; nothing is ranked or allocated
+    Ingest,
+    /// As `Ingest`, then rank every application under review
+    Score,
+    /// As `Score`, then allocate the program pool across the ranking
+    #[default]
+    Allocate,
+}
+
+impl fmt::Display for Stage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Stage::Ingest => "ingest",
+            Stage::Score => "score",
+            Stage::Allocate => "allocate",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Forwards outcomes to the caller's sink while collecting allocation candidates
+struct RunSink<'a> {
+    inner: &'a mut dyn BatchSink,
+    candidates: Vec<ScoredApplication>,
+    panel_scores: HashMap<String, PanelScore>,
+    panel: ReviewPanel,
+    conflicts: Vec<Conflict>,
+}
+
+impl RunSink<'_> {
+    /// Conflicts recorded when reviewers were assigned to `application`,
+    /// plus any a reviewer assigned to it has with the application as submitted
+    fn conflicts_of(&self, application: &GrantApplication) -> Vec<Conflict> {
+        let mut conflicts: Vec<Conflict> = self
+            .panel
+            .conflicts
+            .iter()
+            .filter(|conflict| conflict.application_id == application.id)
+            .cloned()
+            .collect();
+        let assigned = self
+            .panel
+            .assignments
+            .iter()
+            .filter(|assignment| assignment.application_id == application.id)
+            .filter_map(|assignment| {
+                self.panel
+                    .reviewers
+                    .iter()
+                    .find(|reviewer| reviewer.id == assignment.reviewer_id)
+            });
+        for reviewer in assigned {
+            for found in conflict::detect(reviewer, application) {
+                if !conflicts.contains(&found) {
+                    conflicts.push(found);
+                }
+            }
+        }
+        conflicts
+    }
+}
+
+impl BatchSink for RunSink<'_> {
+    fn on_result(&mut self, record: &Record, mut outcome: RecordOutcome) -> Result<()> {
+        let panel_score = self.panel_scores.get(&record.application.id);
+        if let (Some(panel_score), Some(serde_json::Value::Object(data))) =
+            (panel_score, outcome.result.data.as_mut())
+        {
+            data.insert(
+                "review".to_string(),
+                serde_json::to_value(panel_score).map_err(GrantError::output)?,
+            );
+        }
+        debug!(
+            "Result: {}",
+            serde_json::to_string(&outcome.result).map_err(GrantError::output)?
+        );
+
+        for found in self.conflicts_of(&record.application) {
+            warn!(
+                "Conflict of interest: reviewer {} and application {}: {}",
+                found.reviewer_id, found.application_id, found.detail
+            );
+            self.conflicts.push(found);
+        }
+
+        if outcome.result.success {
+            if panel_score.is_none() && !self.panel_scores.is_empty() {
+                warn!("Application {} has no reviews", record.application.id);
+            }
+            self.candidates.push(ScoredApplication {
+                application_id: record.application.id.clone(),
+                requested: record.application.funding.amount,
+                score: ranking_score(panel_score, outcome.result.score()),
+            });
+        }
+        self.inner.on_result(record, outcome)
+    }
+
+    fn on_error(&mut self, error: RecordError) -> Result<()> {
+        self.inner.on_error(error)
+    }
+
+    fn on_progress(&mut self, progress: &Progress) -> Result<()> {
+        self.inner.on_progress(progress)
     }
 }
 
 /// Main processing function
-pub fn run(verbose: bool, input: Option<String>, output: Option<String>) -> Result<()> {
-    // Initialize logging
-    if verbose {
-        env_logger::Builder::from_default_env()
-            .filter_level(log::LevelFilter::Debug)
-            .init();
+///
+/// Logging is left to the caller, so `run` can be embedded in applications
+/// that install their own logger and called any number of times.
+///
+/// # Arguments
+///
+/// * `config` - Settings for the run
+///
+/// # Returns
+///
+/// A `RunReport` holding every result, the batch summary, the allocation and
+/// the processor statistics
+pub fn run(config: &Config) -> Result<RunReport> {
+    let mut collected = BatchReport::default();
+    let mut report = run_with_sink(config, &mut collected)?;
+    report.batch.results = collected.results;
+    report.batch.errors = collected.errors;
+    Ok(report)
+}
+
+/// Process a run, handing each record outcome to `sink` as it is produced
+///
+/// The returned report carries the summary, allocation and statistics but no
+/// per-record results or errors; those went to `sink`.
+///
+/// # Arguments
+///
+/// * `config` - Settings for the run
+/// * `sink` - Receives every record outcome, e.g. an `output::NdjsonSink`
+///
+/// # Returns
+///
+/// A `RunReport` without per-record entries, or `GrantError::Allocation`
+/// when the run goes up to `Stage::Allocate` without a program
+pub fn run_with_sink(config: &Config, sink: &mut dyn BatchSink) -> Result<RunReport> {
+    info!("Starting GrantProgram processing");
+
+    // Load the program definition
+    let program = match &config.program {
+        Some(path) => Some(ProgramDefinition::load(path)?),
+        None => None,
+    };
+    // Fail before anything is recorded rather than after ingesting the input
+    if config.stage == Stage::Allocate && program.is_none() {
+        return Err(GrantError::allocation(
+            "a program definition is required to allocate its pool",
+        ));
+    }
+
+    let mut processor = match &config.store {
+        Some(path) => GrantProgramProcessor::with_store(
+            config.verbose,
+            program,
+            Box::new(JournalStore::open(path)?),
+        )?,
+        None => GrantProgramProcessor::new(config.verbose, program),
+    };
+    processor.threads = config.threads;
+    if let Some(path) = &config.audit_log {
+        processor.set_audit_log(AuditLog::open(path)?);
+    }
+
+    // Combine the review panel's scores, if any, to rank the applications
+    let (panel, panel_scores) = load_panel(config)?;
+
+    let mut sink = RunSink {
+        inner: sink,
+        candidates: Vec::new(),
+        panel_scores,
+        panel,
+        conflicts: Vec::new(),
+    };
+
+    // Process the input data
+    let reads_input =
+        config.stage == Stage::Ingest || config.input.is_some() || config.store.is_none();
+    let summary = if reads_input {
+        let (mut reader, input_format) = open_input(config)?;
+        match input_format {
+            InputFormat::Ndjson => processor.process_stream(
+                batch::NdjsonReader::new(&mut reader),
+                config.continue_on_error,
+                &mut sink,
+            )?,
+            InputFormat::Csv | InputFormat::Json | InputFormat::Auto => {
+                let records = read_all(&mut reader, input_format, config)?;
+                if !config.continue_on_error {
+                    reject_malformed(&records)?;
+                }
+                processor.process_stream(
+                    records.into_iter().map(Ok),
+                    config.continue_on_error,
+                    &mut sink,
+                )?
+            }
+        }
     } else {
-        env_logger::init();
+        BatchSummary::default()
+    };
+    info!(
+        "Processed {} of {} record(s): {} succeeded, {} rejected, {} malformed",
+        summary.processed, summary.total, summary.succeeded, summary.rejected, summary.malformed
+    );
+
+    // Applications left under review by earlier runs compete as well
+    let mut candidates = sink.candidates;
+    if config.stage != Stage::Ingest {
+        add_stored_candidates(&processor, &sink.panel_scores, &mut candidates)?;
     }
-    
-    info!("Starting GrantProgram processing");
-    
-    let mut processor = GrantProgramProcessor::new(verbose);
-    
-    // Read input
-    let input_data = match input {
+    sort_ranking(&mut candidates);
+
+    // Allocate the program pool across the eligible applications
+    let allocation = match processor.program.as_ref() {
+        Some(program) if config.stage == Stage::Allocate => Some(allocator::allocate(
+            program.total_pool,
+            &candidates,
+            config.strategy,
+            program.min_award,
+        )?),
+        _ => None,
+    };
+    if let Some(allocation) = &allocation {
+        info!(
+            "Allocated {:.2} of {:.2} using {} strategy, {:.2} remaining",
+            allocation.total_awarded, allocation.pool, allocation.strategy, allocation.remainder
+        );
+        processor.apply_allocation(allocation)?;
+    }
+
+    Ok(RunReport {
+        stats: processor.get_stats(),
+        conflicts: sink.conflicts,
+        batch: BatchReport {
+            summary,
+            ..BatchReport::default()
+        },
+        ranking: match config.stage {
+            Stage::Score => candidates,
+            _ => Vec::new(),
+        },
+        allocation,
+    })
+}
+
+/// Load the configured review panel and combine its scores per application
+pub(crate) fn load_panel(config: &Config) -> Result<(ReviewPanel, HashMap<String, PanelScore>)> {
+    let panel = match &config.panel {
+        Some(path) => ReviewPanel::load(path)?,
+        None => ReviewPanel::default(),
+    };
+    let panel_scores = panel
+        .aggregate(config.aggregation)?
+        .into_iter()
+        .map(|panel_score| (panel_score.application_id.clone(), panel_score))
+        .collect();
+    Ok((panel, panel_scores))
+}
+
+/// Add every stored application under review that is not a candidate yet
+pub(crate) fn add_stored_candidates(
+    processor: &GrantProgramProcessor,
+    panel_scores: &HashMap<String, PanelScore>,
+    candidates: &mut Vec<ScoredApplication>,
+) -> Result<()> {
+    let Some(store) = processor.store() else {
+        return Ok(());
+    };
+    let rubric = processor
+        .program
+        .as_ref()
+        .and_then(|program| program.rubric.as_ref());
+    let mut seen: HashSet<String> = candidates
+        .iter()
+        .map(|candidate| candidate.application_id.clone())
+        .collect();
+    for application in store.applications()? {
+        let under_review = processor
+            .lifecycle(&application.id)
+            .is_some_and(|lifecycle| lifecycle.status == ApplicationStatus::UnderReview);
+        if !under_review || !seen.insert(application.id.clone()) {
+            continue;
+        }
+        candidates.push(ScoredApplication {
+            application_id: application.id.clone(),
+            requested: application.funding.amount,
+            score: ranking_score(
+                panel_scores.get(&application.id),
+                rubric.map(|rubric| rubric.score(&application).total),
+            ),
+        });
+    }
+    Ok(())
+}
+
+/// Order candidates best score first, ties broken by application id
+pub(crate) fn sort_ranking(candidates: &mut [ScoredApplication]) {
+    candidates.sort_by(|a, b| {
+        b.score
+            .total_cmp(&a.score)
+            .then_with(|| a.application_id.cmp(&b.application_id))
+    });
+}
+
+/// Ranking score of an eligible application
+///
+/// Panel scores take precedence over the rubric; without either every
+/// eligible application ranks equally.
+fn ranking_score(panel_score: Option<&PanelScore>, rubric_score: Option<f64>) -> f64 {
+    panel_score
+        .map(|panel_score| panel_score.score)
+        .or(rubric_score)
+        .unwrap_or(1.0)
+}
+
+/// Check the configured input without processing it
+///
+/// Every record is read and checked against the program, whatever
+/// `continue_on_error` says. The store and audit log are never opened, so
+/// nothing is recorded and no processed count changes.
+///
+/// # Arguments
+///
+/// * `config` - Settings naming the input, its format, mapping and program
+///
+/// # Returns
+///
+/// A `CheckReport` listing every error and warning with its location
+pub fn dry_run(config: &Config) -> Result<CheckReport> {
+    let program = match &config.program {
+        Some(path) => Some(ProgramDefinition::load(path)?),
+        None => None,
+    };
+    let mut processor = GrantProgramProcessor::new(config.verbose, program);
+    processor.threads = config.threads;
+
+    let (mut reader, input_format) = open_input(config)?;
+    let records = match input_format {
+        InputFormat::Ndjson => batch::NdjsonReader::new(&mut reader).collect::<Result<Vec<_>>>()?,
+        _ => read_all(&mut reader, input_format, config)?,
+    };
+    let report = processor.check(records);
+    info!(
+        "Checked {} record(s): {} valid, {} invalid, {} error(s), {} warning(s)",
+        report.summary.records,
+        report.summary.valid,
+        report.summary.invalid,
+        report.summary.errors,
+        report.summary.warnings
+    );
+    Ok(report)
+}
+
+/// Read every application of the configured input without processing it
+///
+/// Used by commands that need the applications themselves, such as reviewer
+/// assignment.
+///
+/// # Arguments
+///
+/// * `config` - Settings naming the input, its format and column mapping
+///
+/// # Returns
+///
+/// The parsed applications. Malformed records fail the call unless
+/// `continue_on_error` is set, in which case they are logged and skipped.
+pub fn load_applications(config: &Config) -> Result<Vec<GrantApplication>> {
+    let (mut reader, input_format) = open_input(config)?;
+    let records = match input_format {
+        InputFormat::Ndjson => batch::NdjsonReader::new(&mut reader).collect::<Result<Vec<_>>>()?,
+        _ => read_all(&mut reader, input_format, config)?,
+    };
+
+    let mut applications = Vec::with_capacity(records.len());
+    for record in records {
+        match record {
+            Ok(record) => applications.push(record.application),
+            Err(error) if config.continue_on_error => warn!("Skipping {}", error),
+            Err(error) => return Err(error.into()),
+        }
+    }
+    Ok(applications)
+}
+
+/// Open the configured input, falling back to stdin, and resolve its format
+fn open_input(config: &Config) -> Result<(Box<dyn BufRead>, InputFormat)> {
+    Ok(match &config.input {
         Some(path) => {
-            // Read file at the given path
-            fs::read_to_string(path).map_err(|e| e.into())
+            let input_format = config.input_format.for_path(Path::new(path));
+            let file = fs::File::open(path).map_err(|e| GrantError::io(path, e))?;
+            (Box::new(BufReader::new(file)), input_format)
         }
         None => {
-            // Use default input (e.g., stdin)
-            Ok(String::new())
+            let mut stdin = io::stdin().lock();
+            let input_format = config.input_format.for_reader(&mut stdin)?;
+            (Box::new(stdin), input_format)
         }
-    }?;
+    })
+}
 
-    // Process the input data
-    processor.process(&input_data)?;
+/// Fail with the first malformed record so nothing of the batch is processed
+fn reject_malformed(records: &[batch::RecordResult]) -> Result<()> {
+    match records.iter().find_map(|record| record.as_ref().err()) {
+        Some(error) => Err(error.clone().into()),
+        None => Ok(()),
+    }
+}
 
-    // Write output
-    let output_data = serde_json::to_string(&processor.get_stats())?;
-    if let Some(output_path) = output {
-        fs::write(output_path, output_data)?;
+/// Read a whole JSON or CSV input into records
+fn read_all(
+    reader: &mut dyn BufRead,
+    input_format: InputFormat,
+    config: &Config,
+) -> Result<Vec<batch::RecordResult>> {
+    let mut input_data = String::new();
+    reader.read_to_string(&mut input_data)?;
+    if input_format == InputFormat::Csv {
+        let mapping = match &config.mapping {
+            Some(path) => ColumnMapping::load(path)?,
+            None => ColumnMapping::default(),
+        };
+        csv_import::read_csv(&input_data, &mapping)
     } else {
-        println!("{}", output_data);
+        batch::read_records(&input_data)
     }
+}
 
-    Ok(())
-}
\ No newline at end of file
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::application;
+
+    #[test]
+    fn lifecycle_follows_allowed_transitions() {
+        use ApplicationStatus::*;
+        let mut lifecycle = Lifecycle::new("APP-1");
+        for to in [Submitted, UnderReview, Shortlisted, Awarded, Closed] {
+            let change = lifecycle.transition(to, "next").unwrap();
+            assert_eq!(change.to, to);
+        }
+        assert_eq!(lifecycle.status, Closed);
+        let path: Vec<Option<ApplicationStatus>> =
+            lifecycle.history.iter().map(|change| change.from).collect();
+        assert_eq!(
+            path,
+            [
+                None,
+                Some(Draft),
+                Some(Submitted),
+                Some(UnderReview),
+                Some(Shortlisted),
+                Some(Awarded)
+            ]
+        );
+    }
+
+    #[test]
+    fn lifecycle_refuses_disallowed_transitions() {
+        use ApplicationStatus::*;
+        let mut lifecycle = Lifecycle::new("APP-1");
+        let error = lifecycle.transition(Awarded, "skip ahead").unwrap_err();
+        assert!(matches!(
+            error,
+            GrantError::Transition {
+                from: Draft,
+                to: Awarded,
+                ..
+            }
+        ));
+        assert_eq!(
+            error.to_string(),
+            "application APP-1 cannot move from draft to awarded (allowed: submitted, withdrawn)"
+        );
+        assert_eq!(lifecycle.status, Draft);
+        assert_eq!(lifecycle.history.len(), 1);
+
+        for status in [Submitted, Declined, Closed] {
+            lifecycle.transition(status, "").unwrap();
+        }
+        let error = lifecycle.transition(Submitted, "reopen").unwrap_err();
+        assert!(error.to_string().ends_with("(no further changes allowed)"));
+    }
+
+    #[test]
+    fn processing_moves_applications_to_review_or_declined() {
+        let mut program = test_support::program(100_000.0);
+        program.max_award = 5000.0;
+        let mut processor = GrantProgramProcessor::new(false, Some(program));
+
+        let passed = processor
+            .process_application(&application("APP-1", 4000.0))
+            .unwrap();
+        assert!(passed.success);
+        assert_eq!(passed.message, "Application APP-1 processed as item #1");
+        let failed = processor
+            .process_application(&application("APP-2", 9000.0))
+            .unwrap();
+        assert!(!failed.success);
+        assert!(failed.rules.iter().any(RuleOutcome::is_failure));
+
+        let status = |id: &str| processor.lifecycle(id).map(|lifecycle| lifecycle.status);
+        assert_eq!(status("APP-1"), Some(ApplicationStatus::UnderReview));
+        assert_eq!(status("APP-2"), Some(ApplicationStatus::Declined));
+        assert_eq!(processor.processed_count, 2);
+    }
+
+    #[test]
+    fn resubmission_is_refused_without_counting_it() {
+        let mut processor = GrantProgramProcessor::new(false, None);
+        processor
+            .process_application(&application("APP-1", 4000.0))
+            .unwrap();
+        let refused = processor
+            .process_application(&application("APP-1", 4000.0))
+            .unwrap();
+        assert!(!refused.success);
+        assert_eq!(
+            refused.message,
+            "application APP-1 cannot move from under_review to submitted (allowed: shortlisted, declined, withdrawn)"
+        );
+        assert_eq!(refused.data.unwrap()["status"], "under_review");
+        assert_eq!(processor.processed_count, 1);
+        assert_eq!(processor.lifecycle("APP-1").unwrap().history.len(), 3);
+    }
+
+    #[test]
+    fn transition_of_an_unknown_application_is_a_validation_error() {
+        let mut processor = GrantProgramProcessor::new(false, None);
+        let error = processor
+            .transition("nope", ApplicationStatus::Withdrawn, "")
+            .unwrap_err();
+        assert!(matches!(error, GrantError::Validation { .. }));
+    }
+
+    #[test]
+    fn allocation_awards_funded_and_declines_the_rest() {
+        let mut processor = GrantProgramProcessor::new(false, None);
+        for id in ["APP-1", "APP-2"] {
+            processor
+                .process_application(&application(id, 4000.0))
+                .unwrap();
+        }
+        let candidates: Vec<ScoredApplication> = ["APP-1", "APP-2"]
+            .iter()
+            .map(|id| ScoredApplication {
+                application_id: id.to_string(),
+                requested: 4000.0,
+                score: 1.0,
+            })
+            .collect();
+        let allocation = allocator::allocate(5000.0, &candidates, Strategy::Greedy, 0.0).unwrap();
+        processor.apply_allocation(&allocation).unwrap();
+
+        let status = |id: &str| processor.lifecycle(id).map(|lifecycle| lifecycle.status);
+        assert_eq!(status("APP-1"), Some(ApplicationStatus::Awarded));
+        assert_eq!(status("APP-2"), Some(ApplicationStatus::Declined));
+    }
+
+    #[test]
+    fn run_reports_conflicts_of_assigned_reviewers_only() {
+        // Every reviewer works at the applicant's institution
+        let panel: ReviewPanel = serde_json::from_value(serde_json::json!({
+            "reviewers": [
+                { "id": "R1", "institution": "Green" },
+                { "id": "R2", "institution": "Green" },
+                { "id": "R3", "institution": "Green" }
+            ],
+            "assignments": [{ "application_id": "APP-1", "reviewer_id": "R1" }],
+            "conflicts": [{
+                "application_id": "APP-1",
+                "reviewer_id": "R2",
+                "reason": "same_institution",
+                "detail": "reviewer and applicant are both at Green"
+            }]
+        }))
+        .unwrap();
+        let mut collected = BatchReport::default();
+        let mut sink = RunSink {
+            inner: &mut collected,
+            candidates: Vec::new(),
+            panel_scores: HashMap::new(),
+            panel,
+            conflicts: Vec::new(),
+        };
+
+        let mut processor = GrantProgramProcessor::new(false, None);
+        for (index, id) in ["APP-1", "APP-2"].into_iter().enumerate() {
+            let record = Record {
+                index: index + 1,
+                line: index + 1,
+                application: application(id, 1000.0),
+            };
+            let outcome = RecordOutcome {
+                index: record.index,
+                line: record.line,
+                result: processor.process_application(&record.application).unwrap(),
+            };
+            sink.on_result(&record, outcome).unwrap();
+        }
+
+        let pairs: Vec<(&str, &str)> = sink
+            .conflicts
+            .iter()
+            .map(|c| (c.application_id.as_str(), c.reviewer_id.as_str()))
+            .collect();
+        assert_eq!(pairs, [("APP-1", "R2"), ("APP-1", "R1")]);
+        assert_eq!(collected.results.len(), 2);
+    }
+
+    /// Write `value` as JSON to `name` in `dir` and return its path
+    fn write_json(dir: &Path, name: &str, value: &impl Serialize) -> Option<String> {
+        let path = dir.join(name);
+        fs::write(&path, serde_json::to_string(value).unwrap()).unwrap();
+        Some(path.to_string_lossy().into_owned())
+    }
+
+    #[test]
+    fn run_can_be_called_repeatedly_without_installing_a_logger() {
+        struct Silent;
+        impl log::Log for Silent {
+            fn enabled(&self, _: &log::Metadata) -> bool {
+                false
+            }
+            fn log(&self, _: &log::Record) {}
+            fn flush(&self) {}
+        }
+        static SILENT: Silent = Silent;
+
+        let dir = tempfile::tempdir().unwrap();
+        let applications = [
+            serde_json::to_value(application("APP-1", 1000.0)).unwrap(),
+            serde_json::to_value(application("APP-2", 2000.0)).unwrap(),
+        ];
+        let config = Config {
+            program: write_json(
+                dir.path(),
+                "program.json",
+                &test_support::program(100_000.0),
+            ),
+            input: write_json(dir.path(), "applications.json", &applications),
+            ..Config::default()
+        };
+
+        let first = run(&config).unwrap();
+        let second = run(&config).unwrap();
+        assert_eq!(first.batch.summary.succeeded, 2);
+        assert_eq!(second.batch.summary.succeeded, 2);
+        assert_eq!(second.batch.results.len(), 2);
+        assert_eq!(first.allocation, second.allocation);
+        // The slot is still free for the embedding application
+        assert!(log::set_logger(&SILENT).is_ok());
+    }
+
+    #[test]
+    fn a_malformed_record_stops_a_document_before_anything_is_stored() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = dir.path().join("store.ndjson");
+        let applications = [
+            serde_json::to_value(application("APP-1", 1000.0)).unwrap(),
+            serde_json::json!({ "id": "APP-2" }),
+        ];
+        let config = Config {
+            program: write_json(
+                dir.path(),
+                "program.json",
+                &test_support::program(100_000.0),
+            ),
+            input: write_json(dir.path(), "applications.json", &applications),
+            store: Some(store.to_string_lossy().into_owned()),
+            ..Config::default()
+        };
+
+        assert!(run(&config).is_err());
+        let store = JournalStore::open(&store).unwrap();
+        assert!(store.applications().unwrap().is_empty());
+        assert_eq!(store.processed_count().unwrap(), 0);
+    }
+
+    #[test]
+    fn allocating_without_a_program_fails_before_reading_input() {
+        let config = Config {
+            stage: Stage::Allocate,
+            input: Some("/nonexistent/applications.json".to_string()),
+            ..Config::default()
+        };
+        let error = run(&config).unwrap_err();
+        assert!(matches!(error, GrantError::Allocation { .. }), "{}", error);
+    }
+
+    #[test]
+    fn a_well_formed_application_has_no_issues() {
+        assert_eq!(application("A1", 10_000.0).validate(), []);
+    }
+
+    #[test]
+    fn each_validation_issue_names_its_field() {
+        type Edit = fn(&mut GrantApplication);
+        let cases: [(Edit, &str, &str); 12] = [
+            (|a| a.id = " ".into(), "id", "must not be empty"),
+            (|a| a.title = String::new(), "title", "must not be empty"),
+            (
+                |a| a.applicant.id = String::new(),
+                "applicant.id",
+                "must not be empty",
+            ),
+            (
+                |a| a.applicant.name = "\t".into(),
+                "applicant.name",
+                "must not be empty",
+            ),
+            (
+                |a| a.applicant.email = "green.org".into(),
+                "applicant.email",
+                "'green.org' is not a valid email address",
+            ),
+            (
+                |a| a.applicant.country = String::new(),
+                "applicant.country",
+                "must not be empty",
+            ),
+            (
+                |a| a.program.id = String::new(),
+                "program.id",
+                "must not be empty",
+            ),
+            (
+                |a| {
+                    a.funding.amount = 0.0;
+                    a.funding.budget.clear();
+                },
+                "funding.amount",
+                "must be a positive amount, got 0",
+            ),
+            (
+                |a| {
+                    a.funding.amount = -5.0;
+                    a.funding.budget.clear();
+                },
+                "funding.amount",
+                "must be a positive amount, got -5",
+            ),
+            (
+                |a| {
+                    a.funding.amount = f64::NAN;
+                    a.funding.budget.clear();
+                },
+                "funding.amount",
+                "must be a positive amount, got NaN",
+            ),
+            (
+                |a| a.funding.budget[0].amount = 9_000.0,
+                "funding.budget",
+                "budget lines total 9000.00 but 10000.00 was requested",
+            ),
+            (
+                |a| {
+                    a.funding.budget[0].amount = -1.0;
+                    a.funding.budget.push(BudgetLine {
+                        category: "travel".into(),
+                        description: String::new(),
+                        amount: 2.0,
+                    });
+                    a.funding.amount = 1.0;
+                },
+                "funding.budget[0].amount",
+                "must not be negative, got -1",
+            ),
+        ];
+
+        for (edit, field, message) in cases {
+            let mut application = application("A1", 10_000.0);
+            edit(&mut application);
+            assert_eq!(
+                application.validate(),
+                [ValidationIssue::new(field, message)],
+                "{field}"
+            );
+        }
+    }
+
+    #[test]
+    fn every_issue_is_reported_in_field_order() {
+        let mut application = application("A1", 10_000.0);
+        application.title = String::new();
+        application.applicant.email = String::new();
+        application.funding.budget[0].amount = -3.0;
+
+        let fields: Vec<String> = application
+            .validate()
+            .into_iter()
+            .map(|issue| issue.field)
+            .collect();
+        assert_eq!(
+            fields,
+            [
+                "title",
+                "applicant.email",
+                "funding.budget[0].amount",
+                "funding.budget"
+            ]
+        );
+    }
+}
@@ -3,26 +3,814 @@
  * Main executable for GrantProgram
  */
 
-use clap::Parser;
-use grantprogram::{Result, run};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use grantprogram::output::{render_rows, standings};
+use grantprogram::{
+    dry_run, load_applications, run_with_sink, Aggregation, AssignmentStrategy, BatchReport,
+    BatchSink, Config, ConfigLayer, GrantError, GrantProgramProcessor, InputFormat, JournalStore,
+    NdjsonSink, OutputFormat, ProgramDefinition, Progress, Record, RecordError, RecordOutcome,
+    ResolvedConfig, Result, Review, ReviewPanel, RunReport, Server, Stage, Store, Strategy,
+};
+use log::{info, warn};
+use serde_json::json;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::process::ExitCode;
+use std::time::{Duration, Instant};
 
 #[derive(Parser)]
 #[command(version, about = "GrantProgram - A Rust implementation")]
 struct Cli {
+    #[command(subcommand)]
+    command: Command,
+
+    #[command(flatten)]
+    global: GlobalArgs,
+}
+
+// Flags shared by every command. Flags that are not given fall back to the
+// config file, then to `GRANTPROGRAM_*` environment variables, then to
+// built-in defaults. A doc comment here would become the program's --help
+// description through `#[command(flatten)]`.
+#[derive(Args)]
+struct GlobalArgs {
+    /// Config file (JSON or TOML); defaults to $GRANTPROGRAM_CONFIG or ./grantprogram.toml
+    #[arg(short, long, global = true)]
+    config: Option<String>,
+
     /// Enable verbose output
-    #[arg(short, long)]
+    #[arg(short, long, global = true)]
     verbose: bool,
-    
-    /// Input file path
+
+    /// Program definition file (JSON or TOML)
+    #[arg(short, long, global = true)]
+    program: Option<String>,
+
+    /// Journal file keeping applications, status changes and awards between runs
+    #[arg(long, global = true)]
+    store: Option<String>,
+
+    /// Hash-chained audit log every decision is appended to
+    #[arg(long, global = true)]
+    audit_log: Option<String>,
+
+    /// Output file path; writes to stdout when omitted
+    #[arg(short, long, global = true)]
+    output: Option<String>,
+
+    /// Output format [default: json]
+    #[arg(short, long, global = true, value_enum)]
+    format: Option<OutputFormat>,
+
+    /// Worker threads evaluating the input; 0 uses every CPU [default: 1]
+    #[arg(long, global = true)]
+    threads: Option<usize>,
+
+    /// Report progress on stderr while records are processed
+    #[arg(long, global = true)]
+    progress: bool,
+}
+
+/// Where applications are read from
+#[derive(Args)]
+struct InputArgs {
+    /// Input file path; reads stdin when omitted
     #[arg(short, long)]
     input: Option<String>,
-    
-    /// Output file path
+
+    /// Input format [default: auto]
+    #[arg(long, value_enum)]
+    input_format: Option<InputFormat>,
+
+    /// Column mapping file (JSON or TOML) for CSV input
     #[arg(short, long)]
-    output: Option<String>,
+    mapping: Option<String>,
+
+    /// Skip malformed records instead of aborting
+    #[arg(long)]
+    continue_on_error: bool,
+}
+
+/// Which review panel ranks the applications
+#[derive(Args)]
+struct PanelArgs {
+    /// Review panel file (JSON or TOML) whose scores rank the applications
+    #[arg(long)]
+    panel: Option<String>,
+
+    /// How the panel's reviews are combined [default: mean]
+    #[arg(long, value_enum)]
+    aggregation: Option<Aggregation>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Check applications against the program without recording anything
+    ///
+    /// Every problem is listed with its record, line and field. The store,
+    /// audit log and processed count are left alone; the exit code is
+    /// non-zero if any record has an error.
+    Validate {
+        #[command(flatten)]
+        input: InputArgs,
+    },
+    /// Submit applications, check their eligibility and score them
+    Ingest {
+        #[command(flatten)]
+        input: InputArgs,
+    },
+    /// Rank the applications under review
+    ///
+    /// Without --input, the applications under review in --store are ranked.
+    Score {
+        #[command(flatten)]
+        input: InputArgs,
+
+        #[command(flatten)]
+        panel: PanelArgs,
+    },
+    /// Allocate the program pool across the applications under review
+    ///
+    /// Without --input, the applications under review in --store compete.
+    Allocate {
+        #[command(flatten)]
+        input: InputArgs,
+
+        #[command(flatten)]
+        panel: PanelArgs,
+
+        /// Strategy used to allocate the program pool [default: greedy]
+        #[arg(short, long, value_enum)]
+        strategy: Option<Strategy>,
+    },
+    /// List where every application in --store stands
+    Report,
+    /// Print processing statistics for --store
+    ///
+    /// Counts by status and program, acceptance and rejection rates, how often
+    /// each rule failed, requested and awarded totals, a score histogram and
+    /// evaluation latency percentiles. Give --program to re-check the stored
+    /// applications for rule failures and scores.
+    Stats,
+    /// Dump the records kept in --store
+    Export {
+        /// Records to export
+        #[arg(long, value_enum, default_value_t = ExportKind::Applications)]
+        what: ExportKind,
+    },
+    /// Print the JSON Schema every submitted application must match
+    Schema,
+    /// Serve the processor as an HTTP JSON API
+    ///
+    /// Applications are kept in --store, or in memory when no store is given.
+    /// Endpoints: POST /applications, GET /applications[?status=],
+    /// GET /applications/{id}, POST /score, POST /allocate[?strategy=] and
+    /// GET /stats.
+    Serve {
+        /// Address to listen on
+        #[arg(long, default_value = grantprogram::server::DEFAULT_LISTEN)]
+        listen: String,
+
+        #[command(flatten)]
+        panel: PanelArgs,
+
+        /// Strategy used by POST /allocate unless the request names one [default: greedy]
+        #[arg(short, long, value_enum)]
+        strategy: Option<Strategy>,
+    },
+    /// Manage review panels
+    #[command(subcommand)]
+    Review(ReviewCommand),
+    /// Inspect the effective configuration
+    #[command(subcommand)]
+    Config(ConfigCommand),
+    /// Check that no audit log entry was edited, removed or reordered
+    VerifyAudit {
+        /// Audit log file; defaults to --audit-log
+        path: Option<String>,
+    },
+}
+
+/// Records the `export` command can dump
+#[derive(Clone, Copy, ValueEnum)]
+enum ExportKind {
+    /// Submitted applications
+    Applications,
+    /// Status history of every application
+    Lifecycles,
+    /// Panel reviews
+    Reviews,
+    /// Awards from the latest allocation
+    Awards,
+}
+
+#[derive(Subcommand)]
+enum ReviewCommand {
+    /// Assign reviewers from a panel file to applications
+    ///
+    /// The updated panel is written to --output, or printed as JSON.
+    Assign {
+        /// Panel file (JSON or TOML) listing the reviewers
+        #[arg(long)]
+        panel: String,
+
+        #[command(flatten)]
+        input: InputArgs,
+
+        /// Reviewers each application should get
+        #[arg(short = 'n', long, default_value_t = 3)]
+        per_application: usize,
+
+        /// How reviewers are chosen
+        #[arg(short, long, value_enum, default_value_t = AssignmentStrategy::LoadBalanced)]
+        strategy: AssignmentStrategy,
+    },
+    /// Record a reviewer's score for an application in a panel file
+    ///
+    /// Ratings are scored with the rubric of --program, and the review is
+    /// also saved to --store when given.
+    Submit {
+        /// Panel file (JSON or TOML), updated in place
+        #[arg(long)]
+        panel: String,
+
+        /// Reviewer submitting the review
+        #[arg(long)]
+        reviewer: String,
+
+        /// Application being reviewed
+        #[arg(long)]
+        application: String,
+
+        /// Overall score
+        #[arg(long)]
+        score: Option<f64>,
+
+        /// Rating for a rubric criterion as CRITERION=VALUE; may be repeated
+        #[arg(long = "rating", value_parser = parse_rating)]
+        ratings: Vec<(String, f64)>,
+
+        /// Free-form remarks
+        #[arg(long, default_value = "")]
+        comment: String,
+    },
+    /// Combine the reviews in a panel file into a ranking
+    Aggregate {
+        /// Panel file (JSON or TOML)
+        #[arg(long)]
+        panel: String,
+
+        /// How the reviews are combined
+        #[arg(short, long, value_enum, default_value_t = Aggregation::Mean)]
+        method: Aggregation,
+    },
+}
+
+#[derive(Subcommand)]
+enum ConfigCommand {
+    /// Print every setting with its effective value and where it came from
+    Show,
+}
+
+fn parse_rating(text: &str) -> std::result::Result<(String, f64), String> {
+    let (criterion, value) = text
+        .split_once('=')
+        .ok_or_else(|| format!("expected CRITERION=VALUE, got '{}'", text))?;
+    let value = value
+        .trim()
+        .parse::<f64>()
+        .map_err(|_| format!("'{}' is not a number", value.trim()))?;
+    Ok((criterion.trim().to_string(), value))
+}
+
+/// Process exit code for each kind of failure
+///
+/// 2 is left to clap for command-line usage errors.
+fn exit_code(error: &GrantError) -> u8 {
+    match error {
+        GrantError::Io { .. } => 3,
+        GrantError::Parse { .. } => 4,
+        GrantError::Validation { .. } => 5,
+        GrantError::Rule { .. } => 6,
+        GrantError::Allocation { .. } => 7,
+        GrantError::Output { .. } => 8,
+        GrantError::Transition { .. } => 9,
+        GrantError::Audit { .. } => 10,
+    }
 }
 
-fn main() -> Result<()> {
-    let args = Cli::parse();
-    run(args.verbose, args.input, args.output)
+fn init_logging(verbose: bool) {
+    if verbose {
+        env_logger::Builder::from_default_env()
+            .filter_level(log::LevelFilter::Debug)
+            .init();
+    } else {
+        env_logger::init();
+    }
+}
+
+fn open_output(path: Option<&str>) -> Result<Box<dyn Write>> {
+    Ok(match path {
+        Some(path) => {
+            let file = fs::File::create(path).map_err(|e| GrantError::io(path, e))?;
+            Box::new(BufWriter::new(file))
+        }
+        None => Box::new(BufWriter::new(io::stdout().lock())),
+    })
+}
+
+fn write_output(path: Option<&str>, mut data: String) -> Result<()> {
+    if !data.ends_with('\n') {
+        data.push('\n');
+    }
+    let mut writer = open_output(path)?;
+    writer.write_all(data.as_bytes())?;
+    writer.flush()?;
+    Ok(())
+}
+
+impl GlobalArgs {
+    /// Settings given as global flags
+    fn layer(&self) -> ConfigLayer {
+        ConfigLayer {
+            verbose: self.verbose.then_some(true),
+            program: self.program.clone(),
+            store: self.store.clone(),
+            audit_log: self.audit_log.clone(),
+            format: self.format,
+            threads: self.threads,
+            ..ConfigLayer::default()
+        }
+    }
+}
+
+impl InputArgs {
+    fn apply(&self, layer: &mut ConfigLayer) {
+        layer.input_format = self.input_format;
+        layer.mapping = self.mapping.clone();
+        layer.continue_on_error = self.continue_on_error.then_some(true);
+    }
+}
+
+impl PanelArgs {
+    fn apply(&self, layer: &mut ConfigLayer) {
+        layer.panel = self.panel.clone();
+        layer.aggregation = self.aggregation;
+    }
+}
+
+impl Command {
+    /// Settings given as flags of this command
+    fn apply(&self, layer: &mut ConfigLayer) {
+        match self {
+            Command::Validate { input } | Command::Ingest { input } => input.apply(layer),
+            Command::Score { input, panel } => {
+                input.apply(layer);
+                panel.apply(layer);
+            }
+            Command::Allocate {
+                input,
+                panel,
+                strategy,
+            } => {
+                input.apply(layer);
+                panel.apply(layer);
+                layer.strategy = *strategy;
+            }
+            Command::Serve {
+                panel, strategy, ..
+            } => {
+                panel.apply(layer);
+                layer.strategy = *strategy;
+            }
+            Command::Review(ReviewCommand::Assign { input, .. }) => input.apply(layer),
+            _ => {}
+        }
+    }
+}
+
+/// Config for a run over `input` that stops at `stage`
+fn run_config(config: &Config, stage: Stage, input: InputArgs) -> Config {
+    Config {
+        stage,
+        input: input.input,
+        ..config.clone()
+    }
+}
+
+fn open_store(config: &Config, command: &str) -> Result<JournalStore> {
+    match &config.store {
+        Some(path) => JournalStore::open(path),
+        None => Err(GrantError::validation(
+            "store",
+            format!("the {} command needs --store", command),
+        )),
+    }
+}
+
+/// Forwards outcomes, printing progress on stderr at most once per interval
+struct ProgressSink<'a> {
+    inner: &'a mut dyn BatchSink,
+    enabled: bool,
+    printed: Option<Instant>,
+    latest: Option<Progress>,
+}
+
+impl<'a> ProgressSink<'a> {
+    const INTERVAL: Duration = Duration::from_secs(1);
+
+    fn new(inner: &'a mut dyn BatchSink, enabled: bool) -> Self {
+        Self {
+            inner,
+            enabled,
+            printed: None,
+            latest: None,
+        }
+    }
+
+    /// Print the final count if the last update was not printed yet
+    fn finish(&mut self) {
+        if let Some(progress) = self.latest.take() {
+            eprintln!("Processed {}", progress);
+        }
+    }
+}
+
+impl BatchSink for ProgressSink<'_> {
+    fn on_result(&mut self, record: &Record, outcome: RecordOutcome) -> Result<()> {
+        self.inner.on_result(record, outcome)
+    }
+
+    fn on_error(&mut self, error: RecordError) -> Result<()> {
+        self.inner.on_error(error)
+    }
+
+    fn on_progress(&mut self, progress: &Progress) -> Result<()> {
+        if self.enabled {
+            self.latest = Some(*progress);
+            if self
+                .printed
+                .is_none_or(|printed| printed.elapsed() >= Self::INTERVAL)
+            {
+                self.finish();
+                self.printed = Some(Instant::now());
+            }
+        }
+        self.inner.on_progress(progress)
+    }
+}
+
+/// Run `config`, collecting every record outcome into the report
+fn run_collected(config: &Config, progress: bool) -> Result<RunReport> {
+    let mut collected = BatchReport::default();
+    let mut sink = ProgressSink::new(&mut collected, progress);
+    let mut report = run_with_sink(config, &mut sink)?;
+    sink.finish();
+    report.batch.results = collected.results;
+    report.batch.errors = collected.errors;
+    Ok(report)
+}
+
+/// Run the processing pipeline and write its report
+fn execute_run(output: Option<&str>, config: &Config, progress: bool) -> Result<RunReport> {
+    let Some(path) = output else {
+        return write_run(None, config, progress);
+    };
+    // Written beside the destination and moved over it once the run
+    // succeeds, so a failed run leaves the previous report in place
+    let staging = format!("{}.partial", path);
+    match write_run(Some(&staging), config, progress) {
+        Ok(report) => {
+            fs::rename(&staging, path).map_err(|e| GrantError::io(path, e))?;
+            Ok(report)
+        }
+        Err(error) => {
+            let _ = fs::remove_file(&staging);
+            Err(error)
+        }
+    }
+}
+
+fn write_run(output: Option<&str>, config: &Config, progress: bool) -> Result<RunReport> {
+    let mut writer = open_output(output)?;
+
+    // NDJSON output is written record by record while processing
+    let (report, output_data) = if config.format == OutputFormat::Ndjson {
+        let mut stream = NdjsonSink::new(&mut writer);
+        let mut sink = ProgressSink::new(&mut stream, progress);
+        let report = run_with_sink(config, &mut sink)?;
+        sink.finish();
+        let tail = report.render_ndjson_tail()?;
+        (report, tail)
+    } else {
+        let report = run_collected(config, progress)?;
+        let mut rendered = report.render(config.format)?;
+        if !rendered.ends_with('\n') {
+            rendered.push('\n');
+        }
+        (report, rendered)
+    };
+
+    writer.write_all(output_data.as_bytes())?;
+    writer.flush()?;
+    Ok(report)
+}
+
+fn execute_review(output: Option<&str>, config: &Config, command: ReviewCommand) -> Result<()> {
+    match command {
+        ReviewCommand::Assign {
+            panel: panel_path,
+            input,
+            per_application,
+            strategy,
+        } => {
+            let mut panel = ReviewPanel::load(&panel_path)?;
+            let applications = load_applications(&run_config(config, Stage::Ingest, input))?;
+            let added = panel.assign(&applications, per_application, strategy)?;
+            info!(
+                "Added {} assignment(s) for {} application(s) using {} strategy",
+                added.len(),
+                applications.len(),
+                strategy
+            );
+            for conflict in &panel.conflicts {
+                warn!(
+                    "Conflict of interest: reviewer {} and application {}: {}",
+                    conflict.reviewer_id, conflict.application_id, conflict.detail
+                );
+            }
+            match output {
+                Some(path) => panel.save(path),
+                None => write_output(
+                    None,
+                    serde_json::to_string_pretty(&panel).map_err(GrantError::output)?,
+                ),
+            }
+        }
+        ReviewCommand::Submit {
+            panel: panel_path,
+            reviewer,
+            application,
+            score,
+            ratings,
+            comment,
+        } => {
+            let mut panel = ReviewPanel::load(&panel_path)?;
+            let program = match &config.program {
+                Some(path) => Some(ProgramDefinition::load(path)?),
+                None => None,
+            };
+            let review = Review {
+                application_id: application,
+                reviewer_id: reviewer,
+                score,
+                ratings: ratings.into_iter().collect::<BTreeMap<_, _>>(),
+                comment,
+                submitted_at: None,
+            };
+            let saved = panel
+                .submit(
+                    review,
+                    program.as_ref().and_then(|program| program.rubric.as_ref()),
+                )?
+                .clone();
+            panel.save(&panel_path)?;
+            if config.store.is_some() {
+                open_store(config, "review submit")?.save_review(&saved)?;
+            }
+            info!("Review recorded in {}", panel_path);
+            Ok(())
+        }
+        ReviewCommand::Aggregate { panel, method } => {
+            let ranking = ReviewPanel::load(panel)?.aggregate(method)?;
+            write_output(
+                output,
+                serde_json::to_string_pretty(&ranking).map_err(GrantError::output)?,
+            )
+        }
+    }
+}
+
+fn execute(
+    command: Command,
+    output: Option<&str>,
+    progress: bool,
+    resolved: ResolvedConfig,
+) -> Result<()> {
+    let config = &resolved.config;
+    match command {
+        Command::Validate { input } => {
+            let report = dry_run(&run_config(config, Stage::Ingest, input))?;
+            write_output(output, report.render(config.format)?)?;
+            if report.has_errors() {
+                return Err(GrantError::validation(
+                    "input",
+                    format!(
+                        "{} error(s) in {} of {} record(s)",
+                        report.summary.errors, report.summary.invalid, report.summary.records
+                    ),
+                ));
+            }
+        }
+        Command::Ingest { input } => {
+            execute_run(output, &run_config(config, Stage::Ingest, input), progress)?;
+        }
+        Command::Score { input, .. } => {
+            let ranking: Vec<_> =
+                run_collected(&run_config(config, Stage::Score, input), progress)?
+                    .ranking
+                    .into_iter()
+                    .enumerate()
+                    .map(|(position, candidate)| {
+                        json!({
+                            "rank": position + 1,
+                            "application_id": candidate.application_id,
+                            "score": candidate.score,
+                            "requested": candidate.requested,
+                        })
+                    })
+                    .collect();
+            write_output(output, render_rows(&ranking, config.format)?)?;
+        }
+        Command::Allocate { input, .. } => {
+            execute_run(
+                output,
+                &run_config(config, Stage::Allocate, input),
+                progress,
+            )?;
+        }
+        Command::Report => {
+            let standings = standings(&open_store(config, "report")?)?;
+            write_output(output, render_rows(&standings, config.format)?)?;
+        }
+        Command::Stats => {
+            let program = match &config.program {
+                Some(path) => Some(ProgramDefinition::load(path)?),
+                None => None,
+            };
+            let processor = GrantProgramProcessor::with_store(
+                config.verbose,
+                program,
+                Box::new(open_store(config, "stats")?),
+            )?;
+            write_output(output, render_rows(&[processor.stats()], config.format)?)?;
+        }
+        Command::Export { what } => {
+            let store = open_store(config, "export")?;
+            let rendered = match what {
+                ExportKind::Applications => render_rows(&store.applications()?, config.format)?,
+                ExportKind::Lifecycles => render_rows(&store.lifecycles()?, config.format)?,
+                ExportKind::Reviews => render_rows(&store.reviews()?, config.format)?,
+                ExportKind::Awards => render_rows(&store.awards()?, config.format)?,
+            };
+            write_output(output, rendered)?;
+        }
+        Command::Schema => {
+            let schema = grantprogram::schema::application_schema();
+            let rendered = match config.format {
+                OutputFormat::Json | OutputFormat::Ndjson => {
+                    serde_json::to_string(schema).map_err(GrantError::output)?
+                }
+                _ => serde_json::to_string_pretty(schema).map_err(GrantError::output)?,
+            };
+            write_output(output, rendered)?;
+        }
+        Command::Serve { listen, .. } => Server::bind(listen.as_str(), config)?.serve()?,
+        Command::Review(command) => execute_review(output, config, command)?,
+        Command::Config(ConfigCommand::Show) => {
+            if let Some(path) = &resolved.file {
+                info!("Read config file {}", path.display());
+            }
+            write_output(output, render_rows(&resolved.entries()?, config.format)?)?;
+        }
+        Command::VerifyAudit { path } => {
+            let path = path.or_else(|| config.audit_log.clone()).ok_or_else(|| {
+                GrantError::validation("path", "give an audit log path or --audit-log")
+            })?;
+            let summary = grantprogram::audit::verify(&path)?;
+            write_output(
+                output,
+                format!(
+                    "audit log {} is intact: {} entries, head {}",
+                    path, summary.entries, summary.head
+                ),
+            )?;
+        }
+    }
+    info!("Finished GrantProgram processing");
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    let Cli { command, global } = Cli::parse();
+    let mut layer = global.layer();
+    command.apply(&mut layer);
+    let resolved = match Config::resolve(global.config.as_deref().map(Path::new), layer) {
+        Ok(resolved) => resolved,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            return ExitCode::from(exit_code(&e));
+        }
+    };
+    init_logging(resolved.config.verbose);
+
+    match execute(command, global.output.as_deref(), global.progress, resolved) {
+        Ok(()) => ExitCode::SUCCESS,
+        // A closed pipe (e.g. `| head`) just means nobody wants more output
+        Err(e) if e.is_broken_pipe() => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            ExitCode::from(exit_code(&e))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Run the command line the way `main` does, without installing a logger
+    fn run_cli(args: &[&str]) -> Result<()> {
+        let Cli { command, global } =
+            Cli::try_parse_from(std::iter::once("grantprogram").chain(args.iter().copied()))
+                .unwrap();
+        let mut layer = global.layer();
+        command.apply(&mut layer);
+        let resolved = Config::resolve(global.config.as_deref().map(Path::new), layer)?;
+        execute(command, global.output.as_deref(), global.progress, resolved)
+    }
+
+    fn application(id: &str, amount: f64) -> serde_json::Value {
+        json!({
+            "id": id,
+            "title": "River cleanup",
+            "applicant": {
+                "id": "A1",
+                "name": "Green Org",
+                "org_type": "nonprofit",
+                "email": "x@green.org",
+                "country": "US"
+            },
+            "program": { "id": "P1" },
+            "funding": {
+                "amount": amount,
+                "budget": [{ "category": "personnel", "amount": amount }]
+            }
+        })
+    }
+
+    #[test]
+    fn validate_exits_non_zero_and_leaves_the_store_alone() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = |name: &str| dir.path().join(name).to_string_lossy().into_owned();
+        fs::write(path("grantprogram.toml"), "").unwrap();
+        fs::write(
+            path("program.json"),
+            json!({
+                "id": "P1",
+                "name": "Clean Water",
+                "total_pool": 100000,
+                "min_award": 1000,
+                "max_award": 50000
+            })
+            .to_string(),
+        )
+        .unwrap();
+        let input = [
+            application("APP-1", 10_000.0),
+            application("APP-2", 60_000.0),
+        ]
+        .map(|application| application.to_string())
+        .join("\n");
+        fs::write(path("applications.ndjson"), input).unwrap();
+        let mut store = JournalStore::open(path("store.jsonl")).unwrap();
+        store.set_processed_count(2).unwrap();
+        drop(store);
+        let journal = fs::read(path("store.jsonl")).unwrap();
+
+        let error = run_cli(&[
+            "--config",
+            &path("grantprogram.toml"),
+            "--program",
+            &path("program.json"),
+            "--store",
+            &path("store.jsonl"),
+            "--output",
+            &path("report.json"),
+            "validate",
+            "--input",
+            &path("applications.ndjson"),
+        ])
+        .unwrap_err();
+
+        assert_eq!(exit_code(&error), 5, "{error}");
+        assert_eq!(fs::read(path("store.jsonl")).unwrap(), journal);
+        let report: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(path("report.json")).unwrap()).unwrap();
+        assert_eq!(report["summary"]["records"], 2);
+        assert_eq!(report["summary"]["errors"], 1);
+        assert_eq!(report["diagnostics"][0]["rule_id"], "program.max_award");
+    }
 }
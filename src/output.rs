@@ -0,0 +1,914 @@
+// src/output.rs
+/*
+ * Writers that render a run report in the supported output formats
+ */
+
+use crate::{
+    Allocation, ApplicationStatus, BatchReport, BatchSink, Conflict, GrantError, Lifecycle, Record,
+    RecordError, RecordOutcome, Result, ScoredApplication, Store, Verdict,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::io::Write;
+
+/// Format used to render a `RunReport`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum OutputFormat {
+    /// Compact single-line JSON
+    Json,
+    /// Indented JSON
+    JsonPretty,
+    /// One JSON object per line, tagged with a `kind` field
+    Ndjson,
+    /// Per-application table, a blank line, then a `metric,value` table
+    Csv,
+    /// Markdown tables
+    Markdown,
+    /// XML document
+    Xml,
+}
+
+/// Everything a run produces
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RunReport {
+    /// Processor statistics as returned by `get_stats`
+    pub stats: serde_json::Value,
+    /// Conflicts of interest recorded while assigning reviewers to the processed
+    /// applications, or held by a reviewer assigned to one
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub conflicts: Vec<Conflict>,
+    /// Per-record results, malformed records and the batch summary
+    #[serde(flatten)]
+    pub batch: BatchReport,
+    /// Applications under review, best first, when the run stopped at `Stage::Score`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub ranking: Vec<ScoredApplication>,
+    /// Allocation of the program pool, when a program was loaded
+    pub allocation: Option<Allocation>,
+}
+
+/// Where a stored application stands in the grant cycle
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Standing {
+    /// Application identifier
+    pub application_id: String,
+    /// Project title
+    pub title: String,
+    /// Applicant display name
+    pub applicant: String,
+    /// Program the application targets
+    pub program: String,
+    /// Current status; `None` if no status change was recorded
+    pub status: Option<ApplicationStatus>,
+    /// Amount requested
+    pub requested: f64,
+    /// Amount awarded, once the pool was allocated
+    pub awarded: Option<f64>,
+    /// When the status last changed
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+/// List where every application in a store stands
+///
+/// # Arguments
+///
+/// * `store` - Store holding the applications, their lifecycles and awards
+///
+/// # Returns
+///
+/// One `Standing` per stored application, ordered by id
+pub fn standings(store: &dyn Store) -> Result<Vec<Standing>> {
+    let lifecycles: HashMap<String, Lifecycle> = store
+        .lifecycles()?
+        .into_iter()
+        .map(|lifecycle| (lifecycle.application_id.clone(), lifecycle))
+        .collect();
+    let awards: HashMap<String, f64> = store
+        .awards()?
+        .into_iter()
+        .map(|award| (award.application_id, award.awarded))
+        .collect();
+
+    Ok(store
+        .applications()?
+        .into_iter()
+        .map(|application| {
+            let lifecycle = lifecycles.get(&application.id);
+            Standing {
+                status: lifecycle.map(|lifecycle| lifecycle.status),
+                updated_at: lifecycle
+                    .and_then(|lifecycle| lifecycle.history.last())
+                    .map(|change| change.at),
+                awarded: awards.get(&application.id).copied(),
+                requested: application.funding.amount,
+                applicant: application.applicant.name,
+                program: application.program.id,
+                title: application.title,
+                application_id: application.id,
+            }
+        })
+        .collect())
+}
+
+/// Writes each record outcome as an NDJSON line as soon as it is produced
+pub struct NdjsonSink<W: Write> {
+    writer: W,
+}
+
+impl<W: Write> NdjsonSink<W> {
+    /// Create a sink writing to `writer`
+    pub fn new(writer: W) -> Self {
+        Self { writer }
+    }
+
+    /// Give back the underlying writer
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl<W: Write> BatchSink for NdjsonSink<W> {
+    fn on_result(&mut self, _record: &Record, outcome: RecordOutcome) -> Result<()> {
+        let value = serde_json::to_value(&outcome).map_err(GrantError::output)?;
+        self.writer
+            .write_all(ndjson_line("result", value).as_bytes())?;
+        Ok(())
+    }
+
+    fn on_error(&mut self, error: RecordError) -> Result<()> {
+        let value = serde_json::to_value(&error).map_err(GrantError::output)?;
+        self.writer
+            .write_all(ndjson_line("error", value).as_bytes())?;
+        Ok(())
+    }
+}
+
+/// One row of the per-application tables
+struct ResultRow {
+    index: usize,
+    line: usize,
+    application_id: String,
+    success: bool,
+    message: String,
+    failed_rules: String,
+    score: Option<f64>,
+    awarded: Option<f64>,
+}
+
+impl RunReport {
+    /// Render the report in the given format
+    ///
+    /// # Arguments
+    ///
+    /// * `format` - Output format
+    ///
+    /// # Returns
+    ///
+    /// The rendered report
+    pub fn render(&self, format: OutputFormat) -> Result<String> {
+        match format {
+            OutputFormat::Json => serde_json::to_string(self).map_err(GrantError::output),
+            OutputFormat::JsonPretty => {
+                serde_json::to_string_pretty(self).map_err(GrantError::output)
+            }
+            OutputFormat::Ndjson => self.to_ndjson(),
+            OutputFormat::Csv => self.to_csv(),
+            OutputFormat::Markdown => Ok(self.to_markdown()),
+            OutputFormat::Xml => Ok(self.to_xml()),
+        }
+    }
+
+    fn rows(&self) -> Vec<ResultRow> {
+        let awards: HashMap<&str, f64> = self
+            .allocation
+            .iter()
+            .flat_map(|allocation| &allocation.awards)
+            .map(|award| (award.application_id.as_str(), award.awarded))
+            .collect();
+
+        self.batch
+            .results
+            .iter()
+            .map(|outcome| {
+                let application_id = outcome
+                    .result
+                    .data
+                    .as_ref()
+                    .and_then(|data| data.get("application_id"))
+                    .and_then(|id| id.as_str())
+                    .unwrap_or_default()
+                    .to_string();
+                let awarded = awards.get(application_id.as_str()).copied();
+                let failed_rules = outcome
+                    .result
+                    .rules
+                    .iter()
+                    .filter(|rule| rule.verdict == Verdict::Failed)
+                    .map(|rule| rule.rule_id.as_str())
+                    .collect::<Vec<_>>()
+                    .join(";");
+
+                ResultRow {
+                    index: outcome.index,
+                    line: outcome.line,
+                    application_id,
+                    success: outcome.result.success,
+                    message: outcome.result.message.clone(),
+                    failed_rules,
+                    score: outcome.result.score(),
+                    awarded,
+                }
+            })
+            .collect()
+    }
+
+    /// Summary counts, statistics and allocation totals as `metric, value` pairs
+    fn metrics(&self) -> Vec<(String, String)> {
+        let mut metrics = Vec::new();
+        let summary = serde_json::to_value(&self.batch.summary).unwrap_or_default();
+        flatten("summary", &summary, &mut metrics);
+        flatten("stats", &self.stats, &mut metrics);
+        if !self.conflicts.is_empty() {
+            metrics.push(("conflicts".into(), self.conflicts.len().to_string()));
+        }
+        if let Some(allocation) = &self.allocation {
+            metrics.push((
+                "allocation.strategy".into(),
+                allocation.strategy.to_string(),
+            ));
+            metrics.push(("allocation.pool".into(), format!("{:.2}", allocation.pool)));
+            metrics.push((
+                "allocation.total_awarded".into(),
+                format!("{:.2}", allocation.total_awarded),
+            ));
+            metrics.push((
+                "allocation.remainder".into(),
+                format!("{:.2}", allocation.remainder),
+            ));
+        }
+        metrics
+    }
+
+    fn to_ndjson(&self) -> Result<String> {
+        let mut out = String::new();
+        for outcome in &self.batch.results {
+            out.push_str(&ndjson_line(
+                "result",
+                serde_json::to_value(outcome).map_err(GrantError::output)?,
+            ));
+        }
+        for error in &self.batch.errors {
+            out.push_str(&ndjson_line(
+                "error",
+                serde_json::to_value(error).map_err(GrantError::output)?,
+            ));
+        }
+        out.push_str(&self.render_ndjson_tail()?);
+        Ok(out)
+    }
+
+    /// Render the NDJSON lines that follow the per-record lines
+    ///
+    /// Used when `result` and `error` lines have already been streamed while
+    /// the batch was processed.
+    ///
+    /// # Returns
+    ///
+    /// The `award`, `summary`, `conflict` and `stats` lines
+    pub fn render_ndjson_tail(&self) -> Result<String> {
+        let mut out = String::new();
+        if let Some(allocation) = &self.allocation {
+            for award in &allocation.awards {
+                out.push_str(&ndjson_line(
+                    "award",
+                    serde_json::to_value(award).map_err(GrantError::output)?,
+                ));
+            }
+        }
+        out.push_str(&ndjson_line(
+            "summary",
+            serde_json::to_value(&self.batch.summary).map_err(GrantError::output)?,
+        ));
+        for conflict in &self.conflicts {
+            out.push_str(&ndjson_line(
+                "conflict",
+                serde_json::to_value(conflict).map_err(GrantError::output)?,
+            ));
+        }
+        out.push_str(&ndjson_line("stats", self.stats.clone()));
+        Ok(out)
+    }
+
+    fn to_csv(&self) -> Result<String> {
+        let mut writer = csv::Writer::from_writer(Vec::new());
+        writer
+            .write_record([
+                "index",
+                "line",
+                "application_id",
+                "success",
+                "message",
+                "failed_rules",
+                "score",
+                "awarded",
+            ])
+            .map_err(GrantError::output)?;
+        for row in self.rows() {
+            writer
+                .write_record([
+                    row.index.to_string(),
+                    row.line.to_string(),
+                    row.application_id,
+                    row.success.to_string(),
+                    row.message,
+                    row.failed_rules,
+                    row.score.map(|s| format!("{:.2}", s)).unwrap_or_default(),
+                    row.awarded.map(|a| format!("{:.2}", a)).unwrap_or_default(),
+                ])
+                .map_err(GrantError::output)?;
+        }
+        let mut out = finish_csv(writer)?;
+
+        out.push('\n');
+        let mut writer = csv::Writer::from_writer(Vec::new());
+        writer
+            .write_record(["metric", "value"])
+            .map_err(GrantError::output)?;
+        for (metric, value) in self.metrics() {
+            writer
+                .write_record([metric, value])
+                .map_err(GrantError::output)?;
+        }
+        out.push_str(&finish_csv(writer)?);
+        Ok(out)
+    }
+
+    fn to_markdown(&self) -> String {
+        let mut out = String::from("# GrantProgram Report\n\n## Summary\n\n");
+        out.push_str("| Metric | Value |\n| --- | --- |\n");
+        for (metric, value) in self.metrics() {
+            let _ = writeln!(out, "| {} | {} |", md(&metric), md(&value));
+        }
+
+        out.push_str("\n## Results\n\n");
+        out.push_str(
+            "| # | Line | Application | Success | Failed rules | Score | Awarded | Message |\n",
+        );
+        out.push_str("| ---: | ---: | --- | --- | --- | ---: | ---: | --- |\n");
+        for row in self.rows() {
+            let _ = writeln!(
+                out,
+                "| {} | {} | {} | {} | {} | {} | {} | {} |",
+                row.index,
+                row.line,
+                md(&row.application_id),
+                if row.success { "yes" } else { "no" },
+                md(&row.failed_rules),
+                row.score.map(|s| format!("{:.2}", s)).unwrap_or_default(),
+                row.awarded.map(|a| format!("{:.2}", a)).unwrap_or_default(),
+                md(&row.message)
+            );
+        }
+
+        if !self.batch.errors.is_empty() {
+            out.push_str("\n## Malformed records\n\n");
+            out.push_str("| # | Line | Column | Error |\n| ---: | ---: | ---: | --- |\n");
+            for error in &self.batch.errors {
+                let _ = writeln!(
+                    out,
+                    "| {} | {} | {} | {} |",
+                    error.index,
+                    error.line,
+                    error.column.map(|c| c.to_string()).unwrap_or_default(),
+                    md(&error.message)
+                );
+            }
+        }
+
+        if !self.conflicts.is_empty() {
+            out.push_str("\n## Conflicts of interest\n\n");
+            out.push_str(
+                "| Application | Reviewer | Reason | Detail |\n| --- | --- | --- | --- |\n",
+            );
+            for conflict in &self.conflicts {
+                let _ = writeln!(
+                    out,
+                    "| {} | {} | {} | {} |",
+                    md(&conflict.application_id),
+                    md(&conflict.reviewer_id),
+                    conflict.reason,
+                    md(&conflict.detail)
+                );
+            }
+        }
+
+        if let Some(allocation) = &self.allocation {
+            out.push_str("\n## Allocation\n\n");
+            out.push_str("| Application | Score | Requested | Awarded | Rationale |\n");
+            out.push_str("| --- | ---: | ---: | ---: | --- |\n");
+            for award in &allocation.awards {
+                let _ = writeln!(
+                    out,
+                    "| {} | {:.2} | {:.2} | {:.2} | {} |",
+                    md(&award.application_id),
+                    award.score,
+                    award.requested,
+                    award.awarded,
+                    md(&award.rationale)
+                );
+            }
+        }
+        out
+    }
+
+    fn to_xml(&self) -> String {
+        let mut out = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<report>\n");
+
+        out.push_str("  <summary>\n");
+        for (metric, value) in self.metrics() {
+            let _ = writeln!(
+                out,
+                "    <metric name=\"{}\">{}</metric>",
+                xml(&metric),
+                xml(&value)
+            );
+        }
+        out.push_str("  </summary>\n  <results>\n");
+
+        for outcome in &self.batch.results {
+            let _ = writeln!(
+                out,
+                "    <result index=\"{}\" line=\"{}\" success=\"{}\">",
+                outcome.index, outcome.line, outcome.result.success
+            );
+            let _ = writeln!(
+                out,
+                "      <message>{}</message>",
+                xml(&outcome.result.message)
+            );
+            if let Some(data) = &outcome.result.data {
+                let _ = writeln!(out, "      <data>{}</data>", xml(&data.to_string()));
+            }
+            for rule in &outcome.result.rules {
+                let verdict = serde_json::to_value(rule.verdict).unwrap_or_default();
+                let _ = writeln!(
+                    out,
+                    "      <rule id=\"{}\" verdict=\"{}\">{}</rule>",
+                    xml(&rule.rule_id),
+                    xml(verdict.as_str().unwrap_or_default()),
+                    xml(&rule.reason)
+                );
+            }
+            out.push_str("    </result>\n");
+        }
+        out.push_str("  </results>\n");
+
+        if !self.batch.errors.is_empty() {
+            out.push_str("  <errors>\n");
+            for error in &self.batch.errors {
+                let column = error
+                    .column
+                    .map(|c| format!(" column=\"{}\"", c))
+                    .unwrap_or_default();
+                let _ = writeln!(
+                    out,
+                    "    <error index=\"{}\" line=\"{}\"{}>{}</error>",
+                    error.index,
+                    error.line,
+                    column,
+                    xml(&error.message)
+                );
+            }
+            out.push_str("  </errors>\n");
+        }
+
+        if !self.conflicts.is_empty() {
+            out.push_str("  <conflicts>\n");
+            for conflict in &self.conflicts {
+                let _ = writeln!(
+                    out,
+                    "    <conflict application_id=\"{}\" reviewer_id=\"{}\" reason=\"{}\">{}</conflict>",
+                    xml(&conflict.application_id),
+                    xml(&conflict.reviewer_id),
+                    conflict.reason,
+                    xml(&conflict.detail)
+                );
+            }
+            out.push_str("  </conflicts>\n");
+        }
+
+        if let Some(allocation) = &self.allocation {
+            let _ = writeln!(
+                out,
+                "  <allocation strategy=\"{}\" pool=\"{:.2}\" total_awarded=\"{:.2}\" remainder=\"{:.2}\">",
+                allocation.strategy, allocation.pool, allocation.total_awarded, allocation.remainder
+            );
+            for award in &allocation.awards {
+                let _ = writeln!(
+                    out,
+                    "    <award application_id=\"{}\" score=\"{:.2}\" requested=\"{:.2}\" awarded=\"{:.2}\">{}</award>",
+                    xml(&award.application_id),
+                    award.score,
+                    award.requested,
+                    award.awarded,
+                    xml(&award.rationale)
+                );
+            }
+            out.push_str("  </allocation>\n");
+        }
+
+        out.push_str("</report>\n");
+        out
+    }
+}
+
+/// Render a list of records as a flat table in the given format
+///
+/// Used for command output that is not a `RunReport`, such as stored
+/// applications or a ranking. Nested fields become dotted columns in the CSV,
+/// Markdown and XML formats; lists are written as JSON text. With no rows
+/// there are no columns either, so CSV output is empty and Markdown output
+/// says so instead of drawing a table.
+///
+/// # Arguments
+///
+/// * `rows` - Records to render, all of the same shape
+/// * `format` - Output format
+///
+/// # Returns
+///
+/// The rendered table
+pub fn render_rows<T: Serialize>(rows: &[T], format: OutputFormat) -> Result<String> {
+    match format {
+        OutputFormat::Json => serde_json::to_string(rows).map_err(GrantError::output),
+        OutputFormat::JsonPretty => serde_json::to_string_pretty(rows).map_err(GrantError::output),
+        OutputFormat::Ndjson => {
+            let mut out = String::new();
+            for row in rows {
+                let line = serde_json::to_string(row).map_err(GrantError::output)?;
+                let _ = writeln!(out, "{}", line);
+            }
+            Ok(out)
+        }
+        OutputFormat::Csv | OutputFormat::Markdown | OutputFormat::Xml => {
+            let mut columns: Vec<String> = Vec::new();
+            let mut cells = Vec::with_capacity(rows.len());
+            for row in rows {
+                let value = serde_json::to_value(row).map_err(GrantError::output)?;
+                let mut fields = Vec::new();
+                match &value {
+                    serde_json::Value::Object(object) => {
+                        for (key, value) in object {
+                            flatten(key, value, &mut fields);
+                        }
+                    }
+                    other => flatten("value", other, &mut fields),
+                }
+                for (column, _) in &fields {
+                    if !columns.contains(column) {
+                        columns.push(column.clone());
+                    }
+                }
+                cells.push(fields.into_iter().collect::<HashMap<_, _>>());
+            }
+            let table = cells.iter().map(|fields| {
+                columns
+                    .iter()
+                    .map(|column| fields.get(column).cloned().unwrap_or_default())
+                    .collect::<Vec<_>>()
+            });
+
+            match format {
+                // Without rows there are no column names to build a header from
+                OutputFormat::Csv if columns.is_empty() => Ok(String::new()),
+                OutputFormat::Markdown if columns.is_empty() => Ok("_No rows._\n".to_string()),
+                OutputFormat::Csv => {
+                    let mut writer = csv::Writer::from_writer(Vec::new());
+                    writer.write_record(&columns).map_err(GrantError::output)?;
+                    for row in table {
+                        writer.write_record(row).map_err(GrantError::output)?;
+                    }
+                    finish_csv(writer)
+                }
+                OutputFormat::Markdown => {
+                    let mut out = String::new();
+                    let header: Vec<String> = columns.iter().map(|column| md(column)).collect();
+                    let _ = writeln!(out, "| {} |", header.join(" | "));
+                    let _ = writeln!(out, "|{}", " --- |".repeat(columns.len()));
+                    for row in table {
+                        let row: Vec<String> = row.iter().map(|cell| md(cell)).collect();
+                        let _ = writeln!(out, "| {} |", row.join(" | "));
+                    }
+                    Ok(out)
+                }
+                _ => {
+                    let mut out =
+                        String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<rows>\n");
+                    for row in table {
+                        out.push_str("  <row>\n");
+                        for (column, cell) in columns.iter().zip(row) {
+                            let _ = writeln!(
+                                out,
+                                "    <field name=\"{}\">{}</field>",
+                                xml(column),
+                                xml(&cell)
+                            );
+                        }
+                        out.push_str("  </row>\n");
+                    }
+                    out.push_str("</rows>\n");
+                    Ok(out)
+                }
+            }
+        }
+    }
+}
+
+fn finish_csv(writer: csv::Writer<Vec<u8>>) -> Result<String> {
+    let bytes = writer.into_inner().map_err(GrantError::output)?;
+    String::from_utf8(bytes).map_err(GrantError::output)
+}
+
+/// Render one NDJSON line, adding a `kind` tag to the value
+pub(crate) fn ndjson_line(kind: &str, value: serde_json::Value) -> String {
+    let line = match value {
+        serde_json::Value::Object(mut fields) => {
+            fields.insert("kind".into(), kind.into());
+            serde_json::Value::Object(fields)
+        }
+        other => serde_json::json!({ "kind": kind, "value": other }),
+    };
+    format!("{}\n", line)
+}
+
+fn flatten(prefix: &str, value: &serde_json::Value, out: &mut Vec<(String, String)>) {
+    match value {
+        serde_json::Value::Object(fields) => {
+            for (key, value) in fields {
+                flatten(&format!("{}.{}", prefix, key), value, out);
+            }
+        }
+        serde_json::Value::String(text) => out.push((prefix.to_string(), text.clone())),
+        serde_json::Value::Null => out.push((prefix.to_string(), String::new())),
+        other => out.push((prefix.to_string(), other.to_string())),
+    }
+}
+
+/// Escape text for a Markdown table cell, keeping it on one line
+fn md(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '|' => escaped.push_str("\\|"),
+            '<' => escaped.push_str("&lt;"),
+            '&' => escaped.push_str("&amp;"),
+            '\r' if chars.peek() == Some(&'\n') => {}
+            '\r' | '\n' => escaped.push(' '),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Escape text for XML, replacing characters XML 1.0 does not allow with U+FFFD
+fn xml(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            '\t' | '\n' | '\r' => escaped.push(c),
+            '\u{0}'..='\u{1f}' | '\u{fffe}' | '\u{ffff}' => {
+                escaped.push(char::REPLACEMENT_CHARACTER)
+            }
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::application;
+    use crate::{BatchSummary, ConflictKind, GrantProgramProcessor};
+
+    const TRICKY: &str = "a | b\r\nc <d> & \"e\" 'f'";
+
+    fn report() -> RunReport {
+        let mut processor = GrantProgramProcessor::new(false, None);
+        let result = processor
+            .process_application(&application("APP-1", 1000.0))
+            .unwrap();
+        RunReport {
+            stats: serde_json::json!({ "processed_count": 1 }),
+            conflicts: vec![Conflict {
+                application_id: "APP-1".to_string(),
+                reviewer_id: "R<1>".to_string(),
+                reason: ConflictKind::Declared,
+                detail: TRICKY.to_string(),
+            }],
+            batch: BatchReport {
+                summary: BatchSummary {
+                    total: 2,
+                    processed: 1,
+                    succeeded: 1,
+                    malformed: 1,
+                    ..BatchSummary::default()
+                },
+                results: vec![RecordOutcome {
+                    index: 1,
+                    line: 1,
+                    result,
+                }],
+                errors: vec![RecordError {
+                    index: 2,
+                    line: 3,
+                    column: Some(7),
+                    message: TRICKY.to_string(),
+                    violations: Vec::new(),
+                }],
+            },
+            ranking: Vec::new(),
+            allocation: None,
+        }
+    }
+
+    #[derive(Serialize)]
+    struct Row {
+        id: String,
+        note: Option<String>,
+        nested: serde_json::Value,
+        tags: Vec<String>,
+    }
+
+    fn rows() -> Vec<Row> {
+        vec![
+            Row {
+                id: "R1".to_string(),
+                note: Some(TRICKY.to_string()),
+                nested: serde_json::json!({ "amount": 10 }),
+                tags: vec!["x".to_string(), "y, z".to_string()],
+            },
+            Row {
+                id: "R2".to_string(),
+                note: None,
+                nested: serde_json::json!({ "extra": true }),
+                tags: Vec::new(),
+            },
+        ]
+    }
+
+    /// Parse CSV text into its records, header included
+    fn parse_csv(text: &str) -> Vec<Vec<String>> {
+        csv::ReaderBuilder::new()
+            .has_headers(false)
+            .from_reader(text.as_bytes())
+            .records()
+            .map(|record| record.unwrap().iter().map(str::to_string).collect())
+            .collect()
+    }
+
+    #[test]
+    fn csv_report_holds_a_results_table_then_metrics() {
+        let out = report().render(OutputFormat::Csv).unwrap();
+        let (results, metrics) = out.split_once("\n\n").unwrap();
+
+        let results = parse_csv(results);
+        assert_eq!(
+            results[0],
+            [
+                "index",
+                "line",
+                "application_id",
+                "success",
+                "message",
+                "failed_rules",
+                "score",
+                "awarded"
+            ]
+        );
+        assert_eq!(results[1][..4], ["1", "1", "APP-1", "true"]);
+        assert_eq!(results.len(), 2);
+
+        let metrics = parse_csv(metrics);
+        assert_eq!(metrics[0], ["metric", "value"]);
+        assert!(metrics.contains(&vec!["summary.malformed".to_string(), "1".to_string()]));
+        assert!(metrics.contains(&vec!["stats.processed_count".to_string(), "1".to_string()]));
+        assert!(metrics.contains(&vec!["conflicts".to_string(), "1".to_string()]));
+    }
+
+    #[test]
+    fn markdown_report_escapes_cells() {
+        let out = report().render(OutputFormat::Markdown).unwrap();
+
+        let escaped = "a \\| b c &lt;d> &amp; \"e\" 'f'";
+        assert!(
+            out.contains(&format!("| 2 | 3 | 7 | {escaped} |\n")),
+            "{out}"
+        );
+        assert!(
+            out.contains(&format!("| APP-1 | R&lt;1> | declared | {escaped} |\n")),
+            "{out}"
+        );
+        assert!(out.contains("| summary.malformed | 1 |\n"), "{out}");
+        assert!(out.contains("| 1 | 1 | APP-1 | yes |"), "{out}");
+    }
+
+    #[test]
+    fn xml_report_escapes_text_and_attributes() {
+        let out = report().render(OutputFormat::Xml).unwrap();
+
+        let escaped = "a | b\r\nc &lt;d&gt; &amp; &quot;e&quot; &apos;f&apos;";
+        assert!(
+            out.contains(&format!(
+                "<error index=\"2\" line=\"3\" column=\"7\">{escaped}</error>"
+            )),
+            "{out}"
+        );
+        assert!(
+            out.contains(&format!(
+                "<conflict application_id=\"APP-1\" reviewer_id=\"R&lt;1&gt;\" reason=\"declared\">{escaped}</conflict>"
+            )),
+            "{out}"
+        );
+        assert!(out.starts_with("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<report>\n"));
+        assert!(out.ends_with("</report>\n"));
+    }
+
+    #[test]
+    fn xml_replaces_characters_xml_does_not_allow() {
+        assert_eq!(
+            xml("bell\u{7}nul\u{0}\ttab\nend\u{fffe}\u{1f600}"),
+            "bell\u{fffd}nul\u{fffd}\ttab\nend\u{fffd}\u{1f600}"
+        );
+    }
+
+    #[test]
+    fn csv_rows_round_trip_through_a_csv_reader() {
+        let out = render_rows(&rows(), OutputFormat::Csv).unwrap();
+
+        assert_eq!(
+            parse_csv(&out),
+            [
+                vec!["id", "note", "nested.amount", "tags", "nested.extra"],
+                vec!["R1", TRICKY, "10", "[\"x\",\"y, z\"]", ""],
+                vec!["R2", "", "", "[]", "true"],
+            ]
+        );
+    }
+
+    #[test]
+    fn markdown_rows_escape_cells() {
+        let out = render_rows(&rows(), OutputFormat::Markdown).unwrap();
+
+        assert_eq!(
+            out.lines().collect::<Vec<_>>(),
+            [
+                "| id | note | nested.amount | tags | nested.extra |",
+                "| --- | --- | --- | --- | --- |",
+                "| R1 | a \\| b c &lt;d> &amp; \"e\" 'f' | 10 | [\"x\",\"y, z\"] |  |",
+                "| R2 |  |  | [] | true |",
+            ]
+        );
+    }
+
+    #[test]
+    fn xml_rows_escape_names_and_values() {
+        let out = render_rows(&rows()[..1], OutputFormat::Xml).unwrap();
+
+        assert!(
+            out.contains(
+                "<field name=\"note\">a | b\r\nc &lt;d&gt; &amp; &quot;e&quot; &apos;f&apos;</field>"
+            ),
+            "{out}"
+        );
+        assert!(
+            out.contains("<field name=\"nested.amount\">10</field>"),
+            "{out}"
+        );
+    }
+
+    #[test]
+    fn no_rows_still_render_as_valid_documents() {
+        let empty: [Row; 0] = [];
+        assert_eq!(
+            render_rows(&empty, OutputFormat::Markdown).unwrap(),
+            "_No rows._\n"
+        );
+        assert_eq!(
+            render_rows(&empty, OutputFormat::Xml).unwrap(),
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<rows>\n</rows>\n"
+        );
+        assert_eq!(render_rows(&empty, OutputFormat::Json).unwrap(), "[]");
+        assert_eq!(render_rows(&empty, OutputFormat::Ndjson).unwrap(), "");
+        assert_eq!(render_rows(&empty, OutputFormat::Csv).unwrap(), "");
+    }
+}
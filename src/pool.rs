@@ -0,0 +1,318 @@
+// src/pool.rs
+/*
+ * Worker pool evaluating batch items in parallel while keeping input order
+ */
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::num::NonZeroUsize;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::mpsc;
+use std::sync::{Mutex, PoisonError};
+use std::thread;
+use std::time::Duration;
+
+/// Items queued per worker before the pool waits for results
+const QUEUE_PER_THREAD: usize = 64;
+
+/// Number of workers to use for a `threads` setting
+///
+/// # Arguments
+///
+/// * `threads` - Requested workers; 0 means one per available CPU
+///
+/// # Returns
+///
+/// At least one worker
+pub fn worker_count(threads: usize) -> usize {
+    match threads {
+        0 => thread::available_parallelism().map_or(1, NonZeroUsize::get),
+        threads => threads,
+    }
+}
+
+/// How far a batch has got
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Progress {
+    /// Records handled so far
+    pub done: usize,
+    /// Records in the batch, when known before the end
+    pub total: Option<usize>,
+    /// Time since the batch started
+    pub elapsed: Duration,
+}
+
+impl Progress {
+    /// Records handled per second so far
+    pub fn rate(&self) -> f64 {
+        let seconds = self.elapsed.as_secs_f64();
+        if seconds > 0.0 {
+            self.done as f64 / seconds
+        } else {
+            0.0
+        }
+    }
+}
+
+impl fmt::Display for Progress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.total {
+            Some(total) if total > 0 => write!(
+                f,
+                "{} of {} record(s) ({:.1}%)",
+                self.done,
+                total,
+                self.done as f64 * 100.0 / total as f64
+            )?,
+            _ => write!(f, "{} record(s)", self.done)?,
+        }
+        write!(
+            f,
+            " in {:.1}s, {:.0} per second",
+            self.elapsed.as_secs_f64(),
+            self.rate()
+        )
+    }
+}
+
+/// Map every item on a pool of workers and hand the results over in input order
+///
+/// Items are pulled from `items` on the calling thread, so the iterator does
+/// not have to be `Send`; only a bounded number are queued at any time, so a
+/// long stream is never read ahead in full. `emit` runs on the calling thread
+/// and sees the results in exactly the order of the items, whatever the
+/// number of workers. With a single worker everything runs on the calling
+/// thread.
+///
+/// # Arguments
+///
+/// * `items` - Items to map, in order
+/// * `threads` - Number of workers; 0 means one per available CPU
+/// * `map` - Work done on the workers
+/// * `emit` - Receives each result in input order; an error stops the pool
+///
+/// # Returns
+///
+/// `Ok(())`, or the first error returned by `emit`. A panic in `map` is
+/// propagated to the caller.
+pub fn map_ordered<T, R, Error, F, E>(
+    items: impl IntoIterator<Item = T>,
+    threads: usize,
+    map: F,
+    mut emit: E,
+) -> std::result::Result<(), Error>
+where
+    T: Send,
+    R: Send,
+    F: Fn(T) -> R + Sync,
+    E: FnMut(R) -> std::result::Result<(), Error>,
+{
+    let workers = worker_count(threads);
+    if workers == 1 {
+        for item in items {
+            emit(map(item))?;
+        }
+        return Ok(());
+    }
+
+    let capacity = workers * QUEUE_PER_THREAD;
+    let (job_tx, job_rx) = mpsc::channel::<(usize, T)>();
+    let (done_tx, done_rx) = mpsc::channel::<(usize, thread::Result<R>)>();
+    let job_rx = Mutex::new(job_rx);
+
+    thread::scope(|scope| {
+        for _ in 0..workers {
+            let (job_rx, done_tx, map) = (&job_rx, done_tx.clone(), &map);
+            scope.spawn(move || loop {
+                let job = job_rx.lock().unwrap_or_else(PoisonError::into_inner).recv();
+                let Ok((position, item)) = job else {
+                    break;
+                };
+                let result = panic::catch_unwind(AssertUnwindSafe(|| map(item)));
+                if done_tx.send((position, result)).is_err() {
+                    break;
+                }
+            });
+        }
+        // Dropping the queue and result channels on the way out stops the workers
+        let (job_tx, done_rx) = (job_tx, done_rx);
+        drop(done_tx);
+
+        let mut items = items.into_iter();
+        let mut finished: BTreeMap<usize, R> = BTreeMap::new();
+        let (mut queued, mut emitted, mut exhausted) = (0, 0, false);
+        loop {
+            while !exhausted && queued - emitted < capacity {
+                match items.next() {
+                    Some(item) => {
+                        job_tx
+                            .send((queued, item))
+                            .expect("workers run until the queue is dropped");
+                        queued += 1;
+                    }
+                    None => exhausted = true,
+                }
+            }
+            if emitted == queued {
+                return Ok(());
+            }
+
+            let (position, result) = done_rx
+                .recv()
+                .expect("workers run until the queue is dropped");
+            match result {
+                Ok(result) => {
+                    finished.insert(position, result);
+                }
+                Err(payload) => panic::resume_unwind(payload),
+            }
+            while let Some(result) = finished.remove(&emitted) {
+                emitted += 1;
+                emit(result)?;
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+    use std::thread::ThreadId;
+
+    /// Map `0..count` with a delay that makes later items finish first
+    fn squares(count: u64, threads: usize) -> Vec<u64> {
+        let mut emitted = Vec::new();
+        map_ordered(
+            0..count,
+            threads,
+            |item| {
+                thread::sleep(Duration::from_micros((count - item) % 7 * 50));
+                item * item
+            },
+            |square| {
+                emitted.push(square);
+                Ok::<(), ()>(())
+            },
+        )
+        .unwrap();
+        emitted
+    }
+
+    #[test]
+    fn results_are_emitted_in_input_order() {
+        let expected: Vec<u64> = (0..1000).map(|item| item * item).collect();
+        for threads in [1, 2, 4, 8, 0] {
+            assert_eq!(squares(1000, threads), expected, "{threads} thread(s)");
+        }
+    }
+
+    #[test]
+    fn streams_longer_than_the_queue_are_read_lazily() {
+        let workers = 4;
+        let pulled = Cell::new(0usize);
+        let mut most_ahead = 0;
+        let mut emitted = 0;
+        let items = (0..workers * QUEUE_PER_THREAD * 5).inspect(|_| pulled.set(pulled.get() + 1));
+        map_ordered(
+            items,
+            workers,
+            |item| item,
+            |item| {
+                assert_eq!(item, emitted);
+                emitted += 1;
+                most_ahead = most_ahead.max(pulled.get() - emitted);
+                Ok::<(), ()>(())
+            },
+        )
+        .unwrap();
+
+        assert_eq!(emitted, workers * QUEUE_PER_THREAD * 5);
+        assert!(most_ahead <= workers * QUEUE_PER_THREAD, "{most_ahead}");
+    }
+
+    #[test]
+    fn an_error_from_emit_stops_the_pool() {
+        let mut emitted = Vec::new();
+        let result = map_ordered(
+            0..10_000,
+            4,
+            |item| item,
+            |item| {
+                if item == 10 {
+                    return Err(format!("stopped at {}", item));
+                }
+                emitted.push(item);
+                Ok(())
+            },
+        );
+
+        assert_eq!(result, Err("stopped at 10".to_string()));
+        assert_eq!(emitted, (0..10).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn one_worker_runs_on_the_calling_thread() {
+        let caller = thread::current().id();
+        let mut threads: Vec<ThreadId> = Vec::new();
+        map_ordered(
+            0..5,
+            1,
+            |_| thread::current().id(),
+            |id| {
+                threads.push(id);
+                Ok::<(), ()>(())
+            },
+        )
+        .unwrap();
+        assert!(threads.iter().all(|&id| id == caller));
+    }
+
+    #[test]
+    fn empty_input_emits_nothing() {
+        assert_eq!(squares(0, 4), Vec::<u64>::new());
+    }
+
+    #[test]
+    #[should_panic(expected = "item 3")]
+    fn a_panic_in_map_reaches_the_caller() {
+        let _ = map_ordered(
+            0..100,
+            4,
+            |item| {
+                if item == 3 {
+                    panic!("item 3");
+                }
+                item
+            },
+            |_| Ok::<(), ()>(()),
+        );
+    }
+
+    #[test]
+    fn worker_count_resolves_zero_to_the_cpus() {
+        assert_eq!(worker_count(3), 3);
+        assert!(worker_count(0) >= 1);
+    }
+
+    #[test]
+    fn progress_reports_rate_and_share() {
+        let progress = Progress {
+            done: 50,
+            total: Some(200),
+            elapsed: Duration::from_secs(2),
+        };
+        assert_eq!(progress.rate(), 25.0);
+        assert_eq!(
+            progress.to_string(),
+            "50 of 200 record(s) (25.0%) in 2.0s, 25 per second"
+        );
+        let open = Progress {
+            total: None,
+            elapsed: Duration::ZERO,
+            ..progress
+        };
+        assert_eq!(open.rate(), 0.0);
+        assert_eq!(open.to_string(), "50 record(s) in 0.0s, 0 per second");
+    }
+}
@@ -0,0 +1,598 @@
+// src/program.rs
+/*
+ * Grant program definitions and their eligibility checks
+ */
+
+use crate::rules::{self, Rule, RuleOutcome, Verdict};
+use crate::scoring::Rubric;
+use crate::{ApplicantType, GrantApplication, GrantError, Result};
+use chrono::{NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::path::Path;
+
+/// Period during which a program accepts applications (both ends inclusive)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FundingWindow {
+    /// First day applications are accepted
+    pub opens: NaiveDate,
+    /// Last day applications are accepted
+    pub closes: NaiveDate,
+}
+
+impl FundingWindow {
+    /// Check whether the given day falls inside the window
+    pub fn contains(&self, day: NaiveDate) -> bool {
+        day >= self.opens && day <= self.closes
+    }
+}
+
+/// Description of a grant program and the rules applications must meet
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProgramDefinition {
+    /// Program identifier, matched against `GrantApplication::program.id`
+    pub id: String,
+    /// Program display name
+    pub name: String,
+    /// Period during which applications are accepted
+    #[serde(default)]
+    pub window: Option<FundingWindow>,
+    /// Total money available for the whole program
+    pub total_pool: f64,
+    /// Smallest single award the program makes
+    #[serde(default)]
+    pub min_award: f64,
+    /// Largest single award the program makes
+    pub max_award: f64,
+    /// Applicant types allowed to apply; empty means everyone
+    #[serde(default)]
+    pub eligible_applicant_types: Vec<ApplicantType>,
+    /// Documents every application must include
+    #[serde(default)]
+    pub required_documents: Vec<String>,
+    /// Additional declarative eligibility rules
+    #[serde(default)]
+    pub rules: Vec<Rule>,
+    /// How eligible applications are scored and ranked
+    #[serde(default)]
+    pub rubric: Option<Rubric>,
+}
+
+impl ProgramDefinition {
+    /// Load a program definition from a JSON or TOML file
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path of the definition; files ending in `.toml` are read as TOML,
+    ///   everything else as JSON
+    ///
+    /// # Returns
+    ///
+    /// The parsed `ProgramDefinition`
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let definition: Self = crate::load_document(path.as_ref())?;
+        definition.validate()?;
+        Ok(definition)
+    }
+
+    /// Check that the definition itself is consistent
+    ///
+    /// # Returns
+    ///
+    /// `Ok(())`, or the first inconsistent field or rule
+    pub fn validate(&self) -> Result<()> {
+        if self.id.trim().is_empty() {
+            return Err(GrantError::validation("id", "must not be empty"));
+        }
+        if !self.total_pool.is_finite() || self.total_pool < 0.0 {
+            return Err(GrantError::validation(
+                "total_pool",
+                format!("must be a non-negative amount, got {}", self.total_pool),
+            ));
+        }
+        if !self.min_award.is_finite() || self.min_award < 0.0 {
+            return Err(GrantError::validation(
+                "min_award",
+                format!("must be a non-negative amount, got {}", self.min_award),
+            ));
+        }
+        if !self.max_award.is_finite() || self.max_award < self.min_award {
+            return Err(GrantError::validation(
+                "max_award",
+                format!(
+                    "must be at least min_award ({}), got {}",
+                    self.min_award, self.max_award
+                ),
+            ));
+        }
+        if let Some(window) = &self.window {
+            if window.opens > window.closes {
+                return Err(GrantError::validation(
+                    "window",
+                    format!(
+                        "opens on {} after closing on {}",
+                        window.opens, window.closes
+                    ),
+                ));
+            }
+        }
+
+        if let Some(rubric) = &self.rubric {
+            rubric.validate()?;
+        }
+
+        let mut seen = HashSet::new();
+        for rule in &self.rules {
+            if rule.id.trim().is_empty() {
+                return Err(GrantError::rule(&rule.id, "rule id must not be empty"));
+            }
+            if rule.id.starts_with("program.") {
+                return Err(GrantError::rule(
+                    &rule.id,
+                    "ids starting with 'program.' are reserved for built-in checks",
+                ));
+            }
+            if !seen.insert(rule.id.as_str()) {
+                return Err(GrantError::rule(
+                    &rule.id,
+                    "rule id is defined more than once",
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Check an application against this program's rules
+    ///
+    /// The built-in constraints (program match, funding window, award limits,
+    /// applicant type and required documents) are reported under `program.*`
+    /// rule ids, followed by the outcomes of the declarative `rules`.
+    ///
+    /// # Arguments
+    ///
+    /// * `application` - Application to check
+    ///
+    /// # Returns
+    ///
+    /// One `RuleOutcome` per rule evaluated
+    pub fn evaluate(&self, application: &GrantApplication) -> Vec<RuleOutcome> {
+        let mut outcomes = Vec::new();
+
+        outcomes.push(outcome(
+            "program.match",
+            application.program.id == self.id,
+            format!("application targets program '{}'", application.program.id),
+            format!(
+                "application targets program '{}' but was checked against '{}'",
+                application.program.id, self.id
+            ),
+        ));
+
+        if let Some(window) = &self.window {
+            let submitted = application
+                .submitted_at
+                .unwrap_or_else(Utc::now)
+                .date_naive();
+            outcomes.push(outcome(
+                "program.window",
+                window.contains(submitted),
+                format!("submitted on {} inside the funding window", submitted),
+                format!(
+                    "submitted on {} outside the funding window {} to {}",
+                    submitted, window.opens, window.closes
+                ),
+            ));
+        }
+
+        let amount = application.funding.amount;
+        outcomes.push(outcome(
+            "program.min_award",
+            amount >= self.min_award,
+            format!(
+                "requested {:.2} meets the minimum award of {:.2}",
+                amount, self.min_award
+            ),
+            format!(
+                "requested {:.2} is below the minimum award of {:.2}",
+                amount, self.min_award
+            ),
+        ));
+        outcomes.push(outcome(
+            "program.max_award",
+            amount <= self.max_award,
+            format!(
+                "requested {:.2} is within the maximum award of {:.2}",
+                amount, self.max_award
+            ),
+            format!(
+                "requested {:.2} exceeds the maximum award of {:.2}",
+                amount, self.max_award
+            ),
+        ));
+        outcomes.push(outcome(
+            "program.pool",
+            amount <= self.total_pool,
+            format!(
+                "requested {:.2} fits the program pool of {:.2}",
+                amount, self.total_pool
+            ),
+            format!(
+                "requested {:.2} exceeds the program pool of {:.2}",
+                amount, self.total_pool
+            ),
+        ));
+
+        if !self.eligible_applicant_types.is_empty() {
+            let org_type = application.applicant.org_type;
+            outcomes.push(outcome(
+                "program.applicant_type",
+                self.eligible_applicant_types.contains(&org_type),
+                format!("applicant type '{}' is eligible", org_type),
+                format!("applicant type '{}' is not eligible", org_type),
+            ));
+        }
+
+        if !self.required_documents.is_empty() {
+            let missing: Vec<&str> = self
+                .required_documents
+                .iter()
+                .filter(|document| !application.documents.contains(document))
+                .map(String::as_str)
+                .collect();
+            outcomes.push(outcome(
+                "program.documents",
+                missing.is_empty(),
+                "all required documents are attached".to_string(),
+                format!("missing required document(s): {}", missing.join(", ")),
+            ));
+        }
+
+        outcomes.extend(rules::evaluate_all(&self.rules, application));
+        outcomes
+    }
+
+    /// Application field a rule checks
+    ///
+    /// # Arguments
+    ///
+    /// * `rule_id` - Id of a built-in `program.*` check or of a declarative rule
+    ///
+    /// # Returns
+    ///
+    /// The dotted field path, or `None` for unknown rule ids
+    pub fn rule_field(&self, rule_id: &str) -> Option<String> {
+        let field = match rule_id {
+            "program.match" => "program.id",
+            "program.window" => "submitted_at",
+            "program.min_award" | "program.max_award" | "program.pool" => "funding.amount",
+            "program.applicant_type" => "applicant.org_type",
+            "program.documents" => "documents",
+            _ => {
+                return self
+                    .rules
+                    .iter()
+                    .find(|rule| rule.id == rule_id)
+                    .map(|rule| rule.check.field.clone())
+            }
+        };
+        Some(field.to_string())
+    }
+}
+
+fn outcome(rule_id: &str, passed: bool, pass_reason: String, fail_reason: String) -> RuleOutcome {
+    if passed {
+        RuleOutcome::new(rule_id, Verdict::Passed, pass_reason)
+    } else {
+        RuleOutcome::new(rule_id, Verdict::Failed, fail_reason)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::Condition;
+    use crate::test_support::{application, program};
+    use chrono::TimeZone;
+    use std::fs;
+
+    fn rule(id: &str, check: &str) -> Rule {
+        Rule {
+            id: id.to_string(),
+            description: String::new(),
+            check: Condition::parse(check).unwrap(),
+            when: None,
+        }
+    }
+
+    fn verdict(program: &ProgramDefinition, application: &GrantApplication, id: &str) -> Verdict {
+        program
+            .evaluate(application)
+            .into_iter()
+            .find(|outcome| outcome.rule_id == id)
+            .unwrap_or_else(|| panic!("no outcome for {id}"))
+            .verdict
+    }
+
+    fn invalid_field(program: &ProgramDefinition) -> String {
+        match program.validate().unwrap_err() {
+            GrantError::Validation { field, .. } => field,
+            GrantError::Rule { rule_id, .. } => format!("rule {}", rule_id),
+            other => panic!("unexpected error {other:?}"),
+        }
+    }
+
+    #[test]
+    fn inconsistent_definitions_are_rejected() {
+        let window = |opens, closes| {
+            Some(FundingWindow {
+                opens: NaiveDate::from_ymd_opt(2026, 1, opens).unwrap(),
+                closes: NaiveDate::from_ymd_opt(2026, 1, closes).unwrap(),
+            })
+        };
+        let base = program(100_000.0);
+        let cases = [
+            (
+                ProgramDefinition {
+                    id: " ".into(),
+                    ..base.clone()
+                },
+                "id",
+            ),
+            (
+                ProgramDefinition {
+                    total_pool: -1.0,
+                    ..base.clone()
+                },
+                "total_pool",
+            ),
+            (
+                ProgramDefinition {
+                    min_award: f64::NAN,
+                    ..base.clone()
+                },
+                "min_award",
+            ),
+            (
+                ProgramDefinition {
+                    max_award: 500.0,
+                    ..base.clone()
+                },
+                "max_award",
+            ),
+            (
+                ProgramDefinition {
+                    window: window(20, 10),
+                    ..base.clone()
+                },
+                "window",
+            ),
+            (
+                ProgramDefinition {
+                    rules: vec![rule("program.cap", "requested_amount <= 1")],
+                    ..base.clone()
+                },
+                "rule program.cap",
+            ),
+            (
+                ProgramDefinition {
+                    rules: vec![
+                        rule("cap", "requested_amount <= 1"),
+                        rule("cap", "country == US"),
+                    ],
+                    ..base.clone()
+                },
+                "rule cap",
+            ),
+        ];
+        for (definition, field) in cases {
+            assert_eq!(invalid_field(&definition), field);
+        }
+
+        assert!(ProgramDefinition {
+            window: window(10, 10),
+            ..base.clone()
+        }
+        .validate()
+        .is_ok());
+    }
+
+    #[test]
+    fn loading_checks_the_definition() {
+        let dir = tempfile::tempdir().unwrap();
+        let valid = dir.path().join("program.toml");
+        fs::write(
+            &valid,
+            "id = \"P1\"\nname = \"Clean Water\"\ntotal_pool = 1000.0\nmax_award = 500.0\n",
+        )
+        .unwrap();
+        let loaded = ProgramDefinition::load(&valid).unwrap();
+        assert_eq!(loaded.min_award, 0.0);
+        assert!(loaded.rules.is_empty());
+
+        let invalid = dir.path().join("program.json");
+        fs::write(
+            &invalid,
+            r#"{ "id": "P1", "name": "x", "total_pool": 1000, "min_award": 600, "max_award": 500 }"#,
+        )
+        .unwrap();
+        assert!(matches!(
+            ProgramDefinition::load(&invalid),
+            Err(GrantError::Validation { .. })
+        ));
+        let malformed = dir.path().join("broken.json");
+        fs::write(&malformed, r#"{ "id": "P1" "#).unwrap();
+        assert!(matches!(
+            ProgramDefinition::load(&malformed),
+            Err(GrantError::Parse { .. })
+        ));
+    }
+
+    #[test]
+    fn built_in_checks_come_first_then_rules() {
+        let program = ProgramDefinition {
+            rules: vec![rule("cap", "requested_amount <= 20000")],
+            ..program(100_000.0)
+        };
+        let ids: Vec<String> = program
+            .evaluate(&application("A1", 10_000.0))
+            .into_iter()
+            .map(|outcome| outcome.rule_id)
+            .collect();
+        assert_eq!(
+            ids,
+            [
+                "program.match",
+                "program.min_award",
+                "program.max_award",
+                "program.pool",
+                "program.documents",
+                "cap"
+            ]
+        );
+    }
+
+    #[test]
+    fn program_match_checks_the_program_id() {
+        let program = program(100_000.0);
+        let mut application = application("A1", 10_000.0);
+        assert_eq!(
+            verdict(&program, &application, "program.match"),
+            Verdict::Passed
+        );
+        application.program.id = "P2".into();
+        assert_eq!(
+            verdict(&program, &application, "program.match"),
+            Verdict::Failed
+        );
+    }
+
+    #[test]
+    fn the_window_includes_both_ends() {
+        let program = ProgramDefinition {
+            window: Some(FundingWindow {
+                opens: NaiveDate::from_ymd_opt(2026, 3, 1).unwrap(),
+                closes: NaiveDate::from_ymd_opt(2026, 3, 31).unwrap(),
+            }),
+            ..program(100_000.0)
+        };
+        let mut application = application("A1", 10_000.0);
+        for (day, expected) in [
+            ((2026, 2, 28), Verdict::Failed),
+            ((2026, 3, 1), Verdict::Passed),
+            ((2026, 3, 31), Verdict::Passed),
+            ((2026, 4, 1), Verdict::Failed),
+        ] {
+            let (year, month, day) = day;
+            application.submitted_at =
+                Some(Utc.with_ymd_and_hms(year, month, day, 12, 0, 0).unwrap());
+            assert_eq!(
+                verdict(&program, &application, "program.window"),
+                expected,
+                "{year}-{month}-{day}"
+            );
+        }
+    }
+
+    #[test]
+    fn award_limits_and_pool_bound_the_request() {
+        let program = program(40_000.0);
+        for (amount, min, max, pool) in [
+            (999.99, Verdict::Failed, Verdict::Passed, Verdict::Passed),
+            (1_000.0, Verdict::Passed, Verdict::Passed, Verdict::Passed),
+            (40_000.0, Verdict::Passed, Verdict::Passed, Verdict::Passed),
+            (45_000.0, Verdict::Passed, Verdict::Passed, Verdict::Failed),
+            (50_000.01, Verdict::Passed, Verdict::Failed, Verdict::Failed),
+        ] {
+            let application = application("A1", amount);
+            assert_eq!(
+                verdict(&program, &application, "program.min_award"),
+                min,
+                "{amount}"
+            );
+            assert_eq!(
+                verdict(&program, &application, "program.max_award"),
+                max,
+                "{amount}"
+            );
+            assert_eq!(
+                verdict(&program, &application, "program.pool"),
+                pool,
+                "{amount}"
+            );
+        }
+    }
+
+    #[test]
+    fn only_eligible_applicant_types_pass() {
+        let program = ProgramDefinition {
+            eligible_applicant_types: vec![ApplicantType::Nonprofit, ApplicantType::University],
+            ..program(100_000.0)
+        };
+        let mut application = application("A1", 10_000.0);
+        assert_eq!(
+            verdict(&program, &application, "program.applicant_type"),
+            Verdict::Passed
+        );
+        application.applicant.org_type = ApplicantType::ForProfit;
+        let outcome = program
+            .evaluate(&application)
+            .into_iter()
+            .find(|outcome| outcome.rule_id == "program.applicant_type")
+            .unwrap();
+        assert_eq!(outcome.verdict, Verdict::Failed);
+        assert!(
+            outcome.reason.contains("not eligible"),
+            "{}",
+            outcome.reason
+        );
+    }
+
+    #[test]
+    fn missing_documents_are_listed() {
+        let program = ProgramDefinition {
+            required_documents: vec!["proposal".into(), "cv".into(), "letter".into()],
+            ..program(100_000.0)
+        };
+        let outcome = program
+            .evaluate(&application("A1", 10_000.0))
+            .into_iter()
+            .find(|outcome| outcome.rule_id == "program.documents")
+            .unwrap();
+        assert_eq!(outcome.verdict, Verdict::Failed);
+        assert_eq!(outcome.reason, "missing required document(s): cv, letter");
+
+        let program = ProgramDefinition {
+            required_documents: vec!["proposal".into(), "budget".into()],
+            ..program
+        };
+        assert_eq!(
+            verdict(&program, &application("A1", 10_000.0), "program.documents"),
+            Verdict::Passed
+        );
+    }
+
+    #[test]
+    fn rules_map_to_the_field_they_check() {
+        let program = ProgramDefinition {
+            rules: vec![rule("cap", "requested_amount <= 20000")],
+            ..program(100_000.0)
+        };
+        for (rule_id, field) in [
+            ("program.match", "program.id"),
+            ("program.window", "submitted_at"),
+            ("program.min_award", "funding.amount"),
+            ("program.max_award", "funding.amount"),
+            ("program.pool", "funding.amount"),
+            ("program.applicant_type", "applicant.org_type"),
+            ("program.documents", "documents"),
+            ("cap", "requested_amount"),
+        ] {
+            assert_eq!(
+                program.rule_field(rule_id).as_deref(),
+                Some(field),
+                "{rule_id}"
+            );
+        }
+        assert_eq!(program.rule_field("unknown"), None);
+    }
+}
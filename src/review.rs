@@ -0,0 +1,978 @@
+// src/review.rs
+/*
+ * Review panels: reviewers, assignment, score submission and aggregation
+ */
+
+use crate::conflict::{self, Conflict};
+use crate::scoring::Rubric;
+use crate::{GrantApplication, GrantError, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashSet};
+use std::fmt;
+use std::path::Path;
+
+/// A member of a review panel
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Reviewer {
+    /// Reviewer identifier
+    pub id: String,
+    /// Display name
+    #[serde(default)]
+    pub name: String,
+    /// Subjects the reviewer is qualified in, matched against application keywords
+    #[serde(default)]
+    pub expertise: Vec<String>,
+    /// Most applications the reviewer may be assigned; unlimited when `None`
+    #[serde(default)]
+    pub max_load: Option<usize>,
+    /// Contact email address
+    #[serde(default)]
+    pub email: String,
+    /// Institution the reviewer belongs to, if any
+    #[serde(default)]
+    pub institution: Option<String>,
+    /// Applicants (by id or name) the reviewer has applied together with before
+    #[serde(default)]
+    pub co_applicants: Vec<String>,
+    /// Declared conflicts: application ids, applicant ids or names, or institutions
+    #[serde(default)]
+    pub conflicts: Vec<String>,
+}
+
+/// An application handed to a reviewer
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Assignment {
+    /// Application to review
+    pub application_id: String,
+    /// Reviewer it is assigned to
+    pub reviewer_id: String,
+}
+
+/// How applications are distributed between reviewers
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum AssignmentStrategy {
+    /// Cycle through the reviewers in roster order
+    RoundRobin,
+    /// Prefer reviewers whose expertise matches the application keywords
+    Expertise,
+    /// Prefer the reviewers with the fewest assignments so far
+    LoadBalanced,
+}
+
+impl fmt::Display for AssignmentStrategy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            AssignmentStrategy::RoundRobin => "round-robin",
+            AssignmentStrategy::Expertise => "expertise",
+            AssignmentStrategy::LoadBalanced => "load-balanced",
+        };
+        f.write_str(name)
+    }
+}
+
+/// How the scores of several reviewers are combined into one
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum Aggregation {
+    /// Arithmetic mean of the scores
+    Mean,
+    /// Mean after dropping the highest and lowest score (with three or more reviews)
+    TrimmedMean,
+    /// Middle score, or the mean of the two middle scores
+    Median,
+    /// Mean of per-reviewer z-scores, mapped back onto the panel's score scale
+    ZScore,
+}
+
+impl fmt::Display for Aggregation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Aggregation::Mean => "mean",
+            Aggregation::TrimmedMean => "trimmed-mean",
+            Aggregation::Median => "median",
+            Aggregation::ZScore => "z-score",
+        };
+        f.write_str(name)
+    }
+}
+
+/// One reviewer's assessment of one application
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Review {
+    /// Application reviewed
+    pub application_id: String,
+    /// Reviewer who submitted the review
+    pub reviewer_id: String,
+    /// Overall score; derived from `ratings` through the program rubric when omitted
+    #[serde(default)]
+    pub score: Option<f64>,
+    /// Ratings per rubric criterion, keyed by criterion id
+    #[serde(default)]
+    pub ratings: BTreeMap<String, f64>,
+    /// Free-form remarks
+    #[serde(default)]
+    pub comment: String,
+    /// When the review was submitted
+    #[serde(default)]
+    pub submitted_at: Option<DateTime<Utc>>,
+}
+
+/// Combined panel score of one application
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PanelScore {
+    /// 1-based position in the panel ranking
+    pub rank: usize,
+    /// Application identifier
+    pub application_id: String,
+    /// Aggregated score
+    pub score: f64,
+    /// Number of reviews combined
+    pub reviews: usize,
+    /// Lowest raw score given
+    pub min: f64,
+    /// Highest raw score given
+    pub max: f64,
+}
+
+/// Reviewers together with their assignments and submitted reviews
+///
+/// A panel file (JSON or TOML) starts out holding only `reviewers`;
+/// assignments and reviews are added to it as the review round progresses.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ReviewPanel {
+    /// Panel members
+    pub reviewers: Vec<Reviewer>,
+    /// Applications handed to each reviewer
+    #[serde(default)]
+    pub assignments: Vec<Assignment>,
+    /// Reviews submitted so far
+    #[serde(default)]
+    pub reviews: Vec<Review>,
+    /// Conflicts of interest that kept reviewers from being assigned
+    #[serde(default)]
+    pub conflicts: Vec<Conflict>,
+}
+
+impl ReviewPanel {
+    /// Load a review panel from a JSON or TOML file
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path of the panel file
+    ///
+    /// # Returns
+    ///
+    /// The parsed and validated `ReviewPanel`
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let panel: ReviewPanel = crate::load_document(path.as_ref())?;
+        panel.validate()?;
+        Ok(panel)
+    }
+
+    /// Write the panel to a JSON or TOML file, chosen by extension
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        crate::save_document(path.as_ref(), self)
+    }
+
+    /// Check that reviewers are unique and assignments and reviews refer to them
+    ///
+    /// # Returns
+    ///
+    /// `Ok(())`, or a validation error naming the offending entry
+    pub fn validate(&self) -> Result<()> {
+        let mut ids = HashSet::new();
+        for (index, reviewer) in self.reviewers.iter().enumerate() {
+            if reviewer.id.trim().is_empty() {
+                return Err(GrantError::validation(
+                    format!("reviewers[{}].id", index),
+                    "must not be empty",
+                ));
+            }
+            if !ids.insert(reviewer.id.as_str()) {
+                return Err(GrantError::validation(
+                    format!("reviewers[{}].id", index),
+                    format!("'{}' is defined more than once", reviewer.id),
+                ));
+            }
+        }
+        for (index, assignment) in self.assignments.iter().enumerate() {
+            if !ids.contains(assignment.reviewer_id.as_str()) {
+                return Err(GrantError::validation(
+                    format!("assignments[{}].reviewer_id", index),
+                    format!("unknown reviewer '{}'", assignment.reviewer_id),
+                ));
+            }
+        }
+        for (index, review) in self.reviews.iter().enumerate() {
+            if !ids.contains(review.reviewer_id.as_str()) {
+                return Err(GrantError::validation(
+                    format!("reviews[{}].reviewer_id", index),
+                    format!("unknown reviewer '{}'", review.reviewer_id),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Number of applications assigned to each reviewer
+    pub fn workload(&self) -> BTreeMap<String, usize> {
+        let mut workload: BTreeMap<String, usize> = self
+            .reviewers
+            .iter()
+            .map(|reviewer| (reviewer.id.clone(), 0))
+            .collect();
+        for assignment in &self.assignments {
+            *workload.entry(assignment.reviewer_id.clone()).or_default() += 1;
+        }
+        workload
+    }
+
+    /// Whether the reviewer has been assigned the application
+    pub fn is_assigned(&self, application_id: &str, reviewer_id: &str) -> bool {
+        self.assignments.iter().any(|assignment| {
+            assignment.application_id == application_id && assignment.reviewer_id == reviewer_id
+        })
+    }
+
+    /// Assign reviewers to applications
+    ///
+    /// Existing assignments are kept and count towards each reviewer's load,
+    /// so a panel can be topped up as new applications arrive. Reviewers at
+    /// their `max_load` are skipped, as are reviewers with a conflict of
+    /// interest; those conflicts are recorded in `conflicts` and the next
+    /// eligible reviewer is chosen instead.
+    ///
+    /// # Arguments
+    ///
+    /// * `applications` - Applications needing reviewers
+    /// * `per_application` - Reviewers each application should end up with
+    /// * `strategy` - How reviewers are chosen
+    ///
+    /// # Returns
+    ///
+    /// The assignments that were added, or a validation error if an
+    /// application cannot get enough reviewers without conflicts
+    pub fn assign(
+        &mut self,
+        applications: &[GrantApplication],
+        per_application: usize,
+        strategy: AssignmentStrategy,
+    ) -> Result<Vec<Assignment>> {
+        let count = self.reviewers.len();
+        let mut load: Vec<usize> = self
+            .reviewers
+            .iter()
+            .map(|reviewer| {
+                self.assignments
+                    .iter()
+                    .filter(|assignment| assignment.reviewer_id == reviewer.id)
+                    .count()
+            })
+            .collect();
+        let mut cursor = 0;
+        let mut added = Vec::new();
+        let mut conflicts = Vec::new();
+
+        for application in applications {
+            let assigned: HashSet<&str> = self
+                .assignments
+                .iter()
+                .chain(&added)
+                .filter(|assignment| assignment.application_id == application.id)
+                .map(|assignment| assignment.reviewer_id.as_str())
+                .collect();
+            let needed = per_application.saturating_sub(assigned.len());
+            if needed == 0 {
+                continue;
+            }
+
+            let mut conflicted = 0;
+            let mut candidates = Vec::new();
+            for (index, reviewer) in self.reviewers.iter().enumerate() {
+                if assigned.contains(reviewer.id.as_str()) {
+                    continue;
+                }
+                let found = conflict::detect(reviewer, application);
+                if !found.is_empty() {
+                    conflicted += 1;
+                    conflicts.extend(found);
+                } else if reviewer.max_load.is_none_or(|max| load[index] < max) {
+                    candidates.push(index);
+                }
+            }
+            if candidates.len() < needed {
+                return Err(GrantError::validation(
+                    "assignments",
+                    format!(
+                        "application {} needs {} more reviewer(s) but only {} are available ({} excluded for conflicts of interest)",
+                        application.id,
+                        needed,
+                        candidates.len(),
+                        conflicted
+                    ),
+                ));
+            }
+
+            match strategy {
+                AssignmentStrategy::RoundRobin => {
+                    candidates.sort_by_key(|&index| (index + count - cursor) % count)
+                }
+                AssignmentStrategy::LoadBalanced => {
+                    candidates.sort_by_key(|&index| (load[index], index))
+                }
+                AssignmentStrategy::Expertise => candidates.sort_by_key(|&index| {
+                    let matches = expertise_matches(&self.reviewers[index], application);
+                    (Reverse(matches), load[index], index)
+                }),
+            }
+
+            let chosen = &candidates[..needed];
+            if let Some(&last) = chosen.last() {
+                cursor = (last + 1) % count;
+            }
+            for &index in chosen {
+                load[index] += 1;
+                added.push(Assignment {
+                    application_id: application.id.clone(),
+                    reviewer_id: self.reviewers[index].id.clone(),
+                });
+            }
+        }
+
+        self.assignments.extend(added.iter().cloned());
+        for found in conflicts {
+            if !self.conflicts.contains(&found) {
+                self.conflicts.push(found);
+            }
+        }
+        Ok(added)
+    }
+
+    /// Record a reviewer's assessment
+    ///
+    /// Each reviewer reviews an application once; a second review of the same
+    /// application by the same reviewer is rejected.
+    ///
+    /// # Arguments
+    ///
+    /// * `review` - The review; its score is derived from `ratings` when omitted
+    /// * `rubric` - Program rubric used to score ratings
+    ///
+    /// # Returns
+    ///
+    /// The review as stored, with its score filled in, or a validation error
+    /// if the reviewer is unknown, was not assigned the application, already
+    /// reviewed it, gave a rating outside its criterion's scale, or gave no
+    /// usable score
+    pub fn submit(&mut self, mut review: Review, rubric: Option<&Rubric>) -> Result<&Review> {
+        if !self
+            .reviewers
+            .iter()
+            .any(|reviewer| reviewer.id == review.reviewer_id)
+        {
+            return Err(GrantError::validation(
+                "review.reviewer_id",
+                format!("unknown reviewer '{}'", review.reviewer_id),
+            ));
+        }
+        if !self.is_assigned(&review.application_id, &review.reviewer_id) {
+            return Err(GrantError::validation(
+                "review.application_id",
+                format!(
+                    "reviewer {} is not assigned to application {}",
+                    review.reviewer_id, review.application_id
+                ),
+            ));
+        }
+        if self.reviews.iter().any(|existing| {
+            existing.application_id == review.application_id
+                && existing.reviewer_id == review.reviewer_id
+        }) {
+            return Err(GrantError::validation(
+                "review.reviewer_id",
+                format!(
+                    "reviewer {} has already reviewed application {}",
+                    review.reviewer_id, review.application_id
+                ),
+            ));
+        }
+        for criterion in rubric.into_iter().flat_map(|rubric| &rubric.criteria) {
+            let Some(&rating) = review.ratings.get(&criterion.id) else {
+                continue;
+            };
+            let scale = criterion.scale;
+            if !(scale.min..=scale.max).contains(&rating) {
+                return Err(GrantError::validation(
+                    format!("review.ratings.{}", criterion.id),
+                    format!(
+                        "must be between {} and {}, got {}",
+                        scale.min, scale.max, rating
+                    ),
+                ));
+            }
+        }
+
+        let score = match (review.score, rubric) {
+            (Some(score), _) => score,
+            (None, Some(rubric)) if !review.ratings.is_empty() => {
+                rubric.score_ratings(&review.ratings).total
+            }
+            (None, _) => {
+                return Err(GrantError::validation(
+                    "review.score",
+                    "a score, or ratings together with a program rubric, is required",
+                ))
+            }
+        };
+        if !score.is_finite() || score < 0.0 {
+            return Err(GrantError::validation(
+                "review.score",
+                format!("must be a non-negative number, got {}", score),
+            ));
+        }
+        review.score = Some(score);
+        review.submitted_at.get_or_insert_with(Utc::now);
+
+        self.reviews.push(review);
+        Ok(&self.reviews[self.reviews.len() - 1])
+    }
+
+    /// Combine the submitted reviews into one score per application
+    ///
+    /// # Arguments
+    ///
+    /// * `method` - How the scores of different reviewers are combined
+    ///
+    /// # Returns
+    ///
+    /// Panel scores ranked from best to worst, or a validation error if a
+    /// review has no score
+    pub fn aggregate(&self, method: Aggregation) -> Result<Vec<PanelScore>> {
+        let mut scores = Vec::with_capacity(self.reviews.len());
+        for (index, review) in self.reviews.iter().enumerate() {
+            match review.score.filter(|score| score.is_finite()) {
+                Some(score) => scores.push((review, score)),
+                None => {
+                    return Err(GrantError::validation(
+                        format!("reviews[{}].score", index),
+                        "missing or not a number",
+                    ))
+                }
+            }
+        }
+
+        // Per-reviewer z-scores are mapped back onto the pooled mean and spread
+        let normalized: Vec<f64> = if method == Aggregation::ZScore {
+            let mut by_reviewer: BTreeMap<&str, Vec<f64>> = BTreeMap::new();
+            for (review, score) in &scores {
+                by_reviewer
+                    .entry(review.reviewer_id.as_str())
+                    .or_default()
+                    .push(*score);
+            }
+            let stats: BTreeMap<&str, (f64, f64)> = by_reviewer
+                .into_iter()
+                .map(|(reviewer, values)| (reviewer, mean_and_deviation(&values)))
+                .collect();
+            let all: Vec<f64> = scores.iter().map(|(_, score)| *score).collect();
+            let (pooled_mean, pooled_deviation) = mean_and_deviation(&all);
+
+            scores
+                .iter()
+                .map(|(review, score)| {
+                    let (mean, deviation) = stats[review.reviewer_id.as_str()];
+                    let z = if deviation > 0.0 {
+                        (score - mean) / deviation
+                    } else {
+                        0.0
+                    };
+                    (pooled_mean + z * pooled_deviation).max(0.0)
+                })
+                .collect()
+        } else {
+            scores.iter().map(|(_, score)| *score).collect()
+        };
+
+        let mut by_application: BTreeMap<&str, (Vec<f64>, Vec<f64>)> = BTreeMap::new();
+        for ((review, raw), value) in scores.iter().zip(normalized) {
+            let entry = by_application
+                .entry(review.application_id.as_str())
+                .or_default();
+            entry.0.push(*raw);
+            entry.1.push(value);
+        }
+
+        let mut ranked: Vec<PanelScore> = by_application
+            .into_iter()
+            .map(|(application_id, (raw, mut values))| {
+                values.sort_by(|a, b| a.total_cmp(b));
+                let score = match method {
+                    Aggregation::Mean | Aggregation::ZScore => mean(&values),
+                    Aggregation::TrimmedMean if values.len() >= 3 => {
+                        mean(&values[1..values.len() - 1])
+                    }
+                    Aggregation::TrimmedMean => mean(&values),
+                    Aggregation::Median => median(&values),
+                };
+                PanelScore {
+                    rank: 0,
+                    application_id: application_id.to_string(),
+                    score,
+                    reviews: raw.len(),
+                    min: raw.iter().copied().fold(f64::INFINITY, f64::min),
+                    max: raw.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+                }
+            })
+            .collect();
+
+        ranked.sort_by(|a, b| {
+            b.score
+                .total_cmp(&a.score)
+                .then_with(|| a.application_id.cmp(&b.application_id))
+        });
+        for (position, entry) in ranked.iter_mut().enumerate() {
+            entry.rank = position + 1;
+        }
+        Ok(ranked)
+    }
+}
+
+/// Number of the reviewer's expertise areas found among the application keywords
+fn expertise_matches(reviewer: &Reviewer, application: &GrantApplication) -> usize {
+    let keywords: HashSet<String> = application
+        .keywords
+        .iter()
+        .map(|keyword| keyword.trim().to_lowercase())
+        .collect();
+    reviewer
+        .expertise
+        .iter()
+        .filter(|area| keywords.contains(&area.trim().to_lowercase()))
+        .count()
+}
+
+fn mean(values: &[f64]) -> f64 {
+    if values.is_empty() {
+        return 0.0;
+    }
+    values.iter().sum::<f64>() / values.len() as f64
+}
+
+/// Median of values that are already sorted
+fn median(values: &[f64]) -> f64 {
+    match values.len() {
+        0 => 0.0,
+        len if len % 2 == 1 => values[len / 2],
+        len => (values[len / 2 - 1] + values[len / 2]) / 2.0,
+    }
+}
+
+/// Mean and population standard deviation
+fn mean_and_deviation(values: &[f64]) -> (f64, f64) {
+    let mean = mean(values);
+    let variance = if values.is_empty() {
+        0.0
+    } else {
+        values
+            .iter()
+            .map(|value| (value - mean).powi(2))
+            .sum::<f64>()
+            / values.len() as f64
+    };
+    (mean, variance.sqrt())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scoring::{Criterion, Scale};
+    use crate::test_support::application;
+
+    fn reviewer(id: &str) -> Reviewer {
+        Reviewer {
+            id: id.to_string(),
+            name: String::new(),
+            expertise: Vec::new(),
+            max_load: None,
+            email: format!("{}@panel.example", id.to_lowercase()),
+            institution: None,
+            co_applicants: Vec::new(),
+            conflicts: Vec::new(),
+        }
+    }
+
+    fn panel(ids: &[&str]) -> ReviewPanel {
+        ReviewPanel {
+            reviewers: ids.iter().map(|id| reviewer(id)).collect(),
+            ..ReviewPanel::default()
+        }
+    }
+
+    fn applications(ids: &[&str]) -> Vec<GrantApplication> {
+        ids.iter().map(|id| application(id, 10_000.0)).collect()
+    }
+
+    fn assignment(application_id: &str, reviewer_id: &str) -> Assignment {
+        Assignment {
+            application_id: application_id.to_string(),
+            reviewer_id: reviewer_id.to_string(),
+        }
+    }
+
+    fn pairs(assignments: &[Assignment]) -> Vec<(&str, &str)> {
+        assignments
+            .iter()
+            .map(|a| (a.application_id.as_str(), a.reviewer_id.as_str()))
+            .collect()
+    }
+
+    fn review(application_id: &str, reviewer_id: &str, score: Option<f64>) -> Review {
+        Review {
+            application_id: application_id.to_string(),
+            reviewer_id: reviewer_id.to_string(),
+            score,
+            ratings: BTreeMap::new(),
+            comment: String::new(),
+            submitted_at: None,
+        }
+    }
+
+    /// A panel holding `reviews` as (application, reviewer, score)
+    fn reviewed(reviews: &[(&str, &str, f64)]) -> ReviewPanel {
+        let mut ids: Vec<&str> = reviews.iter().map(|(_, reviewer, _)| *reviewer).collect();
+        ids.sort();
+        ids.dedup();
+        ReviewPanel {
+            reviews: reviews
+                .iter()
+                .map(|(application, reviewer, score)| review(application, reviewer, Some(*score)))
+                .collect(),
+            ..panel(&ids)
+        }
+    }
+
+    fn scores(panel: &ReviewPanel, method: Aggregation) -> Vec<(String, f64)> {
+        panel
+            .aggregate(method)
+            .unwrap()
+            .into_iter()
+            .map(|score| (score.application_id, score.score))
+            .collect()
+    }
+
+    fn assert_scores(actual: Vec<(String, f64)>, expected: &[(&str, f64)]) {
+        assert_eq!(actual.len(), expected.len(), "{actual:?}");
+        for ((id, score), (expected_id, expected_score)) in actual.iter().zip(expected) {
+            assert_eq!(id, expected_id);
+            assert!(
+                (score - expected_score).abs() < 1e-9,
+                "{id}: {score} != {expected_score}"
+            );
+        }
+    }
+
+    fn validation_field<T: fmt::Debug>(result: Result<T>) -> String {
+        match result {
+            Err(GrantError::Validation { field, .. }) => field,
+            other => panic!("expected a validation error, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn round_robin_cycles_through_the_roster() {
+        let mut panel = panel(&["R1", "R2", "R3"]);
+
+        let added = panel
+            .assign(
+                &applications(&["A1", "A2", "A3"]),
+                2,
+                AssignmentStrategy::RoundRobin,
+            )
+            .unwrap();
+        assert_eq!(
+            pairs(&added),
+            [
+                ("A1", "R1"),
+                ("A1", "R2"),
+                ("A2", "R3"),
+                ("A2", "R1"),
+                ("A3", "R2"),
+                ("A3", "R3")
+            ]
+        );
+        assert_eq!(panel.assignments, added);
+    }
+
+    #[test]
+    fn load_balancing_counts_existing_assignments() {
+        let mut panel = panel(&["R1", "R2", "R3"]);
+        panel.assignments = vec![
+            assignment("X1", "R1"),
+            assignment("X2", "R1"),
+            assignment("X1", "R2"),
+        ];
+
+        let added = panel
+            .assign(
+                &applications(&["A1", "A2", "A3"]),
+                1,
+                AssignmentStrategy::LoadBalanced,
+            )
+            .unwrap();
+        assert_eq!(pairs(&added), [("A1", "R3"), ("A2", "R2"), ("A3", "R3")]);
+        assert_eq!(panel.workload()["R1"], 2);
+        assert_eq!(panel.workload()["R3"], 2);
+    }
+
+    #[test]
+    fn expertise_matches_keywords_case_insensitively() {
+        let mut panel = panel(&["R1", "R2", "R3"]);
+        panel.reviewers[0].expertise = vec!["art".to_string()];
+        panel.reviewers[1].expertise = vec![" Water".to_string(), "ECOLOGY".to_string()];
+        panel.reviewers[2].expertise = vec!["water".to_string()];
+
+        let added = panel
+            .assign(&applications(&["A1"]), 2, AssignmentStrategy::Expertise)
+            .unwrap();
+        assert_eq!(pairs(&added), [("A1", "R2"), ("A1", "R3")]);
+    }
+
+    #[test]
+    fn reviewers_at_their_max_load_are_skipped() {
+        let mut panel = panel(&["R1", "R2", "R3"]);
+        panel.reviewers[0].max_load = Some(1);
+        panel.reviewers[1].max_load = Some(2);
+        panel.reviewers[2].max_load = Some(0);
+        panel.assignments = vec![assignment("X1", "R1")];
+
+        let added = panel
+            .assign(
+                &applications(&["A1", "A2"]),
+                1,
+                AssignmentStrategy::RoundRobin,
+            )
+            .unwrap();
+        assert_eq!(pairs(&added), [("A1", "R2"), ("A2", "R2")]);
+
+        // Every reviewer is now full; nothing is assigned when one application fails
+        let error = panel
+            .assign(&applications(&["A3"]), 1, AssignmentStrategy::LoadBalanced)
+            .unwrap_err();
+        assert!(
+            error
+                .to_string()
+                .contains("needs 1 more reviewer(s) but only 0"),
+            "{error}"
+        );
+        assert_eq!(panel.assignments.len(), 3);
+    }
+
+    #[test]
+    fn topping_up_keeps_existing_assignments() {
+        let mut panel = panel(&["R1", "R2"]);
+        panel.assignments = vec![assignment("A1", "R2")];
+
+        let added = panel
+            .assign(&applications(&["A1"]), 2, AssignmentStrategy::RoundRobin)
+            .unwrap();
+        assert_eq!(pairs(&added), [("A1", "R1")]);
+        let again = panel
+            .assign(&applications(&["A1"]), 2, AssignmentStrategy::RoundRobin)
+            .unwrap();
+        assert!(again.is_empty());
+        assert_eq!(panel.assignments.len(), 2);
+    }
+
+    #[test]
+    fn conflicted_reviewers_are_excluded_and_recorded() {
+        let mut panel = panel(&["R1", "R2", "R3"]);
+        // The fixture applicant is at Green
+        panel.reviewers[0].institution = Some("Green".to_string());
+
+        let added = panel
+            .assign(&applications(&["A1"]), 2, AssignmentStrategy::RoundRobin)
+            .unwrap();
+        assert_eq!(pairs(&added), [("A1", "R2"), ("A1", "R3")]);
+        assert_eq!(panel.conflicts.len(), 1);
+        assert_eq!(panel.conflicts[0].reviewer_id, "R1");
+        assert_eq!(panel.conflicts[0].application_id, "A1");
+
+        let error = panel
+            .assign(&applications(&["A2"]), 3, AssignmentStrategy::LoadBalanced)
+            .unwrap_err();
+        assert!(
+            error
+                .to_string()
+                .contains("only 2 are available (1 excluded for conflicts of interest)"),
+            "{error}"
+        );
+        // A failed assignment records nothing
+        assert_eq!(panel.conflicts.len(), 1);
+    }
+
+    #[test]
+    fn submit_fills_in_the_score_and_submission_time() {
+        let mut panel = panel(&["R1", "R2"]);
+        panel.assignments = vec![assignment("A1", "R1"), assignment("A1", "R2")];
+        let rubric = Rubric {
+            criteria: vec![Criterion {
+                id: "impact".to_string(),
+                name: String::new(),
+                weight: 1.0,
+                scale: Scale::default(),
+                field: None,
+                higher_is_better: true,
+            }],
+        };
+
+        let saved = panel.submit(review("A1", "R1", Some(65.0)), None).unwrap();
+        assert_eq!(saved.score, Some(65.0));
+        assert!(saved.submitted_at.is_some());
+
+        let mut rated = review("A1", "R2", None);
+        rated.ratings.insert("impact".to_string(), 4.0);
+        let saved = panel.submit(rated, Some(&rubric)).unwrap();
+        assert_eq!(saved.score, Some(80.0));
+        assert_eq!(panel.reviews.len(), 2);
+    }
+
+    #[test]
+    fn submit_rejects_invalid_reviews() {
+        let mut panel = panel(&["R1", "R2"]);
+        panel.assignments = vec![assignment("A1", "R1")];
+        let rubric = Rubric {
+            criteria: vec![Criterion {
+                id: "impact".to_string(),
+                name: String::new(),
+                weight: 1.0,
+                scale: Scale { min: 1.0, max: 5.0 },
+                field: None,
+                higher_is_better: true,
+            }],
+        };
+        let rating = |value: f64| {
+            let mut review = review("A1", "R1", None);
+            review.ratings.insert("impact".to_string(), value);
+            review
+        };
+
+        let cases = [
+            (review("A1", "R9", Some(50.0)), "review.reviewer_id"),
+            (review("A1", "R2", Some(50.0)), "review.application_id"),
+            (review("A1", "R1", None), "review.score"),
+            (review("A1", "R1", Some(-1.0)), "review.score"),
+            (review("A1", "R1", Some(f64::NAN)), "review.score"),
+            (rating(0.5), "review.ratings.impact"),
+            (rating(5.5), "review.ratings.impact"),
+            (rating(f64::NAN), "review.ratings.impact"),
+        ];
+        for (review, expected) in cases {
+            assert_eq!(
+                validation_field(panel.submit(review, Some(&rubric))),
+                expected
+            );
+        }
+        assert!(panel.reviews.is_empty());
+
+        panel.submit(rating(5.0), Some(&rubric)).unwrap();
+        let duplicate = panel.submit(review("A1", "R1", Some(10.0)), Some(&rubric));
+        assert_eq!(validation_field(duplicate), "review.reviewer_id");
+        assert_eq!(panel.reviews.len(), 1);
+        assert_eq!(panel.reviews[0].score, Some(100.0));
+    }
+
+    #[test]
+    fn mean_trimmed_mean_and_median_combine_raw_scores() {
+        let panel = reviewed(&[
+            ("A1", "R1", 60.0),
+            ("A1", "R2", 70.0),
+            ("A1", "R3", 95.0),
+            ("A2", "R1", 50.0),
+            ("A2", "R2", 80.0),
+        ]);
+
+        assert_scores(
+            scores(&panel, Aggregation::Mean),
+            &[("A1", 75.0), ("A2", 65.0)],
+        );
+        // Two reviews are too few to trim
+        assert_scores(
+            scores(&panel, Aggregation::TrimmedMean),
+            &[("A1", 70.0), ("A2", 65.0)],
+        );
+        assert_scores(
+            scores(&panel, Aggregation::Median),
+            &[("A1", 70.0), ("A2", 65.0)],
+        );
+
+        let ranking = panel.aggregate(Aggregation::Mean).unwrap();
+        assert_eq!(
+            ranking
+                .iter()
+                .map(|s| (s.rank, s.reviews, s.min, s.max))
+                .collect::<Vec<_>>(),
+            [(1, 3, 60.0, 95.0), (2, 2, 50.0, 80.0)]
+        );
+    }
+
+    #[test]
+    fn z_scores_remove_each_reviewers_leniency() {
+        // R1 is harsh and R2 lenient, but both prefer A2 by the same margin
+        let panel = reviewed(&[
+            ("A1", "R1", 40.0),
+            ("A2", "R1", 60.0),
+            ("A1", "R2", 80.0),
+            ("A2", "R2", 100.0),
+        ]);
+
+        let spread = 500f64.sqrt();
+        assert_scores(
+            scores(&panel, Aggregation::ZScore),
+            &[("A2", 70.0 + spread), ("A1", 70.0 - spread)],
+        );
+    }
+
+    #[test]
+    fn z_scores_of_a_single_review_per_reviewer_fall_back_to_the_pooled_mean() {
+        let panel = reviewed(&[("A2", "R1", 40.0), ("A1", "R2", 80.0)]);
+
+        // Ties are broken by application id
+        assert_scores(
+            scores(&panel, Aggregation::ZScore),
+            &[("A1", 60.0), ("A2", 60.0)],
+        );
+    }
+
+    #[test]
+    fn z_scores_of_a_reviewer_with_zero_variance_are_neutral() {
+        let panel = reviewed(&[
+            ("A1", "R1", 70.0),
+            ("A2", "R1", 70.0),
+            ("A1", "R2", 50.0),
+            ("A2", "R2", 90.0),
+        ]);
+
+        let spread = 200f64.sqrt();
+        assert_scores(
+            scores(&panel, Aggregation::ZScore),
+            &[
+                ("A2", (70.0 + 70.0 + spread) / 2.0),
+                ("A1", (70.0 + 70.0 - spread) / 2.0),
+            ],
+        );
+    }
+
+    #[test]
+    fn a_review_without_a_score_cannot_be_aggregated() {
+        let mut panel = reviewed(&[("A1", "R1", 70.0)]);
+        panel.reviews.push(review("A1", "R1", None));
+
+        assert_eq!(
+            validation_field(panel.aggregate(Aggregation::Median)),
+            "reviews[1].score"
+        );
+    }
+}
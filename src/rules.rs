@@ -0,0 +1,640 @@
+// src/rules.rs
+/*
+ * Declarative eligibility rules evaluated against applications
+ */
+
+use crate::GrantApplication;
+use log::warn;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Comparison performed by a condition
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Operator {
+    /// Field equals the value
+    #[serde(rename = "==")]
+    Eq,
+    /// Field differs from the value
+    #[serde(rename = "!=")]
+    Ne,
+    /// Field is strictly less than the value
+    #[serde(rename = "<")]
+    Lt,
+    /// Field is less than or equal to the value
+    #[serde(rename = "<=")]
+    Le,
+    /// Field is strictly greater than the value
+    #[serde(rename = ">")]
+    Gt,
+    /// Field is greater than or equal to the value
+    #[serde(rename = ">=")]
+    Ge,
+    /// Field is one of the listed values
+    #[serde(rename = "in")]
+    In,
+    /// Field is none of the listed values
+    #[serde(rename = "not in", alias = "not_in")]
+    NotIn,
+    /// Field (a list or string) contains the value
+    #[serde(rename = "contains")]
+    Contains,
+}
+
+impl Operator {
+    fn symbol(self) -> &'static str {
+        match self {
+            Operator::Eq => "==",
+            Operator::Ne => "!=",
+            Operator::Lt => "<",
+            Operator::Le => "<=",
+            Operator::Gt => ">",
+            Operator::Ge => ">=",
+            Operator::In => "in",
+            Operator::NotIn => "not in",
+            Operator::Contains => "contains",
+        }
+    }
+}
+
+impl fmt::Display for Operator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.symbol())
+    }
+}
+
+/// A single `field op value` comparison
+///
+/// Conditions can be written either as an expression string such as
+/// `"applicant.org_type in [nonprofit, university]"` or as a table with
+/// `field`, `op` and `value` keys.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "ConditionSpec", into = "String")]
+pub struct Condition {
+    /// Dotted path of the application field, or one of the field aliases
+    pub field: String,
+    /// Comparison to perform
+    pub op: Operator,
+    /// Value the field is compared with
+    pub value: serde_json::Value,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ConditionSpec {
+    Expr(String),
+    Parts {
+        field: String,
+        op: Operator,
+        value: serde_json::Value,
+    },
+}
+
+impl TryFrom<ConditionSpec> for Condition {
+    type Error = String;
+
+    fn try_from(spec: ConditionSpec) -> std::result::Result<Self, Self::Error> {
+        match spec {
+            ConditionSpec::Expr(expr) => Condition::parse(&expr),
+            ConditionSpec::Parts { field, op, value } => Ok(Condition { field, op, value }),
+        }
+    }
+}
+
+impl From<Condition> for String {
+    fn from(condition: Condition) -> Self {
+        condition.to_string()
+    }
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {} {}", self.field, self.op, self.value)
+    }
+}
+
+/// Shorthand field names accepted in conditions
+const FIELD_ALIASES: &[(&str, &str)] = &[
+    ("requested_amount", "funding.amount"),
+    ("currency", "funding.currency"),
+    ("duration_months", "funding.duration_months"),
+    ("org_type", "applicant.org_type"),
+    ("country", "applicant.country"),
+    ("institution", "applicant.institution"),
+    ("program_id", "program.id"),
+];
+
+impl Condition {
+    /// Parse a condition from an expression such as `requested_amount <= 50000`
+    ///
+    /// # Arguments
+    ///
+    /// * `expr` - Expression of the form `<field> <op> <value>`, with optional spaces
+    ///   around symbolic operators; values may be numbers, booleans, quoted or bare
+    ///   strings, or `[a, b]` lists of those
+    ///
+    /// # Returns
+    ///
+    /// The parsed `Condition`, or a description of why the expression is malformed
+    pub fn parse(expr: &str) -> std::result::Result<Self, String> {
+        let expr = expr.trim();
+        let field_end = expr
+            .find(|c: char| !(c.is_alphanumeric() || matches!(c, '_' | '.' | '-')))
+            .unwrap_or(expr.len());
+        let (field, rest) = expr.split_at(field_end);
+        if field.is_empty() {
+            return Err(format!("expected '<field> <op> <value>' in '{}'", expr));
+        }
+        let rest = rest.trim_start();
+
+        // Longest operators first so "<=" is not read as "<"; word operators
+        // must not run into the value, so "in" never matches "index"
+        let operators = [
+            ("not in", Operator::NotIn),
+            ("contains", Operator::Contains),
+            ("in", Operator::In),
+            ("==", Operator::Eq),
+            ("!=", Operator::Ne),
+            ("<=", Operator::Le),
+            (">=", Operator::Ge),
+            ("<", Operator::Lt),
+            (">", Operator::Gt),
+        ];
+        let (op, value) = operators
+            .iter()
+            .find_map(|(symbol, op)| {
+                let value = rest.strip_prefix(symbol)?;
+                let is_word = symbol.starts_with(char::is_alphabetic);
+                let runs_on = value.starts_with(|c: char| c.is_alphanumeric() || c == '_');
+                (!(is_word && runs_on)).then_some((*op, value))
+            })
+            .ok_or_else(|| format!("unknown operator in '{}'", expr))?;
+
+        Ok(Condition {
+            field: field.to_string(),
+            op,
+            value: parse_literal(value.trim())?,
+        })
+    }
+
+    /// Evaluate the condition against an application
+    ///
+    /// # Returns
+    ///
+    /// The verdict together with a human readable reason
+    pub fn evaluate(&self, application: &GrantApplication) -> (Verdict, String) {
+        let actual = match resolve_field(application, &self.field) {
+            Some(value) if !value.is_null() => value,
+            _ => {
+                return (
+                    Verdict::NotApplicable,
+                    format!("{} is not provided", self.field),
+                )
+            }
+        };
+
+        let outcome = match self.op {
+            Operator::Eq => Some(loose_eq(&actual, &self.value)),
+            Operator::Ne => Some(!loose_eq(&actual, &self.value)),
+            Operator::Lt => compare(&actual, &self.value).map(|o| o.is_lt()),
+            Operator::Le => compare(&actual, &self.value).map(|o| o.is_le()),
+            Operator::Gt => compare(&actual, &self.value).map(|o| o.is_gt()),
+            Operator::Ge => compare(&actual, &self.value).map(|o| o.is_ge()),
+            Operator::In => self
+                .value
+                .as_array()
+                .map(|items| items.iter().any(|item| loose_eq(&actual, item))),
+            Operator::NotIn => self
+                .value
+                .as_array()
+                .map(|items| !items.iter().any(|item| loose_eq(&actual, item))),
+            Operator::Contains => match &actual {
+                serde_json::Value::Array(items) => {
+                    Some(items.iter().any(|item| loose_eq(item, &self.value)))
+                }
+                serde_json::Value::String(text) => self
+                    .value
+                    .as_str()
+                    .map(|needle| text.to_lowercase().contains(&needle.to_lowercase())),
+                _ => None,
+            },
+        };
+
+        match outcome {
+            Some(true) => (
+                Verdict::Passed,
+                format!(
+                    "{} is {}, satisfies {} {}",
+                    self.field, actual, self.op, self.value
+                ),
+            ),
+            Some(false) => (
+                Verdict::Failed,
+                format!(
+                    "{} is {}, required {} {}",
+                    self.field, actual, self.op, self.value
+                ),
+            ),
+            None => {
+                warn!("Cannot evaluate '{}' against value {}", self, actual);
+                (
+                    Verdict::NotApplicable,
+                    format!(
+                        "cannot compare {} ({}) using '{}'",
+                        self.field, actual, self.op
+                    ),
+                )
+            }
+        }
+    }
+}
+
+fn parse_literal(text: &str) -> std::result::Result<serde_json::Value, String> {
+    if let Some(inner) = text.strip_prefix('[') {
+        let inner = inner
+            .strip_suffix(']')
+            .ok_or_else(|| format!("unterminated list '{}'", text))?;
+        let items = split_items(inner)
+            .into_iter()
+            .map(str::trim)
+            .filter(|item| !item.is_empty())
+            .map(parse_literal)
+            .collect::<std::result::Result<Vec<_>, _>>()?;
+        return Ok(serde_json::Value::Array(items));
+    }
+
+    for quote in ['\'', '"'] {
+        if let Some(inner) = text.strip_prefix(quote) {
+            let inner = inner
+                .strip_suffix(quote)
+                .ok_or_else(|| format!("unterminated string {}", text))?;
+            return Ok(serde_json::Value::String(inner.to_string()));
+        }
+    }
+
+    if text.is_empty() {
+        return Err("missing value".to_string());
+    }
+    match text {
+        "true" => return Ok(serde_json::Value::Bool(true)),
+        "false" => return Ok(serde_json::Value::Bool(false)),
+        _ => {}
+    }
+    if let Ok(number) = text.parse::<f64>() {
+        return Ok(serde_json::json!(number));
+    }
+    Ok(serde_json::Value::String(text.to_string()))
+}
+
+/// Split the inside of a list literal at the commas that are not quoted
+fn split_items(text: &str) -> Vec<&str> {
+    let mut items = Vec::new();
+    let (mut start, mut quote) = (0, None);
+    for (position, c) in text.char_indices() {
+        match (quote, c) {
+            (None, '\'' | '"') => quote = Some(c),
+            (Some(open), _) if c == open => quote = None,
+            (None, ',') => {
+                items.push(&text[start..position]);
+                start = position + 1;
+            }
+            _ => {}
+        }
+    }
+    items.push(&text[start..]);
+    items
+}
+
+pub(crate) fn resolve_field(
+    application: &GrantApplication,
+    field: &str,
+) -> Option<serde_json::Value> {
+    let path = FIELD_ALIASES
+        .iter()
+        .find(|(alias, _)| *alias == field)
+        .map_or(field, |(_, path)| path);
+
+    // Fields are read straight from the struct; serializing the whole
+    // application is left for paths rules rarely use, such as budget lines
+    let value = match path {
+        "budget_total" => serde_json::json!(application.funding.budget_total()),
+        "document_count" => serde_json::json!(application.documents.len()),
+        "id" => serde_json::json!(application.id),
+        "title" => serde_json::json!(application.title),
+        "summary" => serde_json::json!(application.summary),
+        "applicant.id" => serde_json::json!(application.applicant.id),
+        "applicant.name" => serde_json::json!(application.applicant.name),
+        "applicant.org_type" => serde_json::json!(application.applicant.org_type),
+        "applicant.email" => serde_json::json!(application.applicant.email),
+        "applicant.institution" => serde_json::json!(application.applicant.institution),
+        "applicant.country" => serde_json::json!(application.applicant.country),
+        "program.id" => serde_json::json!(application.program.id),
+        "program.name" => serde_json::json!(application.program.name),
+        "funding.amount" => serde_json::json!(application.funding.amount),
+        "funding.currency" => serde_json::json!(application.funding.currency),
+        "funding.duration_months" => serde_json::json!(application.funding.duration_months),
+        "documents" => serde_json::json!(application.documents),
+        "keywords" => serde_json::json!(application.keywords),
+        "submitted_at" => serde_json::json!(application.submitted_at),
+        _ => match path.strip_prefix("ratings.") {
+            Some(criterion) => serde_json::json!(application.ratings.get(criterion)?),
+            None => {
+                let document = serde_json::to_value(application).ok()?;
+                let pointer = format!("/{}", path.replace('.', "/"));
+                return document.pointer(&pointer).cloned();
+            }
+        },
+    };
+    Some(value)
+}
+
+fn loose_eq(left: &serde_json::Value, right: &serde_json::Value) -> bool {
+    match (left, right) {
+        (serde_json::Value::String(a), serde_json::Value::String(b)) => a.eq_ignore_ascii_case(b),
+        (serde_json::Value::Number(a), serde_json::Value::Number(b)) => a.as_f64() == b.as_f64(),
+        _ => left == right,
+    }
+}
+
+fn compare(left: &serde_json::Value, right: &serde_json::Value) -> Option<std::cmp::Ordering> {
+    match (left, right) {
+        (serde_json::Value::Number(a), serde_json::Value::Number(b)) => {
+            a.as_f64()?.partial_cmp(&b.as_f64()?)
+        }
+        // ISO dates and timestamps order correctly as strings
+        (serde_json::Value::String(a), serde_json::Value::String(b)) => Some(a.cmp(b)),
+        _ => None,
+    }
+}
+
+/// Outcome of evaluating one rule
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Verdict {
+    /// The application satisfies the rule
+    Passed,
+    /// The application breaks the rule
+    Failed,
+    /// The rule does not apply to this application
+    NotApplicable,
+}
+
+/// A named eligibility rule
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Rule {
+    /// Identifier reported in rule outcomes
+    pub id: String,
+    /// What the rule is for, shown to applicants
+    #[serde(default)]
+    pub description: String,
+    /// Condition the application must satisfy
+    pub check: Condition,
+    /// Optional guard; when it does not hold the rule is not applicable
+    #[serde(default)]
+    pub when: Option<Condition>,
+}
+
+impl Rule {
+    /// Evaluate the rule against an application
+    ///
+    /// # Arguments
+    ///
+    /// * `application` - Application to evaluate
+    ///
+    /// # Returns
+    ///
+    /// A `RuleOutcome` explaining the verdict
+    pub fn evaluate(&self, application: &GrantApplication) -> RuleOutcome {
+        if let Some(guard) = &self.when {
+            let (verdict, reason) = guard.evaluate(application);
+            if verdict != Verdict::Passed {
+                return RuleOutcome::new(
+                    &self.id,
+                    Verdict::NotApplicable,
+                    format!("only applies when {} ({})", guard, reason),
+                );
+            }
+        }
+
+        let (verdict, reason) = self.check.evaluate(application);
+        RuleOutcome::new(&self.id, verdict, reason)
+    }
+}
+
+/// Result of evaluating a rule against one application
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RuleOutcome {
+    /// Identifier of the evaluated rule
+    pub rule_id: String,
+    /// Whether the rule passed, failed or did not apply
+    pub verdict: Verdict,
+    /// Explanation suitable for showing to the applicant
+    pub reason: String,
+}
+
+impl RuleOutcome {
+    /// Create a new rule outcome
+    pub fn new(rule_id: &str, verdict: Verdict, reason: impl Into<String>) -> Self {
+        Self {
+            rule_id: rule_id.to_string(),
+            verdict,
+            reason: reason.into(),
+        }
+    }
+
+    /// Whether the outcome rejects the application
+    pub fn is_failure(&self) -> bool {
+        self.verdict == Verdict::Failed
+    }
+}
+
+/// Evaluate every rule in order
+///
+/// # Arguments
+///
+/// * `rules` - Rules to evaluate
+/// * `application` - Application to evaluate them against
+///
+/// # Returns
+///
+/// One `RuleOutcome` per rule
+pub fn evaluate_all(rules: &[Rule], application: &GrantApplication) -> Vec<RuleOutcome> {
+    rules
+        .iter()
+        .map(|rule| rule.evaluate(application))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::application;
+    use serde_json::json;
+
+    fn condition(expr: &str) -> Condition {
+        Condition::parse(expr).unwrap_or_else(|e| panic!("'{}' does not parse: {}", expr, e))
+    }
+
+    #[test]
+    fn parses_operators_with_and_without_spaces() {
+        for (expr, op) in [
+            ("amount <= 5000", Operator::Le),
+            ("amount<=5000", Operator::Le),
+            ("amount>=5000", Operator::Ge),
+            ("amount<5000", Operator::Lt),
+            ("amount >5000", Operator::Gt),
+            ("amount==5000", Operator::Eq),
+            ("amount!= 5000", Operator::Ne),
+        ] {
+            let parsed = condition(expr);
+            assert_eq!(parsed.field, "amount", "{}", expr);
+            assert_eq!(parsed.op, op, "{}", expr);
+            assert_eq!(parsed.value, json!(5000.0), "{}", expr);
+        }
+    }
+
+    #[test]
+    fn parses_word_operators() {
+        let parsed = condition("org_type not in [nonprofit, university]");
+        assert_eq!(parsed.op, Operator::NotIn);
+        assert_eq!(parsed.value, json!(["nonprofit", "university"]));
+
+        let parsed = condition("keywords contains 'water'");
+        assert_eq!(parsed.op, Operator::Contains);
+        assert_eq!(parsed.value, json!("water"));
+
+        let parsed = condition("country in['US', 'CA']");
+        assert_eq!(parsed.op, Operator::In);
+        assert_eq!(parsed.value, json!(["US", "CA"]));
+    }
+
+    #[test]
+    fn keeps_commas_inside_quoted_list_items() {
+        let parsed = condition(r#"title in ["a, b", 'c,d', e]"#);
+        assert_eq!(parsed.value, json!(["a, b", "c,d", "e"]));
+    }
+
+    #[test]
+    fn parses_literals() {
+        assert_eq!(condition("x == true").value, json!(true));
+        assert_eq!(condition("x == -2.5").value, json!(-2.5));
+        assert_eq!(condition("x == bare").value, json!("bare"));
+        assert_eq!(condition("x == []").value, json!([]));
+    }
+
+    #[test]
+    fn rejects_malformed_expressions() {
+        for expr in [
+            "",
+            "<= 5",
+            "amount",
+            "amount ~ 5",
+            "amount <=",
+            "x in [a",
+            "x == 'a",
+        ] {
+            assert!(
+                Condition::parse(expr).is_err(),
+                "'{}' should not parse",
+                expr
+            );
+        }
+        assert!(Condition::parse("amount index 5").is_err());
+    }
+
+    #[test]
+    fn display_round_trips() {
+        let parsed = condition(r#"title in ["a, b", c]"#);
+        assert_eq!(condition(&parsed.to_string()), parsed);
+    }
+
+    #[test]
+    fn deserializes_expression_and_table_forms() {
+        let rule: Rule = serde_json::from_value(json!({
+            "id": "cap",
+            "check": { "field": "requested_amount", "op": "<=", "value": 50000 },
+            "when": "country == US"
+        }))
+        .unwrap();
+        assert_eq!(rule.check.op, Operator::Le);
+        assert_eq!(rule.when.unwrap().field, "country");
+    }
+
+    #[test]
+    fn resolves_aliases_paths_and_computed_fields() {
+        let app = application("APP-1", 30000.0);
+        assert_eq!(
+            resolve_field(&app, "requested_amount"),
+            Some(json!(30000.0))
+        );
+        assert_eq!(resolve_field(&app, "org_type"), Some(json!("nonprofit")));
+        assert_eq!(resolve_field(&app, "budget_total"), Some(json!(30000.0)));
+        assert_eq!(resolve_field(&app, "document_count"), Some(json!(2)));
+        assert_eq!(resolve_field(&app, "ratings.impact"), Some(json!(4.0)));
+        assert_eq!(resolve_field(&app, "ratings.missing"), None);
+        assert_eq!(
+            resolve_field(&app, "funding.budget.0.category"),
+            Some(json!("personnel"))
+        );
+        assert_eq!(resolve_field(&app, "no.such.field"), None);
+    }
+
+    #[test]
+    fn evaluates_conditions() {
+        let app = application("APP-1", 30000.0);
+        let verdict = |expr: &str| condition(expr).evaluate(&app).0;
+        assert_eq!(verdict("requested_amount<=50000"), Verdict::Passed);
+        assert_eq!(verdict("requested_amount > 50000"), Verdict::Failed);
+        assert_eq!(verdict("country == us"), Verdict::Passed);
+        assert_eq!(
+            verdict("org_type in [university, nonprofit]"),
+            Verdict::Passed
+        );
+        assert_eq!(verdict("org_type not in [nonprofit]"), Verdict::Failed);
+        assert_eq!(verdict("keywords contains WATER"), Verdict::Passed);
+        assert_eq!(verdict("title contains clean"), Verdict::Passed);
+        assert_eq!(
+            verdict("submitted_at >= '2026-01-01'"),
+            Verdict::NotApplicable
+        );
+        assert_eq!(verdict("title < 5"), Verdict::NotApplicable);
+    }
+
+    #[test]
+    fn guard_makes_rule_not_applicable() {
+        let app = application("APP-1", 30000.0);
+        let rule = Rule {
+            id: "cap".to_string(),
+            description: String::new(),
+            check: condition("requested_amount <= 10000"),
+            when: Some(condition("country == CA")),
+        };
+        let outcome = rule.evaluate(&app);
+        assert_eq!(outcome.verdict, Verdict::NotApplicable);
+        assert!(!outcome.is_failure());
+
+        let rule = Rule {
+            when: Some(condition("country == US")),
+            ..rule
+        };
+        let outcome = rule.evaluate(&app);
+        assert_eq!(outcome.verdict, Verdict::Failed);
+        assert_eq!(outcome.rule_id, "cap");
+    }
+
+    #[test]
+    fn evaluates_every_rule_in_order() {
+        let app = application("APP-1", 30000.0);
+        let rules: Vec<Rule> = ["a", "b"]
+            .iter()
+            .map(|id| Rule {
+                id: id.to_string(),
+                description: String::new(),
+                check: condition("requested_amount > 0"),
+                when: None,
+            })
+            .collect();
+        let outcomes = evaluate_all(&rules, &app);
+        let ids: Vec<&str> = outcomes.iter().map(|o| o.rule_id.as_str()).collect();
+        assert_eq!(ids, ["a", "b"]);
+    }
+}
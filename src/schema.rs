@@ -0,0 +1,773 @@
+// src/schema.rs
+/*
+ * JSON Schema generation for the application types and validation against it
+ */
+
+use crate::{
+    Applicant, ApplicantType, BudgetLine, FundingRequest, GrantApplication, GrantError, Program,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use std::fmt;
+use std::sync::OnceLock;
+
+/// JSON Schema dialect of the generated schemas
+pub const SCHEMA_DIALECT: &str = "https://json-schema.org/draft/2020-12/schema";
+
+/// Types that can describe themselves as a JSON Schema
+///
+/// The schema must accept exactly what the type's `Deserialize` impl accepts,
+/// plus the constraints `GrantApplication::validate` checks field by field.
+pub trait JsonSchema {
+    /// Name the type is listed under in `$defs`
+    fn schema_name() -> &'static str;
+
+    /// Schema of the type; nested types are added to `definitions` and referenced
+    fn json_schema(definitions: &mut Definitions) -> Value;
+}
+
+/// The `$defs` section collected while building a schema
+#[derive(Debug, Default)]
+pub struct Definitions {
+    schemas: Map<String, Value>,
+}
+
+impl Definitions {
+    /// Add `T` to the definitions if it is not there yet
+    ///
+    /// # Returns
+    ///
+    /// A `$ref` schema pointing at the definition
+    pub fn reference<T: JsonSchema>(&mut self) -> Value {
+        let name = T::schema_name();
+        if !self.schemas.contains_key(name) {
+            // Reserve the name first so recursive types terminate
+            self.schemas.insert(name.to_string(), Value::Null);
+            let schema = T::json_schema(self);
+            self.schemas.insert(name.to_string(), schema);
+        }
+        json!({ "$ref": format!("#/$defs/{}", name) })
+    }
+}
+
+/// Build a complete schema document for `T`
+///
+/// # Returns
+///
+/// The schema with `$schema`, `title` and, when nested types were used, `$defs`
+pub fn root_schema<T: JsonSchema>() -> Value {
+    let mut definitions = Definitions::default();
+    let mut document = Map::new();
+    document.insert("$schema".into(), SCHEMA_DIALECT.into());
+    document.insert("title".into(), T::schema_name().into());
+    if let Value::Object(fields) = T::json_schema(&mut definitions) {
+        document.extend(fields);
+    }
+    if !definitions.schemas.is_empty() {
+        document.insert("$defs".into(), Value::Object(definitions.schemas));
+    }
+    Value::Object(document)
+}
+
+/// Schema every submitted application is checked against
+pub fn application_schema() -> &'static Value {
+    static SCHEMA: OnceLock<Value> = OnceLock::new();
+    SCHEMA.get_or_init(root_schema::<GrantApplication>)
+}
+
+impl JsonSchema for GrantApplication {
+    fn schema_name() -> &'static str {
+        "GrantApplication"
+    }
+
+    fn json_schema(definitions: &mut Definitions) -> Value {
+        json!({
+            "description": "A grant application as submitted by an applicant",
+            "type": "object",
+            "required": ["id", "title", "applicant", "program", "funding"],
+            "properties": {
+                "id": { "description": "Application identifier", "type": "string", "minLength": 1 },
+                "title": { "description": "Project title", "type": "string", "minLength": 1 },
+                "summary": { "description": "Short project summary", "type": "string" },
+                "applicant": definitions.reference::<Applicant>(),
+                "program": definitions.reference::<Program>(),
+                "funding": definitions.reference::<FundingRequest>(),
+                "documents": {
+                    "description": "Names of the supporting documents attached",
+                    "type": "array",
+                    "items": { "type": "string" }
+                },
+                "keywords": {
+                    "description": "Subject keywords, matched against reviewer expertise",
+                    "type": "array",
+                    "items": { "type": "string" }
+                },
+                "submitted_at": {
+                    "description": "Submission timestamp (RFC 3339)",
+                    "type": ["string", "null"],
+                    "format": "date-time"
+                },
+                "ratings": {
+                    "description": "Ratings per scoring criterion, keyed by criterion id",
+                    "type": "object",
+                    "additionalProperties": { "type": "number" }
+                }
+            }
+        })
+    }
+}
+
+impl JsonSchema for Applicant {
+    fn schema_name() -> &'static str {
+        "Applicant"
+    }
+
+    fn json_schema(definitions: &mut Definitions) -> Value {
+        json!({
+            "description": "The person or organisation submitting an application",
+            "type": "object",
+            "required": ["id", "name", "org_type", "email", "country"],
+            "properties": {
+                "id": { "description": "Stable applicant identifier", "type": "string", "minLength": 1 },
+                "name": { "description": "Display name of the applicant", "type": "string", "minLength": 1 },
+                "org_type": definitions.reference::<ApplicantType>(),
+                "email": { "description": "Contact email address", "type": "string", "format": "email" },
+                "institution": {
+                    "description": "Institution the applicant belongs to, if any",
+                    "type": ["string", "null"]
+                },
+                "country": { "description": "ISO 3166 country code", "type": "string", "minLength": 1 }
+            }
+        })
+    }
+}
+
+impl JsonSchema for ApplicantType {
+    fn schema_name() -> &'static str {
+        "ApplicantType"
+    }
+
+    fn json_schema(_definitions: &mut Definitions) -> Value {
+        json!({
+            "description": "Kind of organisation behind an application",
+            "type": "string",
+            "enum": ["nonprofit", "university", "for_profit", "government", "individual", "other"]
+        })
+    }
+}
+
+impl JsonSchema for Program {
+    fn schema_name() -> &'static str {
+        "Program"
+    }
+
+    fn json_schema(_definitions: &mut Definitions) -> Value {
+        json!({
+            "description": "Reference to the program an application is submitted to",
+            "type": "object",
+            "required": ["id"],
+            "properties": {
+                "id": { "description": "Program identifier", "type": "string", "minLength": 1 },
+                "name": { "description": "Program display name", "type": "string" }
+            }
+        })
+    }
+}
+
+impl JsonSchema for FundingRequest {
+    fn schema_name() -> &'static str {
+        "FundingRequest"
+    }
+
+    fn json_schema(definitions: &mut Definitions) -> Value {
+        json!({
+            "description": "The money being asked for and how it will be spent",
+            "type": "object",
+            "required": ["amount"],
+            "properties": {
+                "amount": { "description": "Total amount requested", "type": "number", "exclusiveMinimum": 0 },
+                "currency": { "description": "ISO 4217 currency code", "type": "string", "default": "USD" },
+                "duration_months": {
+                    "description": "Project duration in months",
+                    "type": "integer",
+                    "minimum": 0,
+                    "maximum": u32::MAX
+                },
+                "budget": {
+                    "description": "Itemised budget backing the request",
+                    "type": "array",
+                    "items": definitions.reference::<BudgetLine>()
+                }
+            }
+        })
+    }
+}
+
+impl JsonSchema for BudgetLine {
+    fn schema_name() -> &'static str {
+        "BudgetLine"
+    }
+
+    fn json_schema(_definitions: &mut Definitions) -> Value {
+        json!({
+            "description": "A single line of a proposed budget",
+            "type": "object",
+            "required": ["category", "amount"],
+            "properties": {
+                "category": { "description": "Budget category", "type": "string" },
+                "description": { "description": "Free-form description of the expense", "type": "string" },
+                "amount": { "description": "Amount requested for this line", "type": "number", "minimum": 0 }
+            }
+        })
+    }
+}
+
+/// A place where a document breaks its schema
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SchemaViolation {
+    /// JSON Pointer to the offending value; empty for the document itself
+    pub instance_path: String,
+    /// JSON Pointer to the schema keyword that failed, e.g.
+    /// `#/$defs/FundingRequest/properties/amount/exclusiveMinimum`
+    pub schema_path: String,
+    /// What is wrong
+    pub message: String,
+}
+
+impl SchemaViolation {
+    /// The instance path as a dotted field path such as `funding.budget[0].amount`
+    pub fn field(&self) -> String {
+        let mut field = String::new();
+        for segment in self.instance_path.split('/').skip(1) {
+            let segment = segment.replace("~1", "/").replace("~0", "~");
+            if segment.parse::<usize>().is_ok() {
+                field.push_str(&format!("[{}]", segment));
+            } else {
+                if !field.is_empty() {
+                    field.push('.');
+                }
+                field.push_str(&segment);
+            }
+        }
+        field
+    }
+}
+
+impl fmt::Display for SchemaViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let path = if self.instance_path.is_empty() {
+            "/"
+        } else {
+            &self.instance_path
+        };
+        write!(
+            f,
+            "{}: {} (schema {})",
+            path, self.message, self.schema_path
+        )
+    }
+}
+
+/// Check a document against a schema
+///
+/// Supports the keywords the generated schemas use: `$ref` to `#/...`,
+/// `type`, `enum`, `required`, `properties`, `additionalProperties`, `items`,
+/// `minLength`, `minimum`, `maximum`, `exclusiveMinimum` and the `date-time`
+/// and `email` formats.
+///
+/// # Arguments
+///
+/// * `schema` - Root schema; `$ref`s are resolved against it
+/// * `instance` - Document to check
+///
+/// # Returns
+///
+/// Every violation found; empty when the document is valid
+pub fn validate(schema: &Value, instance: &Value) -> Vec<SchemaViolation> {
+    let mut validator = Validator {
+        root: schema,
+        violations: Vec::new(),
+    };
+    validator.check(schema, "#", instance, "");
+    validator.violations
+}
+
+/// Check a document against the application schema
+pub fn validate_application(instance: &Value) -> Vec<SchemaViolation> {
+    validate(application_schema(), instance)
+}
+
+/// Turn a document into an application, checking it against the schema first
+///
+/// # Arguments
+///
+/// * `value` - Parsed JSON of one application
+///
+/// # Returns
+///
+/// The application, or every schema violation found
+pub fn parse_application(
+    value: Value,
+) -> std::result::Result<GrantApplication, Vec<SchemaViolation>> {
+    let violations = validate_application(&value);
+    if !violations.is_empty() {
+        return Err(violations);
+    }
+    serde_json::from_value(value).map_err(|e| {
+        vec![SchemaViolation {
+            instance_path: String::new(),
+            schema_path: "#".to_string(),
+            message: e.to_string(),
+        }]
+    })
+}
+
+/// Summarise violations as one message, e.g. for a `RecordError`
+pub fn describe(violations: &[SchemaViolation]) -> String {
+    violations
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+/// Turn the violations of a single application into a validation error
+///
+/// The error names the field of the first violation, or `application` when
+/// the document as a whole is wrong.
+pub fn violation_error(violations: &[SchemaViolation]) -> GrantError {
+    let field = violations
+        .first()
+        .map(SchemaViolation::field)
+        .filter(|field| !field.is_empty())
+        .unwrap_or_else(|| "application".to_string());
+    GrantError::validation(field, describe(violations))
+}
+
+struct Validator<'a> {
+    root: &'a Value,
+    violations: Vec<SchemaViolation>,
+}
+
+impl Validator<'_> {
+    fn fail(&mut self, instance_path: &str, schema_path: String, message: String) {
+        self.violations.push(SchemaViolation {
+            instance_path: instance_path.to_string(),
+            schema_path,
+            message,
+        });
+    }
+
+    fn check(&mut self, schema: &Value, schema_path: &str, instance: &Value, instance_path: &str) {
+        let Value::Object(keywords) = schema else {
+            return;
+        };
+
+        if let Some(Value::String(reference)) = keywords.get("$ref") {
+            match reference
+                .strip_prefix('#')
+                .and_then(|pointer| self.root.pointer(pointer))
+            {
+                Some(target) => self.check(target, reference, instance, instance_path),
+                None => self.fail(
+                    instance_path,
+                    format!("{}/$ref", schema_path),
+                    format!("unresolvable reference '{}'", reference),
+                ),
+            }
+        }
+
+        if let Some(types) = keywords.get("type") {
+            let allowed: Vec<&str> = match types {
+                Value::String(name) => vec![name.as_str()],
+                Value::Array(names) => names.iter().filter_map(Value::as_str).collect(),
+                _ => Vec::new(),
+            };
+            if !allowed.iter().any(|name| has_type(instance, name)) {
+                self.fail(
+                    instance_path,
+                    format!("{}/type", schema_path),
+                    format!(
+                        "expected {}, got {}",
+                        allowed.join(" or "),
+                        type_name(instance)
+                    ),
+                );
+                return;
+            }
+        }
+
+        if let Some(Value::Array(options)) = keywords.get("enum") {
+            if !options.contains(instance) {
+                let names: Vec<String> = options.iter().map(ToString::to_string).collect();
+                self.fail(
+                    instance_path,
+                    format!("{}/enum", schema_path),
+                    format!("{} is not one of {}", instance, names.join(", ")),
+                );
+            }
+        }
+
+        match instance {
+            Value::String(text) => self.check_string(keywords, schema_path, text, instance_path),
+            Value::Number(number) => {
+                let value = number.as_f64().unwrap_or(f64::NAN);
+                self.check_number(keywords, schema_path, value, instance_path);
+            }
+            Value::Array(items) => {
+                if let Some(item_schema) = keywords.get("items") {
+                    for (index, item) in items.iter().enumerate() {
+                        self.check(
+                            item_schema,
+                            &format!("{}/items", schema_path),
+                            item,
+                            &format!("{}/{}", instance_path, index),
+                        );
+                    }
+                }
+            }
+            Value::Object(fields) => {
+                self.check_object(keywords, schema_path, fields, instance_path)
+            }
+            Value::Bool(_) | Value::Null => {}
+        }
+    }
+
+    fn check_string(
+        &mut self,
+        keywords: &Map<String, Value>,
+        schema_path: &str,
+        text: &str,
+        instance_path: &str,
+    ) {
+        if let Some(min) = keywords.get("minLength").and_then(Value::as_u64) {
+            if (text.chars().count() as u64) < min {
+                self.fail(
+                    instance_path,
+                    format!("{}/minLength", schema_path),
+                    format!("must be at least {} character(s) long", min),
+                );
+            }
+        }
+        let valid = match keywords.get("format").and_then(Value::as_str) {
+            Some("date-time") => chrono::DateTime::parse_from_rfc3339(text).is_ok(),
+            Some("email") => text.contains('@'),
+            _ => true,
+        };
+        if !valid {
+            let format = keywords["format"].as_str().unwrap_or_default();
+            self.fail(
+                instance_path,
+                format!("{}/format", schema_path),
+                format!("'{}' is not a valid {}", text, format),
+            );
+        }
+    }
+
+    fn check_number(
+        &mut self,
+        keywords: &Map<String, Value>,
+        schema_path: &str,
+        value: f64,
+        instance_path: &str,
+    ) {
+        let bound = |name: &str| keywords.get(name).and_then(Value::as_f64);
+        if let Some(min) = bound("minimum").filter(|&min| value < min) {
+            self.fail(
+                instance_path,
+                format!("{}/minimum", schema_path),
+                format!("must be at least {}, got {}", min, value),
+            );
+        }
+        if let Some(max) = bound("maximum").filter(|&max| value > max) {
+            self.fail(
+                instance_path,
+                format!("{}/maximum", schema_path),
+                format!("must be at most {}, got {}", max, value),
+            );
+        }
+        if let Some(min) = bound("exclusiveMinimum").filter(|&min| value <= min) {
+            self.fail(
+                instance_path,
+                format!("{}/exclusiveMinimum", schema_path),
+                format!("must be greater than {}, got {}", min, value),
+            );
+        }
+    }
+
+    fn check_object(
+        &mut self,
+        keywords: &Map<String, Value>,
+        schema_path: &str,
+        fields: &Map<String, Value>,
+        instance_path: &str,
+    ) {
+        if let Some(Value::Array(required)) = keywords.get("required") {
+            for name in required.iter().filter_map(Value::as_str) {
+                if !fields.contains_key(name) {
+                    self.fail(
+                        instance_path,
+                        format!("{}/required", schema_path),
+                        format!("missing required property '{}'", name),
+                    );
+                }
+            }
+        }
+
+        let properties = keywords.get("properties").and_then(Value::as_object);
+        for (name, value) in fields {
+            let path = format!("{}/{}", instance_path, escape(name));
+            match properties.and_then(|properties| properties.get(name)) {
+                Some(property) => self.check(
+                    property,
+                    &format!("{}/properties/{}", schema_path, escape(name)),
+                    value,
+                    &path,
+                ),
+                None => match keywords.get("additionalProperties") {
+                    Some(Value::Bool(false)) => self.fail(
+                        &path,
+                        format!("{}/additionalProperties", schema_path),
+                        format!("unknown property '{}'", name),
+                    ),
+                    Some(additional @ Value::Object(_)) => self.check(
+                        additional,
+                        &format!("{}/additionalProperties", schema_path),
+                        value,
+                        &path,
+                    ),
+                    _ => {}
+                },
+            }
+        }
+    }
+}
+
+fn has_type(instance: &Value, name: &str) -> bool {
+    match name {
+        "object" => instance.is_object(),
+        "array" => instance.is_array(),
+        "string" => instance.is_string(),
+        "number" => instance.is_number(),
+        "integer" => {
+            instance.is_i64()
+                || instance.is_u64()
+                || instance.as_f64().is_some_and(|value| value.fract() == 0.0)
+        }
+        "boolean" => instance.is_boolean(),
+        "null" => instance.is_null(),
+        _ => false,
+    }
+}
+
+fn type_name(instance: &Value) -> &'static str {
+    match instance {
+        Value::Object(_) => "object",
+        Value::Array(_) => "array",
+        Value::String(_) => "string",
+        Value::Number(_) => "number",
+        Value::Bool(_) => "boolean",
+        Value::Null => "null",
+    }
+}
+
+/// Escape a property name for use in a JSON Pointer
+fn escape(name: &str) -> String {
+    name.replace('~', "~0").replace('/', "~1")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::application;
+
+    fn document() -> Value {
+        serde_json::to_value(application("A1", 10_000.0)).unwrap()
+    }
+
+    fn paths(violations: &[SchemaViolation]) -> Vec<(&str, &str)> {
+        violations
+            .iter()
+            .map(|violation| {
+                (
+                    violation.instance_path.as_str(),
+                    violation.schema_path.as_str(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn a_serialized_application_is_valid() {
+        let document = document();
+        assert_eq!(validate_application(&document), []);
+        assert_eq!(
+            parse_application(document).unwrap(),
+            application("A1", 10_000.0)
+        );
+    }
+
+    #[test]
+    fn the_schema_lists_every_nested_type() {
+        let schema = application_schema();
+        assert_eq!(schema["$schema"], SCHEMA_DIALECT);
+        assert_eq!(schema["title"], "GrantApplication");
+        let names: Vec<&String> = schema["$defs"].as_object().unwrap().keys().collect();
+        for name in [
+            "Applicant",
+            "ApplicantType",
+            "Program",
+            "FundingRequest",
+            "BudgetLine",
+        ] {
+            assert!(names.iter().any(|defined| *defined == name), "{name}");
+        }
+    }
+
+    #[test]
+    fn missing_required_properties_are_reported_where_they_belong() {
+        let mut document = document();
+        let fields = document.as_object_mut().unwrap();
+        fields.remove("title");
+        fields["applicant"].as_object_mut().unwrap().remove("email");
+
+        let violations = validate_application(&document);
+        assert_eq!(
+            paths(&violations),
+            [
+                ("", "#/required"),
+                ("/applicant", "#/$defs/Applicant/required")
+            ]
+        );
+        assert_eq!(violations[0].message, "missing required property 'title'");
+        assert_eq!(violations[1].message, "missing required property 'email'");
+    }
+
+    #[test]
+    fn keyword_failures_carry_instance_and_schema_paths() {
+        let mut document = document();
+        document["title"] = json!("");
+        document["applicant"]["org_type"] = json!("charity");
+        document["applicant"]["email"] = json!("nobody");
+        document["funding"]["amount"] = json!(0);
+        document["funding"]["duration_months"] = json!(1.5);
+        document["funding"]["budget"][0]["amount"] = json!(-1);
+        document["submitted_at"] = json!("yesterday");
+        document["ratings"]["impact"] = json!("high");
+
+        let violations = validate_application(&document);
+        let mut found = paths(&violations);
+        found.sort();
+        assert_eq!(
+            found,
+            [
+                (
+                    "/applicant/email",
+                    "#/$defs/Applicant/properties/email/format"
+                ),
+                ("/applicant/org_type", "#/$defs/ApplicantType/enum"),
+                (
+                    "/funding/amount",
+                    "#/$defs/FundingRequest/properties/amount/exclusiveMinimum"
+                ),
+                (
+                    "/funding/budget/0/amount",
+                    "#/$defs/BudgetLine/properties/amount/minimum"
+                ),
+                (
+                    "/funding/duration_months",
+                    "#/$defs/FundingRequest/properties/duration_months/type"
+                ),
+                (
+                    "/ratings/impact",
+                    "#/properties/ratings/additionalProperties/type"
+                ),
+                ("/submitted_at", "#/properties/submitted_at/format"),
+                ("/title", "#/properties/title/minLength"),
+            ]
+        );
+    }
+
+    #[test]
+    fn a_wrong_type_stops_checking_below_it() {
+        let mut document = document();
+        document["funding"] = json!("lots");
+
+        let violations = validate_application(&document);
+        assert_eq!(
+            paths(&violations),
+            [("/funding", "#/$defs/FundingRequest/type")]
+        );
+        assert_eq!(violations[0].message, "expected object, got string");
+    }
+
+    #[test]
+    fn parse_application_returns_every_violation() {
+        let mut document = document();
+        document["id"] = json!("");
+        document["funding"]["amount"] = json!(-5);
+
+        let violations = parse_application(document).unwrap_err();
+        assert_eq!(violations.len(), 2);
+        assert!(describe(&violations).contains("; "));
+    }
+
+    #[test]
+    fn violations_name_their_field() {
+        let violation = SchemaViolation {
+            instance_path: "/funding/budget/0/amount".to_string(),
+            schema_path: "#/$defs/BudgetLine/properties/amount/minimum".to_string(),
+            message: "must be at least 0, got -1".to_string(),
+        };
+        assert_eq!(violation.field(), "funding.budget[0].amount");
+        assert_eq!(
+            violation.to_string(),
+            "/funding/budget/0/amount: must be at least 0, got -1 \
+             (schema #/$defs/BudgetLine/properties/amount/minimum)"
+        );
+
+        let escaped = SchemaViolation {
+            instance_path: "/ratings/a~1b~0c".to_string(),
+            ..violation
+        };
+        assert_eq!(escaped.field(), "ratings.a/b~c");
+    }
+
+    #[test]
+    fn violation_errors_name_the_first_field() {
+        let mut document = document();
+        document["applicant"]["country"] = json!("");
+        let violations = validate_application(&document);
+
+        match violation_error(&violations) {
+            GrantError::Validation { field, message, .. } => {
+                assert_eq!(field, "applicant.country");
+                assert!(message.contains("at least 1 character"), "{message}");
+            }
+            other => panic!("expected a validation error, got {other:?}"),
+        }
+        match violation_error(&validate_application(&json!([]))) {
+            GrantError::Validation { field, .. } => assert_eq!(field, "application"),
+            other => panic!("expected a validation error, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn unresolvable_references_are_violations() {
+        let schema = json!({ "$ref": "#/$defs/Missing" });
+        let violations = validate(&schema, &json!({}));
+        assert_eq!(paths(&violations), [("", "#/$ref")]);
+    }
+
+    #[test]
+    fn closed_objects_reject_unknown_properties() {
+        let schema = json!({
+            "type": "object",
+            "properties": { "a": { "type": "integer" } },
+            "additionalProperties": false
+        });
+        assert_eq!(validate(&schema, &json!({ "a": 2.0 })), []);
+        let violations = validate(&schema, &json!({ "a": 1, "b~/": 2 }));
+        assert_eq!(paths(&violations), [("/b~0~1", "#/additionalProperties")]);
+    }
+}
@@ -0,0 +1,384 @@
+// src/scoring.rs
+/*
+ * Weighted multi-criteria scoring of applications
+ */
+
+use crate::rules::resolve_field;
+use crate::{GrantApplication, GrantError, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+
+/// Range of raw values a criterion is rated on
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Scale {
+    /// Worst possible rating
+    pub min: f64,
+    /// Best possible rating
+    pub max: f64,
+}
+
+impl Default for Scale {
+    fn default() -> Self {
+        Self { min: 0.0, max: 5.0 }
+    }
+}
+
+/// One criterion of a scoring rubric
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Criterion {
+    /// Identifier, also the key looked up in `GrantApplication::ratings`
+    pub id: String,
+    /// Display name
+    #[serde(default)]
+    pub name: String,
+    /// Relative weight of the criterion
+    pub weight: f64,
+    /// Range raw ratings are given on
+    #[serde(default)]
+    pub scale: Scale,
+    /// Numeric application field to score instead of a rating (e.g. `budget_total`)
+    #[serde(default)]
+    pub field: Option<String>,
+    /// Whether higher raw values are better; set to false for costs
+    #[serde(default = "default_true")]
+    pub higher_is_better: bool,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+/// How applications of a program are scored
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Rubric {
+    /// Criteria making up the composite score
+    pub criteria: Vec<Criterion>,
+}
+
+/// Score awarded for a single criterion
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CriterionScore {
+    /// Criterion identifier
+    pub criterion: String,
+    /// Raw value as rated or read from the application
+    pub raw: f64,
+    /// Raw value mapped onto 0..=1 within the criterion's scale
+    pub normalized: f64,
+    /// Share of the total weight carried by this criterion
+    pub weight: f64,
+    /// Points contributed to the composite score (out of 100)
+    pub contribution: f64,
+}
+
+/// Composite score of one application with its per-criterion breakdown
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ScoreCard {
+    /// Weighted score from 0 to 100
+    pub total: f64,
+    /// Contribution of every criterion that could be scored
+    pub breakdown: Vec<CriterionScore>,
+    /// Criteria with no rating; they score 0 but keep their weight
+    pub missing: Vec<String>,
+}
+
+impl Rubric {
+    /// Check that the rubric is usable
+    ///
+    /// # Returns
+    ///
+    /// `Ok(())`, or a validation error naming the offending criterion
+    pub fn validate(&self) -> Result<()> {
+        let mut seen = HashSet::new();
+        for (index, criterion) in self.criteria.iter().enumerate() {
+            let field = |name: &str| format!("rubric.criteria[{}].{}", index, name);
+            if criterion.id.trim().is_empty() {
+                return Err(GrantError::validation(field("id"), "must not be empty"));
+            }
+            if !seen.insert(criterion.id.as_str()) {
+                return Err(GrantError::validation(
+                    field("id"),
+                    format!("'{}' is defined more than once", criterion.id),
+                ));
+            }
+            if !criterion.weight.is_finite() || criterion.weight < 0.0 {
+                return Err(GrantError::validation(
+                    field("weight"),
+                    format!("must be a non-negative number, got {}", criterion.weight),
+                ));
+            }
+            let scale = criterion.scale;
+            if !(scale.min.is_finite() && scale.max.is_finite() && scale.max > scale.min) {
+                return Err(GrantError::validation(
+                    field("scale"),
+                    format!(
+                        "max ({}) must be greater than min ({})",
+                        criterion.scale.max, criterion.scale.min
+                    ),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Score an application against the rubric
+    ///
+    /// # Arguments
+    ///
+    /// * `application` - Application to score
+    ///
+    /// # Returns
+    ///
+    /// A `ScoreCard` whose total is the weighted mean of the normalized
+    /// criterion scores, scaled to 0..=100. A criterion without a value
+    /// counts as 0, so a partly rated application never outscores the same
+    /// application rated in full.
+    pub fn score(&self, application: &GrantApplication) -> ScoreCard {
+        score_with(self.criteria.iter(), |criterion| {
+            raw_value(criterion, application)
+        })
+    }
+
+    /// Score a set of ratings on their own, e.g. those of a single reviewer
+    ///
+    /// Criteria read from an application field are not rated by reviewers
+    /// and are left out; a rated criterion without a rating counts as 0.
+    ///
+    /// # Arguments
+    ///
+    /// * `ratings` - Ratings keyed by criterion id
+    ///
+    /// # Returns
+    ///
+    /// A `ScoreCard` over the criteria reviewers rate
+    pub fn score_ratings(&self, ratings: &BTreeMap<String, f64>) -> ScoreCard {
+        let rated = self
+            .criteria
+            .iter()
+            .filter(|criterion| criterion.field.is_none());
+        score_with(rated, |criterion| {
+            ratings
+                .get(&criterion.id)
+                .copied()
+                .filter(|value| value.is_finite())
+        })
+    }
+}
+
+/// Weighted score over `criteria`, with every criterion's weight counted
+/// whether or not `raw_value` has a value for it
+fn score_with<'a>(
+    criteria: impl Iterator<Item = &'a Criterion>,
+    raw_value: impl Fn(&Criterion) -> Option<f64>,
+) -> ScoreCard {
+    let mut rated = Vec::new();
+    let mut missing = Vec::new();
+    let mut total_weight = 0.0;
+
+    for criterion in criteria {
+        total_weight += criterion.weight;
+        match raw_value(criterion) {
+            Some(raw) => rated.push((criterion, raw)),
+            None => missing.push(criterion.id.clone()),
+        }
+    }
+
+    let breakdown: Vec<CriterionScore> = rated
+        .into_iter()
+        .map(|(criterion, raw)| {
+            let span = criterion.scale.max - criterion.scale.min;
+            let mut normalized = ((raw - criterion.scale.min) / span).clamp(0.0, 1.0);
+            if !criterion.higher_is_better {
+                normalized = 1.0 - normalized;
+            }
+            let weight = if total_weight > 0.0 {
+                criterion.weight / total_weight
+            } else {
+                0.0
+            };
+            CriterionScore {
+                criterion: criterion.id.clone(),
+                raw,
+                normalized,
+                weight,
+                contribution: normalized * weight * 100.0,
+            }
+        })
+        .collect();
+
+    ScoreCard {
+        total: breakdown.iter().map(|score| score.contribution).sum(),
+        breakdown,
+        missing,
+    }
+}
+
+fn raw_value(criterion: &Criterion, application: &GrantApplication) -> Option<f64> {
+    let value = match &criterion.field {
+        Some(field) => resolve_field(application, field)?.as_f64()?,
+        None => *application.ratings.get(&criterion.id)?,
+    };
+    value.is_finite().then_some(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::application;
+
+    fn criterion(id: &str, weight: f64) -> Criterion {
+        Criterion {
+            id: id.to_string(),
+            name: String::new(),
+            weight,
+            scale: Scale::default(),
+            field: None,
+            higher_is_better: true,
+        }
+    }
+
+    fn rubric(criteria: Vec<Criterion>) -> Rubric {
+        Rubric { criteria }
+    }
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!((actual - expected).abs() < 1e-9, "{actual} != {expected}");
+    }
+
+    #[test]
+    fn total_is_the_weighted_mean_of_normalized_ratings() {
+        // impact 4/5 and feasibility 3/5, weighted 3:1
+        let rubric = rubric(vec![
+            criterion("impact", 3.0),
+            criterion("feasibility", 1.0),
+        ]);
+
+        let card = rubric.score(&application("APP-1", 10_000.0));
+        assert_close(card.total, 75.0);
+        assert!(card.missing.is_empty());
+        let expected = [
+            ("impact", 0.8, 0.75, 60.0),
+            ("feasibility", 0.6, 0.25, 15.0),
+        ];
+        assert_eq!(card.breakdown.len(), expected.len());
+        for (score, (id, normalized, weight, contribution)) in card.breakdown.iter().zip(expected) {
+            assert_eq!(score.criterion, id);
+            assert_close(score.normalized, normalized);
+            assert_close(score.weight, weight);
+            assert_close(score.contribution, contribution);
+        }
+    }
+
+    #[test]
+    fn field_criteria_read_the_application_and_costs_are_inverted() {
+        let mut cost = criterion("cost", 1.0);
+        cost.field = Some("funding.amount".to_string());
+        cost.scale = Scale {
+            min: 0.0,
+            max: 50_000.0,
+        };
+        cost.higher_is_better = false;
+        let rubric = rubric(vec![cost]);
+
+        assert_close(rubric.score(&application("APP-1", 10_000.0)).total, 80.0);
+        // Values outside the scale are clamped
+        assert_close(rubric.score(&application("APP-1", 80_000.0)).total, 0.0);
+    }
+
+    #[test]
+    fn a_missing_criterion_scores_zero_and_keeps_its_weight() {
+        let rubric = rubric(vec![
+            criterion("impact", 1.0),
+            criterion("feasibility", 1.0),
+        ]);
+        let full = application("FULL", 10_000.0);
+        let mut partial = application("PARTIAL", 10_000.0);
+        partial.ratings.remove("feasibility");
+        partial.ratings.insert("impact".to_string(), 5.0);
+
+        let full_card = rubric.score(&full);
+        let partial_card = rubric.score(&partial);
+        assert_close(full_card.total, 70.0);
+        assert_close(partial_card.total, 50.0);
+        assert_eq!(partial_card.missing, ["feasibility"]);
+        assert_eq!(partial_card.breakdown[0].weight, 0.5);
+
+        // Rated only on its strongest criterion, it still ranks below
+        let mut ranked = [("PARTIAL", partial_card.total), ("FULL", full_card.total)];
+        ranked.sort_by(|a, b| b.1.total_cmp(&a.1));
+        assert_eq!(ranked.map(|(id, _)| id), ["FULL", "PARTIAL"]);
+    }
+
+    #[test]
+    fn non_finite_ratings_count_as_missing() {
+        let rubric = rubric(vec![
+            criterion("impact", 1.0),
+            criterion("feasibility", 1.0),
+        ]);
+        let mut app = application("APP-1", 10_000.0);
+        app.ratings.insert("impact".to_string(), f64::NAN);
+
+        let card = rubric.score(&app);
+        assert_close(card.total, 30.0);
+        assert_eq!(card.missing, ["impact"]);
+    }
+
+    #[test]
+    fn reviewer_ratings_leave_field_criteria_out() {
+        let mut cost = criterion("cost", 3.0);
+        cost.field = Some("funding.amount".to_string());
+        let rubric = rubric(vec![
+            criterion("impact", 1.0),
+            cost,
+            criterion("reach", 1.0),
+        ]);
+
+        let mut ratings = BTreeMap::from([("impact".to_string(), 5.0)]);
+        ratings.insert("reach".to_string(), 2.5);
+        let card = rubric.score_ratings(&ratings);
+        assert_close(card.total, 75.0);
+        assert!(card.missing.is_empty());
+
+        ratings.remove("reach");
+        let card = rubric.score_ratings(&ratings);
+        assert_close(card.total, 50.0);
+        assert_eq!(card.missing, ["reach"]);
+    }
+
+    #[test]
+    fn zero_total_weight_scores_zero() {
+        let rubric = rubric(vec![criterion("impact", 0.0)]);
+        let card = rubric.score(&application("APP-1", 10_000.0));
+        assert_eq!(card.total, 0.0);
+        assert_eq!(card.breakdown[0].weight, 0.0);
+    }
+
+    #[test]
+    fn invalid_rubrics_name_the_offending_criterion() {
+        let mut inverted = criterion("reach", 1.0);
+        inverted.scale = Scale { min: 5.0, max: 1.0 };
+        let cases = [
+            (vec![criterion(" ", 1.0)], "rubric.criteria[0].id"),
+            (
+                vec![criterion("impact", 1.0), criterion("impact", 2.0)],
+                "rubric.criteria[1].id",
+            ),
+            (vec![criterion("impact", -1.0)], "rubric.criteria[0].weight"),
+            (
+                vec![criterion("impact", f64::NAN)],
+                "rubric.criteria[0].weight",
+            ),
+            (
+                vec![criterion("impact", 1.0), inverted],
+                "rubric.criteria[1].scale",
+            ),
+        ];
+
+        for (criteria, expected) in cases {
+            match rubric(criteria).validate() {
+                Err(GrantError::Validation { field, .. }) => assert_eq!(field, expected),
+                other => panic!("expected a validation error on {expected}, got {other:?}"),
+            }
+        }
+        assert!(rubric(vec![criterion("impact", 1.0)]).validate().is_ok());
+    }
+}
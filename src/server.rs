@@ -0,0 +1,900 @@
+// src/server.rs
+/*
+ * HTTP/JSON API exposing a processor on a local socket
+ */
+
+use crate::concurrent::lock;
+use crate::review::PanelScore;
+use crate::{
+    add_stored_candidates, allocator, load_panel, output, schema, sort_ranking, ApplicationStatus,
+    AuditLog, Config, GrantError, GrantProgramProcessor, JournalStore, MemoryStore,
+    ProgramDefinition, Result, Store, Strategy,
+};
+use log::{info, warn};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Address `serve` listens on when none is given
+pub const DEFAULT_LISTEN: &str = "127.0.0.1:8080";
+
+/// Largest request body accepted
+const MAX_BODY: usize = 16 * 1024 * 1024;
+
+/// Longest the request line or a header line may be, in bytes
+const MAX_LINE: usize = 8 * 1024;
+
+/// Most headers accepted in one request
+const MAX_HEADERS: usize = 100;
+
+/// How long a connection has to send its request and take the response
+const CONNECTION_DEADLINE: Duration = Duration::from_secs(30);
+
+/// Connections answered at the same time; more are refused with a 503
+const MAX_CONNECTIONS: usize = 64;
+
+/// A parsed HTTP request
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Request {
+    /// Request method, e.g. `GET`
+    pub method: String,
+    /// Path without the query string
+    pub path: String,
+    /// Decoded query parameters in the order given
+    pub query: Vec<(String, String)>,
+    /// Request body
+    pub body: String,
+}
+
+impl Request {
+    /// Build a request from a method, a target such as `/applications?status=awarded`
+    /// and a body
+    pub fn new(method: &str, target: &str, body: impl Into<String>) -> Self {
+        let (path, query) = target.split_once('?').unwrap_or((target, ""));
+        Self {
+            method: method.to_ascii_uppercase(),
+            path: path.to_string(),
+            query: query
+                .split('&')
+                .filter(|pair| !pair.is_empty())
+                .map(|pair| {
+                    let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+                    (percent_decode(key), percent_decode(value))
+                })
+                .collect(),
+            body: body.into(),
+        }
+    }
+
+    /// Value of the first query parameter named `key`
+    pub fn param(&self, key: &str) -> Option<&str> {
+        self.query
+            .iter()
+            .find(|(name, _)| name == key)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Read one request from a connection
+    ///
+    /// # Arguments
+    ///
+    /// * `reader` - Buffered connection
+    ///
+    /// # Returns
+    ///
+    /// The request, `None` if the connection closed before sending one, a
+    /// parse error for a malformed request, or a validation error for a line,
+    /// header count or body over the limits
+    pub fn read<R: BufRead>(reader: &mut R) -> Result<Option<Self>> {
+        let mut line = String::new();
+        if read_line(reader, &mut line, "request line")? == 0 {
+            return Ok(None);
+        }
+        let mut parts = line.split_whitespace();
+        let (Some(method), Some(target), Some(_version)) =
+            (parts.next(), parts.next(), parts.next())
+        else {
+            return Err(GrantError::parse(format!(
+                "malformed request line '{}'",
+                line.trim_end()
+            )));
+        };
+        let (method, target) = (method.to_string(), target.to_string());
+
+        let mut length = 0;
+        let mut headers = 0;
+        loop {
+            line.clear();
+            if read_line(reader, &mut line, "header")? == 0 || line.trim().is_empty() {
+                break;
+            }
+            headers += 1;
+            if headers > MAX_HEADERS {
+                return Err(GrantError::validation(
+                    "headers",
+                    format!("more than {} headers", MAX_HEADERS),
+                ));
+            }
+            let Some((name, value)) = line.split_once(':') else {
+                return Err(GrantError::parse(format!(
+                    "malformed header '{}'",
+                    line.trim_end()
+                )));
+            };
+            let value = value.trim();
+            if name.eq_ignore_ascii_case("content-length") {
+                length = value.parse().map_err(|_| {
+                    GrantError::parse(format!("invalid Content-Length '{}'", value))
+                })?;
+            } else if name.eq_ignore_ascii_case("transfer-encoding") {
+                return Err(GrantError::parse(
+                    "chunked request bodies are not supported; send a Content-Length",
+                ));
+            }
+        }
+        if length > MAX_BODY {
+            return Err(GrantError::validation(
+                "Content-Length",
+                format!(
+                    "body of {} bytes exceeds the {} byte limit",
+                    length, MAX_BODY
+                ),
+            ));
+        }
+
+        let mut body = vec![0; length];
+        reader.read_exact(&mut body)?;
+        let body = String::from_utf8(body)
+            .map_err(|_| GrantError::parse("request body is not valid UTF-8"))?;
+        Ok(Some(Self::new(&method, &target, body)))
+    }
+}
+
+/// An HTTP response carrying a JSON body
+#[derive(Debug, Clone, PartialEq)]
+pub struct Response {
+    /// HTTP status code
+    pub status: u16,
+    /// JSON body
+    pub body: serde_json::Value,
+}
+
+impl Response {
+    /// Response with `body` serialized as JSON
+    pub fn json<T: Serialize>(status: u16, body: &T) -> Result<Self> {
+        Ok(Self {
+            status,
+            body: serde_json::to_value(body).map_err(GrantError::output)?,
+        })
+    }
+
+    /// Error response with the given status
+    pub fn message(status: u16, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            body: serde_json::json!({ "error": message.into() }),
+        }
+    }
+
+    /// Error response for a failed request
+    ///
+    /// Malformed and invalid input is a 400, a refused status change a 409,
+    /// rules and allocations that cannot be applied a 422, and everything
+    /// else a 500.
+    pub fn error(error: &GrantError) -> Self {
+        let status = match error {
+            GrantError::Parse { .. } | GrantError::Validation { .. } => 400,
+            GrantError::Transition { .. } => 409,
+            GrantError::Rule { .. } | GrantError::Allocation { .. } => 422,
+            GrantError::Io { .. } | GrantError::Audit { .. } | GrantError::Output { .. } => 500,
+        };
+        let mut response = Self::message(status, error.to_string());
+        if let GrantError::Validation { field, .. } = error {
+            response.body["field"] = field.clone().into();
+        }
+        response
+    }
+
+    /// Write the response as HTTP/1.1, closing the connection afterwards
+    pub fn write_to<W: Write>(&self, writer: &mut W) -> Result<()> {
+        let body = serde_json::to_string(&self.body).map_err(GrantError::output)?;
+        write!(
+            writer,
+            "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            self.status,
+            reason(self.status),
+            body.len(),
+            body
+        )?;
+        writer.flush()?;
+        Ok(())
+    }
+}
+
+/// Serves a processor over HTTP
+///
+/// Every connection is read and answered on its own thread within a fixed
+/// deadline, so a slow client holds up no one else. The requests themselves
+/// are handled one at a time by a single processor; every submission is
+/// stored, audited and counted exactly as in a `run`. The endpoints are:
+///
+/// * `POST /applications` - submit one application (JSON body)
+/// * `GET /applications` - list stored applications, optionally `?status=`
+/// * `GET /applications/{id}` - one application with its lifecycle and award
+/// * `POST /score` - rank the applications under review
+/// * `POST /allocate` - allocate the pool, optionally `?strategy=`
+/// * `GET /stats` - what `GrantProgramProcessor::get_stats` returns
+#[derive(Debug)]
+pub struct Server {
+    listener: TcpListener,
+    service: Arc<Mutex<Service>>,
+}
+
+/// The processor and settings requests are answered from
+#[derive(Debug)]
+struct Service {
+    processor: GrantProgramProcessor,
+    panel_scores: HashMap<String, PanelScore>,
+    strategy: Strategy,
+}
+
+impl Server {
+    /// Bind a server to `addr` with the processor described by `config`
+    ///
+    /// Without a configured store, submissions are kept in memory for the
+    /// lifetime of the server.
+    ///
+    /// # Arguments
+    ///
+    /// * `addr` - Address to listen on; port 0 picks a free port
+    /// * `config` - Program, store, audit log, panel and strategy to use
+    ///
+    /// # Returns
+    ///
+    /// The bound server, or the error that prevented loading or binding
+    pub fn bind<A: ToSocketAddrs>(addr: A, config: &Config) -> Result<Self> {
+        let program = match &config.program {
+            Some(path) => Some(ProgramDefinition::load(path)?),
+            None => None,
+        };
+        let store: Box<dyn Store> = match &config.store {
+            Some(path) => Box::new(JournalStore::open(path)?),
+            None => Box::new(MemoryStore::new()),
+        };
+        let mut processor = GrantProgramProcessor::with_store(config.verbose, program, store)?;
+        if let Some(path) = &config.audit_log {
+            processor.set_audit_log(AuditLog::open(path)?);
+        }
+        let (_, panel_scores) = load_panel(config)?;
+
+        Ok(Self {
+            listener: TcpListener::bind(addr)?,
+            service: Arc::new(Mutex::new(Service {
+                processor,
+                panel_scores,
+                strategy: config.strategy,
+            })),
+        })
+    }
+
+    /// Address the server listens on
+    pub fn local_addr(&self) -> Result<SocketAddr> {
+        Ok(self.listener.local_addr()?)
+    }
+
+    /// Run `f` with the processor handling the requests
+    ///
+    /// Requests wait until `f` returns.
+    pub fn with_processor<R>(&self, f: impl FnOnce(&GrantProgramProcessor) -> R) -> R {
+        f(&lock(&self.service).processor)
+    }
+
+    /// Accept connections until the listener fails, answering each on its own thread
+    ///
+    /// A connection that fails is logged and dropped; the server keeps going.
+    /// Once `MAX_CONNECTIONS` are open, further connections are answered with
+    /// a 503 straight away.
+    pub fn serve(&self) -> Result<()> {
+        info!("Listening on http://{}", self.local_addr()?);
+        let open = Arc::new(AtomicUsize::new(0));
+        loop {
+            let (stream, peer) = self.listener.accept()?;
+            let Some(slot) = Slot::claim(&open) else {
+                warn!(
+                    "Refusing connection from {}: too many open connections",
+                    peer
+                );
+                refuse(stream);
+                continue;
+            };
+
+            let service = Arc::clone(&self.service);
+            let spawned = thread::Builder::new()
+                .name(format!("connection-{}", peer))
+                .spawn(move || {
+                    let _slot = slot;
+                    if let Err(error) = answer(&service, stream) {
+                        warn!("Connection from {} failed: {}", peer, error);
+                    }
+                });
+            if let Err(error) = spawned {
+                warn!("Connection from {} dropped: {}", peer, error);
+            }
+        }
+    }
+
+    /// Answer the request on one connection on the calling thread
+    pub fn handle_connection(&self, stream: TcpStream) -> Result<()> {
+        answer(&self.service, stream)
+    }
+
+    /// Answer a single request
+    pub fn handle(&self, request: &Request) -> Response {
+        lock(&self.service).handle(request)
+    }
+}
+
+impl Service {
+    /// Answer a single request
+    fn handle(&mut self, request: &Request) -> Response {
+        let segments: Vec<&str> = request
+            .path
+            .split('/')
+            .filter(|segment| !segment.is_empty())
+            .collect();
+        let result = match (request.method.as_str(), segments.as_slice()) {
+            ("POST", ["applications"]) => self.submit(request),
+            ("GET", ["applications"]) => self.list(request),
+            ("GET", ["applications", id]) => self.application(&percent_decode(id)),
+            ("POST", ["score"]) => self.score(),
+            ("POST", ["allocate"]) => self.allocate(request),
+            ("GET", ["stats"]) => Response::json(200, &self.processor.get_stats()),
+            (_, ["applications"] | ["applications", _] | ["score"] | ["allocate"] | ["stats"]) => {
+                Ok(Response::message(
+                    405,
+                    format!("{} is not allowed on {}", request.method, request.path),
+                ))
+            }
+            _ => Ok(Response::message(
+                404,
+                format!("no endpoint at {}", request.path),
+            )),
+        };
+        result.unwrap_or_else(|error| Response::error(&error))
+    }
+
+    /// `POST /applications`
+    fn submit(&mut self, request: &Request) -> Result<Response> {
+        let application = schema::parse_application(serde_json::from_str(&request.body)?)
+            .map_err(|violations| schema::violation_error(&violations))?;
+        let accepted = self
+            .processor
+            .lifecycle(&application.id)
+            .is_none_or(|lifecycle| {
+                lifecycle
+                    .status
+                    .can_transition_to(ApplicationStatus::Submitted)
+            });
+        let result = self.processor.process_application(&application)?;
+        Response::json(if accepted { 201 } else { 409 }, &result)
+    }
+
+    /// `GET /applications`
+    fn list(&self, request: &Request) -> Result<Response> {
+        let status = request
+            .param("status")
+            .map(|status| parse_param::<ApplicationStatus>("status", status))
+            .transpose()?;
+        let mut standings = output::standings(self.store())?;
+        if let Some(status) = status {
+            standings.retain(|standing| standing.status == Some(status));
+        }
+        Response::json(200, &standings)
+    }
+
+    /// `GET /applications/{id}`
+    fn application(&self, id: &str) -> Result<Response> {
+        let store = self.store();
+        let Some(application) = store.application(id)? else {
+            return Ok(Response::message(
+                404,
+                format!("unknown application '{}'", id),
+            ));
+        };
+        let award = store
+            .awards()?
+            .into_iter()
+            .find(|award| award.application_id == id);
+        Response::json(
+            200,
+            &serde_json::json!({
+                "application": application,
+                "lifecycle": self.processor.lifecycle(id),
+                "award": award
+            }),
+        )
+    }
+
+    /// `POST /score`
+    fn score(&self) -> Result<Response> {
+        Response::json(200, &self.ranking()?)
+    }
+
+    /// `POST /allocate`
+    fn allocate(&mut self, request: &Request) -> Result<Response> {
+        let strategy = match request.param("strategy") {
+            Some(strategy) => parse_param("strategy", strategy)?,
+            None => self.strategy,
+        };
+        let candidates = self.ranking()?;
+        let Some(program) = self.processor.program.as_ref() else {
+            return Err(GrantError::allocation(
+                "no program is configured, so there is no pool to allocate",
+            ));
+        };
+        let allocation =
+            allocator::allocate(program.total_pool, &candidates, strategy, program.min_award)?;
+        info!(
+            "Allocated {:.2} of {:.2} using {} strategy, {:.2} remaining",
+            allocation.total_awarded, allocation.pool, allocation.strategy, allocation.remainder
+        );
+        self.processor.apply_allocation(&allocation)?;
+        Response::json(200, &allocation)
+    }
+
+    /// Every application under review, best first
+    fn ranking(&self) -> Result<Vec<allocator::ScoredApplication>> {
+        let mut candidates = Vec::new();
+        add_stored_candidates(&self.processor, &self.panel_scores, &mut candidates)?;
+        sort_ranking(&mut candidates);
+        Ok(candidates)
+    }
+
+    fn store(&self) -> &dyn Store {
+        self.processor
+            .store()
+            .expect("the server's processor always has a store")
+    }
+}
+
+/// Read the request on a connection and write the response
+///
+/// The client has `CONNECTION_DEADLINE` to send its request, and as long
+/// again to take the response once it is ready. A connection that fails or
+/// runs out of time while sending its request is dropped without an answer.
+fn answer(service: &Mutex<Service>, stream: TcpStream) -> Result<()> {
+    let mut reader = BufReader::new(Deadline::new(stream.try_clone()?));
+    let response = match Request::read(&mut reader) {
+        Ok(Some(request)) => {
+            let response = lock(service).handle(&request);
+            info!("{} {} {}", request.method, request.path, response.status);
+            response
+        }
+        Ok(None) => return Ok(()),
+        Err(error @ GrantError::Io { .. }) => return Err(error),
+        Err(error) => Response::error(&error),
+    };
+    response.write_to(&mut Deadline::new(stream))
+}
+
+/// Answer a connection over the limit with a 503, giving up quickly
+fn refuse(mut stream: TcpStream) {
+    let response = Response::message(503, "too many open connections; try again later");
+    let _ = stream.set_write_timeout(Some(Duration::from_secs(1)));
+    let _ = response.write_to(&mut stream);
+}
+
+/// A place among the open connections, given back when dropped
+struct Slot(Arc<AtomicUsize>);
+
+impl Slot {
+    /// Take a place, or `None` when `MAX_CONNECTIONS` are already open
+    fn claim(open: &Arc<AtomicUsize>) -> Option<Self> {
+        let slot = Self(Arc::clone(open));
+        (open.fetch_add(1, Ordering::SeqCst) < MAX_CONNECTIONS).then_some(slot)
+    }
+}
+
+impl Drop for Slot {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// A connection whose reads and writes fail once its deadline has passed
+struct Deadline {
+    stream: TcpStream,
+    until: Instant,
+}
+
+impl Deadline {
+    /// Give a connection `CONNECTION_DEADLINE` from now
+    fn new(stream: TcpStream) -> Self {
+        Self {
+            stream,
+            until: Instant::now() + CONNECTION_DEADLINE,
+        }
+    }
+
+    fn remaining(&self) -> io::Result<Duration> {
+        let remaining = self.until.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                "connection deadline passed",
+            ));
+        }
+        Ok(remaining)
+    }
+}
+
+impl Read for Deadline {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.stream.set_read_timeout(Some(self.remaining()?))?;
+        self.stream.read(buf)
+    }
+}
+
+impl Write for Deadline {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.stream.set_write_timeout(Some(self.remaining()?))?;
+        self.stream.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.stream.flush()
+    }
+}
+
+/// Read one line of the request head, refusing lines over `MAX_LINE` bytes
+fn read_line<R: BufRead>(reader: &mut R, line: &mut String, field: &str) -> Result<usize> {
+    let read = reader.take(MAX_LINE as u64 + 1).read_line(line)?;
+    if line.len() > MAX_LINE {
+        return Err(GrantError::validation(
+            field,
+            format!("line exceeds the {} byte limit", MAX_LINE),
+        ));
+    }
+    Ok(read)
+}
+
+/// Parse a query parameter the way the same value is parsed in a config file
+fn parse_param<T: DeserializeOwned>(name: &str, value: &str) -> Result<T> {
+    serde_json::from_value(serde_json::Value::String(value.to_string()))
+        .map_err(|e| GrantError::validation(name, e.to_string()))
+}
+
+/// Decode `%XX` escapes and `+` in a URL component
+fn percent_decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = bytes
+            .get(i + 1..i + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (bytes[i], escaped) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 2;
+            }
+            (b'+', _) => decoded.push(b' '),
+            (byte, _) => decoded.push(byte),
+        }
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// Reason phrase of the status codes the server sends
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        201 => "Created",
+        400 => "Bad Request",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        409 => "Conflict",
+        422 => "Unprocessable Entity",
+        503 => "Service Unavailable",
+        _ => "Internal Server Error",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{application, program};
+    use std::io::Cursor;
+    use std::path::Path;
+
+    /// Server on a free localhost port for program `P1` with a 100000 pool
+    fn server(dir: &Path) -> Server {
+        let path = dir.join("program.json");
+        std::fs::write(&path, serde_json::to_string(&program(100_000.0)).unwrap()).unwrap();
+        let config = Config {
+            program: Some(path.to_string_lossy().into_owned()),
+            ..Config::default()
+        };
+        Server::bind("127.0.0.1:0", &config).unwrap()
+    }
+
+    fn submit(server: &Server, id: &str, amount: f64) -> Response {
+        let body = serde_json::to_string(&application(id, amount)).unwrap();
+        server.handle(&Request::new("POST", "/applications", body))
+    }
+
+    fn read(raw: &str) -> Result<Option<Request>> {
+        Request::read(&mut Cursor::new(raw.as_bytes().to_vec()))
+    }
+
+    /// Send raw bytes to a running server and return the whole response
+    fn exchange(addr: SocketAddr, raw: &str) -> String {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        stream
+            .set_read_timeout(Some(Duration::from_secs(10)))
+            .unwrap();
+        stream.write_all(raw.as_bytes()).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        response
+    }
+
+    #[test]
+    fn requests_are_parsed_with_query_and_body() {
+        let request = read(
+            "post /applications?status=under_review&q=a%20b+c HTTP/1.1\r\n\
+             Host: localhost\r\nContent-Length: 2\r\n\r\n{}",
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(request.method, "POST");
+        assert_eq!(request.path, "/applications");
+        assert_eq!(request.param("status"), Some("under_review"));
+        assert_eq!(request.param("q"), Some("a b c"));
+        assert_eq!(request.param("missing"), None);
+        assert_eq!(request.body, "{}");
+        assert_eq!(read("").unwrap(), None);
+    }
+
+    #[test]
+    fn malformed_and_oversized_requests_are_refused() {
+        assert!(matches!(
+            read("GET /\r\n\r\n"),
+            Err(GrantError::Parse { .. })
+        ));
+        assert!(matches!(
+            read("GET / HTTP/1.1\r\nno colon\r\n\r\n"),
+            Err(GrantError::Parse { .. })
+        ));
+        assert!(matches!(
+            read("POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n"),
+            Err(GrantError::Parse { .. })
+        ));
+
+        let long_target = format!("GET /{} HTTP/1.1\r\n\r\n", "a".repeat(MAX_LINE));
+        let long_header = format!("GET / HTTP/1.1\r\nX-Long: {}\r\n\r\n", "a".repeat(MAX_LINE));
+        let many_headers = format!(
+            "GET / HTTP/1.1\r\n{}\r\n",
+            "X-Header: 1\r\n".repeat(MAX_HEADERS + 1)
+        );
+        let huge_body = format!(
+            "POST / HTTP/1.1\r\nContent-Length: {}\r\n\r\n",
+            MAX_BODY + 1
+        );
+        for (raw, expected) in [
+            (long_target, "request line"),
+            (long_header, "header"),
+            (many_headers, "headers"),
+            (huge_body, "Content-Length"),
+        ] {
+            match read(&raw) {
+                Err(GrantError::Validation { field, .. }) => assert_eq!(field, expected),
+                other => panic!("expected a validation error, got {other:?}"),
+            }
+        }
+    }
+
+    #[test]
+    fn submissions_are_created_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let server = server(dir.path());
+
+        let created = submit(&server, "A1", 10_000.0);
+        assert_eq!(created.status, 201, "{}", created.body);
+        assert_eq!(created.body["success"], true);
+        let repeated = submit(&server, "A1", 10_000.0);
+        assert_eq!(repeated.status, 409, "{}", repeated.body);
+        assert_eq!(
+            server.with_processor(|processor| processor.processed_count),
+            1
+        );
+    }
+
+    #[test]
+    fn invalid_submissions_name_the_field() {
+        let dir = tempfile::tempdir().unwrap();
+        let server = server(dir.path());
+
+        let mut document = serde_json::to_value(application("A1", 10_000.0)).unwrap();
+        document["funding"]["amount"] = serde_json::json!(-1);
+        let response = server.handle(&Request::new("POST", "/applications", document.to_string()));
+        assert_eq!(response.status, 400);
+        assert_eq!(response.body["field"], "funding.amount");
+
+        let response = server.handle(&Request::new("POST", "/applications", "{"));
+        assert_eq!(response.status, 400);
+    }
+
+    #[test]
+    fn applications_can_be_fetched_and_listed_by_status() {
+        let dir = tempfile::tempdir().unwrap();
+        let server = server(dir.path());
+        submit(&server, "A1", 10_000.0);
+        submit(&server, "A 2", 20_000.0);
+
+        let response = server.handle(&Request::new("GET", "/applications/A%202", ""));
+        assert_eq!(response.status, 200);
+        assert_eq!(response.body["application"]["id"], "A 2");
+        assert_eq!(response.body["lifecycle"]["status"], "under_review");
+        assert!(response.body["award"].is_null());
+
+        let missing = server.handle(&Request::new("GET", "/applications/nope", ""));
+        assert_eq!(missing.status, 404);
+
+        let listed = server.handle(&Request::new(
+            "GET",
+            "/applications?status=under_review",
+            "",
+        ));
+        assert_eq!(listed.status, 200);
+        assert_eq!(listed.body.as_array().unwrap().len(), 2);
+        let none = server.handle(&Request::new("GET", "/applications?status=awarded", ""));
+        assert_eq!(none.body, serde_json::json!([]));
+        let bad = server.handle(&Request::new("GET", "/applications?status=lost", ""));
+        assert_eq!(bad.status, 400);
+        assert_eq!(bad.body["field"], "status");
+    }
+
+    #[test]
+    fn scoring_and_allocation_award_the_pool() {
+        let dir = tempfile::tempdir().unwrap();
+        let server = server(dir.path());
+        submit(&server, "A1", 30_000.0);
+        submit(&server, "A2", 40_000.0);
+
+        let ranking = server.handle(&Request::new("POST", "/score", ""));
+        assert_eq!(ranking.status, 200);
+        assert_eq!(ranking.body.as_array().unwrap().len(), 2);
+
+        let allocation = server.handle(&Request::new("POST", "/allocate?strategy=greedy", ""));
+        assert_eq!(allocation.status, 200, "{}", allocation.body);
+        assert_eq!(allocation.body["strategy"], "greedy");
+        assert_eq!(allocation.body["total_awarded"], 70_000.0);
+
+        let awarded = server.handle(&Request::new("GET", "/applications?status=awarded", ""));
+        assert_eq!(awarded.body.as_array().unwrap().len(), 2);
+        let bad = server.handle(&Request::new("POST", "/allocate?strategy=lottery", ""));
+        assert_eq!(bad.status, 400);
+    }
+
+    #[test]
+    fn allocating_without_a_program_is_unprocessable() {
+        let server = Server::bind("127.0.0.1:0", &Config::default()).unwrap();
+        let response = server.handle(&Request::new("POST", "/allocate", ""));
+        assert_eq!(response.status, 422);
+    }
+
+    #[test]
+    fn stats_report_what_the_processor_reports() {
+        let dir = tempfile::tempdir().unwrap();
+        let server = server(dir.path());
+        submit(&server, "A1", 10_000.0);
+
+        let response = server.handle(&Request::new("GET", "/stats", ""));
+        assert_eq!(response.status, 200);
+        assert_eq!(response.body["processed_count"], 1);
+        assert_eq!(
+            response.body,
+            server.with_processor(GrantProgramProcessor::get_stats)
+        );
+    }
+
+    #[test]
+    fn unknown_routes_and_methods_are_refused() {
+        let server = Server::bind("127.0.0.1:0", &Config::default()).unwrap();
+        assert_eq!(
+            server.handle(&Request::new("GET", "/nowhere", "")).status,
+            404
+        );
+        assert_eq!(
+            server.handle(&Request::new("DELETE", "/stats", "")).status,
+            405
+        );
+        assert_eq!(
+            server.handle(&Request::new("GET", "/score", "")).status,
+            405
+        );
+    }
+
+    #[test]
+    fn the_api_answers_over_localhost() {
+        let dir = tempfile::tempdir().unwrap();
+        let server = Arc::new(server(dir.path()));
+        let addr = server.local_addr().unwrap();
+        thread::spawn({
+            let server = Arc::clone(&server);
+            move || server.serve()
+        });
+
+        let body = serde_json::to_string(&application("A1", 10_000.0)).unwrap();
+        let response = exchange(
+            addr,
+            &format!(
+                "POST /applications HTTP/1.1\r\nContent-Length: {}\r\n\r\n{}",
+                body.len(),
+                body
+            ),
+        );
+        assert!(
+            response.starts_with("HTTP/1.1 201 Created\r\n"),
+            "{response}"
+        );
+        assert!(response.contains("Content-Type: application/json"));
+
+        let response = exchange(addr, "GET /stats HTTP/1.1\r\n\r\n");
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{response}");
+        let (_, body) = response.split_once("\r\n\r\n").unwrap();
+        let stats: serde_json::Value = serde_json::from_str(body).unwrap();
+        assert_eq!(stats["processed_count"], 1);
+
+        let response = exchange(
+            addr,
+            &format!("GET / HTTP/1.1\r\nX-Long: {}\r\n\r\n", "a".repeat(MAX_LINE)),
+        );
+        assert!(
+            response.starts_with("HTTP/1.1 400 Bad Request\r\n"),
+            "{response}"
+        );
+    }
+
+    #[test]
+    fn an_idle_client_does_not_hold_up_others() {
+        let dir = tempfile::tempdir().unwrap();
+        let server = Arc::new(server(dir.path()));
+        let addr = server.local_addr().unwrap();
+        thread::spawn({
+            let server = Arc::clone(&server);
+            move || server.serve()
+        });
+
+        let mut idle = TcpStream::connect(addr).unwrap();
+        idle.write_all(b"GET /stats HTTP/1.1\r\n").unwrap();
+
+        let started = Instant::now();
+        let response = exchange(addr, "GET /stats HTTP/1.1\r\n\r\n");
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\n"), "{response}");
+        assert!(started.elapsed() < Duration::from_secs(5));
+        drop(idle);
+    }
+
+    #[test]
+    fn connections_give_back_their_slot() {
+        let open = Arc::new(AtomicUsize::new(0));
+        let slots: Vec<Slot> = (0..MAX_CONNECTIONS)
+            .map(|_| Slot::claim(&open).unwrap())
+            .collect();
+        assert!(Slot::claim(&open).is_none());
+        assert_eq!(open.load(Ordering::SeqCst), MAX_CONNECTIONS);
+        drop(slots);
+        assert_eq!(open.load(Ordering::SeqCst), 0);
+        assert!(Slot::claim(&open).is_some());
+    }
+}
@@ -0,0 +1,554 @@
+// src/stats.rs
+/*
+ * Statistics over the applications a processor has seen
+ */
+
+use crate::allocator::Award;
+use crate::store::Store;
+use crate::{
+    ApplicationStatus, Evaluation, GrantApplication, Lifecycle, ProgramDefinition, Result,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Width of one score histogram bucket, in points out of 100
+const SCORE_BUCKET_WIDTH: usize = 10;
+
+/// Latency percentiles reported
+const PERCENTILES: [f64; 3] = [50.0, 90.0, 99.0];
+
+/// Everything `get_stats` reports about a processor
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProcessorStats {
+    /// Number of applications processed over the processor's lifetime
+    pub processed_count: usize,
+    /// Whether the processor runs in verbose mode
+    pub verbose: bool,
+    /// Applications in each status
+    pub statuses: BTreeMap<String, usize>,
+    /// Figures for each program applied to, keyed by program id
+    pub programs: BTreeMap<String, ProgramStats>,
+    /// Share of decided applications that passed validation and every rule
+    pub acceptance_rate: f64,
+    /// Share of decided applications declined when evaluated
+    pub rejection_rate: f64,
+    /// Number of applications each eligibility rule failed, keyed by rule id
+    pub failed_rules: BTreeMap<String, usize>,
+    /// Money asked for and granted across all programs
+    pub funding: FundingTotals,
+    /// Distribution of rubric scores
+    pub scores: ScoreDistribution,
+    /// Time taken to validate, check and score each application
+    pub latency: Latency,
+}
+
+/// Figures for the applications to one program
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ProgramStats {
+    /// Applications received
+    pub applications: usize,
+    /// Applications that passed validation and every rule
+    pub accepted: usize,
+    /// Applications declined when evaluated
+    pub rejected: usize,
+    /// Applications in each status
+    pub statuses: BTreeMap<String, usize>,
+    /// Money asked for and granted
+    pub funding: FundingTotals,
+}
+
+/// Money asked for and granted
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct FundingTotals {
+    /// Sum of the amounts requested
+    pub requested: f64,
+    /// Sum of the amounts awarded
+    pub awarded: f64,
+}
+
+/// Distribution of rubric scores
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ScoreDistribution {
+    /// Applications scored
+    pub count: usize,
+    /// Lowest score, if any application was scored
+    pub min: Option<f64>,
+    /// Highest score, if any application was scored
+    pub max: Option<f64>,
+    /// Mean score, if any application was scored
+    pub mean: Option<f64>,
+    /// Applications per 10 point range, keyed like `"70-80"`; the last range includes 100
+    pub histogram: BTreeMap<String, usize>,
+}
+
+/// Percentiles of the time taken to evaluate an application, in milliseconds
+///
+/// Each application counts once, with its latest evaluation. Applications
+/// reloaded from a store are evaluated again, and those runs are timed.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Latency {
+    /// Applications timed
+    pub samples: usize,
+    /// Median
+    pub p50_ms: Option<f64>,
+    /// 90th percentile
+    pub p90_ms: Option<f64>,
+    /// 99th percentile
+    pub p99_ms: Option<f64>,
+    /// Slowest evaluation
+    pub max_ms: Option<f64>,
+}
+
+/// What an evaluation found about one application
+#[derive(Debug, Clone)]
+struct Facts {
+    program_id: String,
+    requested: f64,
+    score: Option<f64>,
+    failed_rules: Vec<String>,
+    /// Milliseconds the evaluation took
+    elapsed_ms: f64,
+}
+
+/// Running record a processor keeps to build its statistics
+///
+/// Lifecycles are not copied here; the processor hands them over when the
+/// statistics are built.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Tally {
+    /// Latest evaluation of every application, keyed by application id
+    applications: BTreeMap<String, Facts>,
+    /// Amount awarded to every application allocated, keyed by application id
+    awards: BTreeMap<String, f64>,
+}
+
+impl Tally {
+    /// Rebuild the tally for everything in a store
+    ///
+    /// Stored applications are scored and checked again against `program`,
+    /// which changes nothing in the store.
+    ///
+    /// # Arguments
+    ///
+    /// * `program` - Program definition applications are checked against
+    /// * `store` - Store to read applications and awards from
+    ///
+    /// # Returns
+    ///
+    /// The tally, or the store's error if it cannot be read
+    pub(crate) fn load(program: Option<&ProgramDefinition>, store: &dyn Store) -> Result<Self> {
+        let mut tally = Self::default();
+        for application in store.applications()? {
+            tally.record(&application, &Evaluation::new(program, &application));
+        }
+        for award in store.awards()? {
+            tally.record_award(&award);
+        }
+        Ok(tally)
+    }
+
+    /// Record the evaluation of an application, replacing any earlier one
+    pub(crate) fn record(&mut self, application: &GrantApplication, evaluation: &Evaluation) {
+        let facts = Facts {
+            program_id: application.program.id.clone(),
+            requested: application.funding.amount,
+            score: evaluation.score.as_ref().map(|score| score.total),
+            failed_rules: evaluation
+                .outcomes
+                .iter()
+                .filter(|outcome| outcome.is_failure())
+                .map(|outcome| outcome.rule_id.clone())
+                .collect(),
+            elapsed_ms: evaluation.elapsed.as_secs_f64() * 1000.0,
+        };
+        self.applications.insert(application.id.clone(), facts);
+    }
+
+    /// Record an award, replacing any earlier one to the same application
+    pub(crate) fn record_award(&mut self, award: &Award) {
+        self.awards
+            .insert(award.application_id.clone(), award.awarded);
+    }
+
+    /// Build the statistics
+    ///
+    /// # Arguments
+    ///
+    /// * `processed_count` - Applications processed so far
+    /// * `verbose` - Whether the processor runs in verbose mode
+    /// * `lifecycles` - Lifecycle of every application seen
+    ///
+    /// # Returns
+    ///
+    /// The processor's statistics
+    pub(crate) fn stats<'a>(
+        &self,
+        processed_count: usize,
+        verbose: bool,
+        lifecycles: impl IntoIterator<Item = &'a Lifecycle>,
+    ) -> ProcessorStats {
+        let mut programs: BTreeMap<String, ProgramStats> = BTreeMap::new();
+        let mut failed_rules: BTreeMap<String, usize> = BTreeMap::new();
+        let mut funding = FundingTotals::default();
+        let mut scores = Vec::new();
+        let mut latencies = Vec::new();
+        for facts in self.applications.values() {
+            let program = programs.entry(facts.program_id.clone()).or_default();
+            program.applications += 1;
+            program.funding.requested += facts.requested;
+            funding.requested += facts.requested;
+            for rule_id in &facts.failed_rules {
+                *failed_rules.entry(rule_id.clone()).or_default() += 1;
+            }
+            scores.extend(facts.score);
+            latencies.push(facts.elapsed_ms);
+        }
+        for (application_id, awarded) in &self.awards {
+            funding.awarded += awarded;
+            if let Some(facts) = self.applications.get(application_id) {
+                if let Some(program) = programs.get_mut(&facts.program_id) {
+                    program.funding.awarded += awarded;
+                }
+            }
+        }
+
+        let mut statuses: BTreeMap<String, usize> = BTreeMap::new();
+        let (mut accepted, mut rejected) = (0, 0);
+        for lifecycle in lifecycles {
+            // The latest submission decides whether the application counts as accepted
+            let latest = decisions(lifecycle).last();
+            match latest {
+                Some(true) => accepted += 1,
+                Some(false) => rejected += 1,
+                None => {}
+            }
+
+            let status = lifecycle.status.to_string();
+            *statuses.entry(status.clone()).or_default() += 1;
+            let program = self
+                .applications
+                .get(&lifecycle.application_id)
+                .and_then(|facts| programs.get_mut(&facts.program_id));
+            if let Some(program) = program {
+                *program.statuses.entry(status).or_default() += 1;
+                match latest {
+                    Some(true) => program.accepted += 1,
+                    Some(false) => program.rejected += 1,
+                    None => {}
+                }
+            }
+        }
+
+        let decided = accepted + rejected;
+        let rate = |count: usize| match decided {
+            0 => 0.0,
+            decided => count as f64 / decided as f64,
+        };
+        ProcessorStats {
+            processed_count,
+            verbose,
+            statuses,
+            programs,
+            acceptance_rate: rate(accepted),
+            rejection_rate: rate(rejected),
+            failed_rules,
+            funding,
+            scores: ScoreDistribution::new(&scores),
+            latency: Latency::new(latencies),
+        }
+    }
+}
+
+impl ScoreDistribution {
+    /// Summarise scores out of 100
+    fn new(scores: &[f64]) -> Self {
+        let mut histogram: BTreeMap<String, usize> = (0..100)
+            .step_by(SCORE_BUCKET_WIDTH)
+            .map(|from| (bucket_label(from), 0))
+            .collect();
+        for score in scores {
+            let bucket = (score.clamp(0.0, 100.0) as usize / SCORE_BUCKET_WIDTH)
+                .min(100 / SCORE_BUCKET_WIDTH - 1);
+            *histogram
+                .entry(bucket_label(bucket * SCORE_BUCKET_WIDTH))
+                .or_default() += 1;
+        }
+        Self {
+            count: scores.len(),
+            min: scores.iter().copied().reduce(f64::min),
+            max: scores.iter().copied().reduce(f64::max),
+            mean: (!scores.is_empty()).then(|| scores.iter().sum::<f64>() / scores.len() as f64),
+            histogram,
+        }
+    }
+}
+
+impl Latency {
+    /// Summarise evaluation times in milliseconds
+    fn new(mut samples: Vec<f64>) -> Self {
+        samples.sort_by(f64::total_cmp);
+        let [p50_ms, p90_ms, p99_ms] = PERCENTILES.map(|percentile| {
+            // Nearest-rank percentile
+            let rank = (percentile / 100.0 * samples.len() as f64).ceil() as usize;
+            samples.get(rank.max(1) - 1).copied()
+        });
+        Self {
+            samples: samples.len(),
+            p50_ms,
+            p90_ms,
+            p99_ms,
+            max_ms: samples.last().copied(),
+        }
+    }
+}
+
+fn bucket_label(from: usize) -> String {
+    format!("{}-{}", from, from + SCORE_BUCKET_WIDTH)
+}
+
+/// Every eligibility decision in a lifecycle, in order
+///
+/// Each is whether the application passed the checks made on a submission.
+fn decisions(lifecycle: &Lifecycle) -> impl Iterator<Item = bool> + '_ {
+    lifecycle.history.windows(2).filter_map(|pair| {
+        let (received, decided) = (&pair[0], &pair[1]);
+        let passed = match decided.to {
+            ApplicationStatus::UnderReview => true,
+            ApplicationStatus::Declined => false,
+            _ => return None,
+        };
+        (received.to == ApplicationStatus::Submitted
+            && decided.from == Some(ApplicationStatus::Submitted))
+        .then_some(passed)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rules::{Condition, Rule};
+    use crate::store::JournalStore;
+    use crate::test_support::{application, program};
+    use crate::{GrantProgramProcessor, SharedGrantProcessor, StatusChange};
+    use chrono::{TimeDelta, Utc};
+    use std::time::Duration;
+
+    /// Program `P1` whose only rule caps requests at 20000
+    fn capped_program() -> ProgramDefinition {
+        ProgramDefinition {
+            rules: vec![Rule {
+                id: "cap".to_string(),
+                description: String::new(),
+                check: Condition::parse("requested_amount <= 20000").unwrap(),
+                when: None,
+            }],
+            ..program(100_000.0)
+        }
+    }
+
+    /// Lifecycle submitted and put under review `seconds` apart
+    fn reviewed(application_id: &str, seconds: i64) -> Lifecycle {
+        let submitted = Utc::now();
+        let change = |from, to, at| StatusChange {
+            from,
+            to,
+            at,
+            reason: String::new(),
+        };
+        Lifecycle {
+            application_id: application_id.to_string(),
+            status: ApplicationStatus::UnderReview,
+            history: vec![
+                change(
+                    Some(ApplicationStatus::Draft),
+                    ApplicationStatus::Submitted,
+                    submitted,
+                ),
+                change(
+                    Some(ApplicationStatus::Submitted),
+                    ApplicationStatus::UnderReview,
+                    submitted + TimeDelta::seconds(seconds),
+                ),
+            ],
+        }
+    }
+
+    #[test]
+    fn latency_comes_from_the_timed_evaluations() {
+        let mut tally = Tally::default();
+        let mut lifecycles = Vec::new();
+        for milliseconds in 1..=10 {
+            let id = format!("APP-{:02}", milliseconds);
+            let application = application(&id, 10_000.0);
+            let mut evaluation = Evaluation::new(None, &application);
+            evaluation.elapsed = Duration::from_millis(milliseconds);
+            tally.record(&application, &evaluation);
+            // Lifecycle timestamps are minutes apart and must not be used
+            lifecycles.push(reviewed(&id, 600));
+        }
+
+        let latency = tally.stats(10, false, &lifecycles).latency;
+        assert_eq!(
+            latency,
+            Latency {
+                samples: 10,
+                p50_ms: Some(5.0),
+                p90_ms: Some(9.0),
+                p99_ms: Some(10.0),
+                max_ms: Some(10.0),
+            }
+        );
+    }
+
+    #[test]
+    fn a_re_evaluation_replaces_the_earlier_timing() {
+        let mut tally = Tally::default();
+        let application = application("A1", 10_000.0);
+        for milliseconds in [40, 2] {
+            let mut evaluation = Evaluation::new(None, &application);
+            evaluation.elapsed = Duration::from_millis(milliseconds);
+            tally.record(&application, &evaluation);
+        }
+
+        let latency = tally.stats(1, false, &[]).latency;
+        assert_eq!(latency.samples, 1);
+        assert_eq!(latency.max_ms, Some(2.0));
+    }
+
+    #[test]
+    fn percentiles_use_the_nearest_rank() {
+        let latency = Latency::new(vec![3.0, 1.0, 2.0]);
+        assert_eq!(latency.samples, 3);
+        assert_eq!(latency.p50_ms, Some(2.0));
+        assert_eq!(latency.p90_ms, Some(3.0));
+        assert_eq!(latency.max_ms, Some(3.0));
+
+        let empty = Latency::new(Vec::new());
+        assert_eq!(empty.samples, 0);
+        assert_eq!(empty.p50_ms, None);
+        assert_eq!(empty.max_ms, None);
+    }
+
+    #[test]
+    fn scores_fall_into_ten_point_buckets() {
+        let scores = ScoreDistribution::new(&[0.0, 9.99, 10.0, 55.0, 100.0, 120.0, -5.0]);
+        assert_eq!(scores.count, 7);
+        assert_eq!(scores.min, Some(-5.0));
+        assert_eq!(scores.max, Some(120.0));
+        assert_eq!(scores.histogram.len(), 10);
+        assert_eq!(scores.histogram["0-10"], 3);
+        assert_eq!(scores.histogram["10-20"], 1);
+        assert_eq!(scores.histogram["50-60"], 1);
+        assert_eq!(scores.histogram["90-100"], 2);
+        assert_eq!(scores.histogram["20-30"], 0);
+
+        let none = ScoreDistribution::new(&[]);
+        assert_eq!((none.count, none.mean), (0, None));
+        assert_eq!(none.histogram.values().sum::<usize>(), 0);
+    }
+
+    #[test]
+    fn processors_report_statuses_rates_rules_and_funding() {
+        let mut processor = GrantProgramProcessor::new(false, Some(capped_program()));
+        for (id, amount) in [("A1", 10_000.0), ("A2", 30_000.0), ("A1", 10_000.0)] {
+            processor
+                .process_application(&application(id, amount))
+                .unwrap();
+        }
+
+        let stats = processor.stats();
+        assert_eq!(stats.processed_count, 2);
+        assert_eq!(stats.statuses["under_review"], 1);
+        assert_eq!(stats.statuses["declined"], 1);
+        assert_eq!((stats.acceptance_rate, stats.rejection_rate), (0.5, 0.5));
+        assert_eq!(stats.failed_rules["cap"], 1);
+        assert_eq!(stats.funding.requested, 40_000.0);
+        assert_eq!(stats.funding.awarded, 0.0);
+        let program = &stats.programs["P1"];
+        assert_eq!(
+            (program.applications, program.accepted, program.rejected),
+            (2, 1, 1)
+        );
+        assert_eq!(stats.latency.samples, 2);
+        assert!(stats.latency.max_ms.unwrap() >= 0.0);
+    }
+
+    #[test]
+    fn both_processors_time_the_same_evaluations() {
+        let mut single = GrantProgramProcessor::new(false, Some(capped_program()));
+        let shared = SharedGrantProcessor::new(false, Some(capped_program()));
+        for (id, amount) in [("A1", 10_000.0), ("A2", 30_000.0)] {
+            single
+                .process_application(&application(id, amount))
+                .unwrap();
+            shared
+                .process_application(&application(id, amount))
+                .unwrap();
+        }
+
+        let (single, shared) = (single.stats(), shared.stats());
+        assert_eq!(single.latency.samples, shared.latency.samples);
+        assert_eq!(
+            ProcessorStats {
+                latency: shared.latency.clone(),
+                ..single
+            },
+            shared
+        );
+    }
+
+    #[test]
+    fn awards_count_towards_the_awarded_totals() {
+        let mut tally = Tally::default();
+        let application = application("A1", 10_000.0);
+        tally.record(&application, &Evaluation::new(None, &application));
+        for awarded in [4_000.0, 6_500.0] {
+            tally.record_award(&Award {
+                application_id: "A1".to_string(),
+                requested: 10_000.0,
+                awarded,
+                score: 80.0,
+                rationale: String::new(),
+            });
+        }
+
+        let stats = tally.stats(1, false, &[]);
+        assert_eq!(stats.funding.awarded, 6_500.0);
+        assert_eq!(stats.programs["P1"].funding.awarded, 6_500.0);
+    }
+
+    #[test]
+    fn reopening_a_store_times_the_stored_applications_again() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("journal.jsonl");
+        let mut processor = GrantProgramProcessor::with_store(
+            false,
+            Some(capped_program()),
+            Box::new(JournalStore::open(&path).unwrap()),
+        )
+        .unwrap();
+        for (id, amount) in [("A1", 10_000.0), ("A2", 30_000.0)] {
+            processor
+                .process_application(&application(id, amount))
+                .unwrap();
+        }
+        let before = processor.stats();
+        drop(processor);
+
+        let reopened = GrantProgramProcessor::with_store(
+            false,
+            Some(capped_program()),
+            Box::new(JournalStore::open(&path).unwrap()),
+        )
+        .unwrap()
+        .stats();
+        assert_eq!(reopened.latency.samples, 2);
+        assert_eq!(
+            ProcessorStats {
+                latency: reopened.latency.clone(),
+                ..before
+            },
+            reopened
+        );
+    }
+}
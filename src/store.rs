@@ -0,0 +1,618 @@
+// src/store.rs
+/*
+ * Persistent storage of applications, status changes, reviews and awards
+ */
+
+use crate::allocator::Award;
+use crate::review::Review;
+use crate::{GrantApplication, GrantError, Lifecycle, Result, StatusChange};
+use log::warn;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// Where a processor keeps what it has learned between runs
+pub trait Store: fmt::Debug + Send {
+    /// Save an application, replacing any earlier version with the same id
+    fn save_application(&mut self, application: &GrantApplication) -> Result<()>;
+
+    /// Look up an application by id
+    fn application(&self, id: &str) -> Result<Option<GrantApplication>>;
+
+    /// Every stored application, ordered by id
+    fn applications(&self) -> Result<Vec<GrantApplication>>;
+
+    /// Append a status change to an application's lifecycle
+    fn record_transition(&mut self, application_id: &str, change: &StatusChange) -> Result<()>;
+
+    /// Lifecycle of every application with recorded status changes, ordered by id
+    fn lifecycles(&self) -> Result<Vec<Lifecycle>>;
+
+    /// Save a review, replacing an earlier one by the same reviewer for the same application
+    fn save_review(&mut self, review: &Review) -> Result<()>;
+
+    /// Every stored review
+    fn reviews(&self) -> Result<Vec<Review>>;
+
+    /// Save an award, replacing any earlier award to the same application
+    fn save_award(&mut self, award: &Award) -> Result<()>;
+
+    /// Every stored award, ordered by application id
+    fn awards(&self) -> Result<Vec<Award>>;
+
+    /// Number of applications processed over the store's lifetime
+    fn processed_count(&self) -> Result<usize>;
+
+    /// Update the number of applications processed
+    fn set_processed_count(&mut self, count: usize) -> Result<()>;
+}
+
+/// One change written to a store journal
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum StoreEvent {
+    /// An application was saved
+    Application {
+        /// The saved application
+        application: Box<GrantApplication>,
+    },
+    /// An application changed status
+    Transition {
+        /// Application concerned
+        application_id: String,
+        /// The recorded change
+        change: StatusChange,
+    },
+    /// A review was saved
+    Review {
+        /// The saved review
+        review: Review,
+    },
+    /// An award was saved
+    Award {
+        /// The saved award
+        award: Award,
+    },
+    /// The processed count changed
+    ProcessedCount {
+        /// New count
+        count: usize,
+    },
+}
+
+/// Store that keeps everything in memory; nothing survives the process
+#[derive(Debug, Clone, Default)]
+pub struct MemoryStore {
+    applications: BTreeMap<String, GrantApplication>,
+    lifecycles: BTreeMap<String, Lifecycle>,
+    reviews: Vec<Review>,
+    awards: BTreeMap<String, Award>,
+    processed_count: usize,
+}
+
+impl MemoryStore {
+    /// Create an empty store
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Apply a journal event to the in-memory state
+    pub fn apply(&mut self, event: StoreEvent) {
+        match event {
+            StoreEvent::Application { application } => {
+                self.applications
+                    .insert(application.id.clone(), *application);
+            }
+            StoreEvent::Transition {
+                application_id,
+                change,
+            } => {
+                let lifecycle = self
+                    .lifecycles
+                    .entry(application_id.clone())
+                    .or_insert_with(|| Lifecycle {
+                        application_id,
+                        status: change.to,
+                        history: Vec::new(),
+                    });
+                lifecycle.status = change.to;
+                lifecycle.history.push(change);
+            }
+            StoreEvent::Review { review } => {
+                match self.reviews.iter_mut().find(|existing| {
+                    existing.application_id == review.application_id
+                        && existing.reviewer_id == review.reviewer_id
+                }) {
+                    Some(existing) => *existing = review,
+                    None => self.reviews.push(review),
+                }
+            }
+            StoreEvent::Award { award } => {
+                self.awards.insert(award.application_id.clone(), award);
+            }
+            StoreEvent::ProcessedCount { count } => self.processed_count = count,
+        }
+    }
+}
+
+impl Store for MemoryStore {
+    fn save_application(&mut self, application: &GrantApplication) -> Result<()> {
+        self.apply(StoreEvent::Application {
+            application: Box::new(application.clone()),
+        });
+        Ok(())
+    }
+
+    fn application(&self, id: &str) -> Result<Option<GrantApplication>> {
+        Ok(self.applications.get(id).cloned())
+    }
+
+    fn applications(&self) -> Result<Vec<GrantApplication>> {
+        Ok(self.applications.values().cloned().collect())
+    }
+
+    fn record_transition(&mut self, application_id: &str, change: &StatusChange) -> Result<()> {
+        self.apply(StoreEvent::Transition {
+            application_id: application_id.to_string(),
+            change: change.clone(),
+        });
+        Ok(())
+    }
+
+    fn lifecycles(&self) -> Result<Vec<Lifecycle>> {
+        Ok(self.lifecycles.values().cloned().collect())
+    }
+
+    fn save_review(&mut self, review: &Review) -> Result<()> {
+        self.apply(StoreEvent::Review {
+            review: review.clone(),
+        });
+        Ok(())
+    }
+
+    fn reviews(&self) -> Result<Vec<Review>> {
+        Ok(self.reviews.clone())
+    }
+
+    fn save_award(&mut self, award: &Award) -> Result<()> {
+        self.apply(StoreEvent::Award {
+            award: award.clone(),
+        });
+        Ok(())
+    }
+
+    fn awards(&self) -> Result<Vec<Award>> {
+        Ok(self.awards.values().cloned().collect())
+    }
+
+    fn processed_count(&self) -> Result<usize> {
+        Ok(self.processed_count)
+    }
+
+    fn set_processed_count(&mut self, count: usize) -> Result<()> {
+        self.apply(StoreEvent::ProcessedCount { count });
+        Ok(())
+    }
+}
+
+/// Store backed by an append-only JSON-lines journal file
+///
+/// Every change is appended to the journal as one `StoreEvent` line and
+/// flushed before the call returns. Opening the store replays the journal
+/// into memory, so reads never touch the file.
+#[derive(Debug)]
+pub struct JournalStore {
+    path: PathBuf,
+    file: File,
+    state: MemoryStore,
+}
+
+impl JournalStore {
+    /// Open a journal, creating it if it does not exist yet
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path of the journal file
+    ///
+    /// # Returns
+    ///
+    /// The store with the journal replayed, or a parse error naming the
+    /// first line that cannot be read. An unreadable last line without a
+    /// trailing newline is what a crash mid-append leaves behind; it is
+    /// dropped from the journal with a warning instead.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let mut state = MemoryStore::new();
+        let mut complete_len = None;
+        let mut unterminated = false;
+
+        match fs::read_to_string(&path) {
+            Ok(contents) => {
+                let (complete, last) = match contents.rfind('\n') {
+                    Some(end) => contents.split_at(end + 1),
+                    None => ("", contents.as_str()),
+                };
+                for (number, line) in complete.lines().enumerate() {
+                    if line.trim().is_empty() {
+                        continue;
+                    }
+                    let event: StoreEvent = serde_json::from_str(line)
+                        .map_err(|e| replay_error(&path, number + 1, e))?;
+                    state.apply(event);
+                }
+                if !last.trim().is_empty() {
+                    let number = complete.lines().count() + 1;
+                    match serde_json::from_str::<StoreEvent>(last) {
+                        Ok(event) => {
+                            state.apply(event);
+                            unterminated = true;
+                        }
+                        Err(e) => {
+                            warn!(
+                                "Dropping incomplete line {} at the end of {}: {}",
+                                number,
+                                path.display(),
+                                e
+                            );
+                            complete_len = Some(complete.len() as u64);
+                        }
+                    }
+                }
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(GrantError::io(&path, e)),
+        }
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .map_err(|e| GrantError::io(&path, e))?;
+        // Later appends must start on a line of their own
+        if let Some(len) = complete_len {
+            file.set_len(len).map_err(|e| GrantError::io(&path, e))?;
+        }
+        if unterminated {
+            file.write_all(b"\n")
+                .map_err(|e| GrantError::io(&path, e))?;
+        }
+        Ok(Self { path, file, state })
+    }
+
+    /// Path of the journal file
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn append(&mut self, event: StoreEvent) -> Result<()> {
+        let mut line = serde_json::to_string(&event).map_err(GrantError::output)?;
+        line.push('\n');
+        self.file
+            .write_all(line.as_bytes())
+            .and_then(|()| self.file.flush())
+            .map_err(|e| GrantError::io(&self.path, e))?;
+        self.state.apply(event);
+        Ok(())
+    }
+}
+
+impl Store for JournalStore {
+    fn save_application(&mut self, application: &GrantApplication) -> Result<()> {
+        self.append(StoreEvent::Application {
+            application: Box::new(application.clone()),
+        })
+    }
+
+    fn application(&self, id: &str) -> Result<Option<GrantApplication>> {
+        self.state.application(id)
+    }
+
+    fn applications(&self) -> Result<Vec<GrantApplication>> {
+        self.state.applications()
+    }
+
+    fn record_transition(&mut self, application_id: &str, change: &StatusChange) -> Result<()> {
+        self.append(StoreEvent::Transition {
+            application_id: application_id.to_string(),
+            change: change.clone(),
+        })
+    }
+
+    fn lifecycles(&self) -> Result<Vec<Lifecycle>> {
+        self.state.lifecycles()
+    }
+
+    fn save_review(&mut self, review: &Review) -> Result<()> {
+        self.append(StoreEvent::Review {
+            review: review.clone(),
+        })
+    }
+
+    fn reviews(&self) -> Result<Vec<Review>> {
+        self.state.reviews()
+    }
+
+    fn save_award(&mut self, award: &Award) -> Result<()> {
+        self.append(StoreEvent::Award {
+            award: award.clone(),
+        })
+    }
+
+    fn awards(&self) -> Result<Vec<Award>> {
+        self.state.awards()
+    }
+
+    fn processed_count(&self) -> Result<usize> {
+        self.state.processed_count()
+    }
+
+    fn set_processed_count(&mut self, count: usize) -> Result<()> {
+        self.append(StoreEvent::ProcessedCount { count })
+    }
+}
+
+/// Parse error for a journal line that cannot be replayed
+fn replay_error(path: &Path, line: usize, error: serde_json::Error) -> GrantError {
+    GrantError::Parse {
+        path: Some(path.to_path_buf()),
+        record: None,
+        line: Some(line),
+        column: Some(error.column()).filter(|&column| column > 0),
+        message: error.to_string(),
+        source: Some(Box::new(error)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::application;
+    use crate::ApplicationStatus;
+    use chrono::Utc;
+
+    fn change(from: Option<ApplicationStatus>, to: ApplicationStatus) -> StatusChange {
+        StatusChange {
+            from,
+            to,
+            at: Utc::now(),
+            reason: String::new(),
+        }
+    }
+
+    fn review(application_id: &str, reviewer_id: &str, score: f64) -> Review {
+        Review {
+            application_id: application_id.to_string(),
+            reviewer_id: reviewer_id.to_string(),
+            score: Some(score),
+            ratings: BTreeMap::new(),
+            comment: String::new(),
+            submitted_at: None,
+        }
+    }
+
+    fn award(application_id: &str, awarded: f64) -> Award {
+        Award {
+            application_id: application_id.to_string(),
+            requested: 10_000.0,
+            awarded,
+            score: 71.25,
+            rationale: "ranked first".to_string(),
+        }
+    }
+
+    #[test]
+    fn reopening_a_journal_replays_every_event() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("journal.jsonl");
+
+        let mut store = JournalStore::open(&path).unwrap();
+        store.save_application(&application("A", 10_000.0)).unwrap();
+        store.save_application(&application("B", 2_500.5)).unwrap();
+        store
+            .record_transition("A", &change(None, ApplicationStatus::Draft))
+            .unwrap();
+        store
+            .record_transition(
+                "A",
+                &change(Some(ApplicationStatus::Draft), ApplicationStatus::Submitted),
+            )
+            .unwrap();
+        store.save_review(&review("A", "R1", 80.0)).unwrap();
+        store.save_award(&award("A", 100_000.0 / 3.0)).unwrap();
+        store.set_processed_count(2).unwrap();
+        let expected = (
+            store.applications().unwrap(),
+            store.lifecycles().unwrap(),
+            store.reviews().unwrap(),
+            store.awards().unwrap(),
+        );
+        drop(store);
+
+        let reopened = JournalStore::open(&path).unwrap();
+        assert_eq!(reopened.applications().unwrap(), expected.0);
+        assert_eq!(reopened.lifecycles().unwrap(), expected.1);
+        assert_eq!(reopened.reviews().unwrap(), expected.2);
+        assert_eq!(reopened.awards().unwrap(), expected.3);
+        assert_eq!(reopened.processed_count().unwrap(), 2);
+
+        let lifecycle = &reopened.lifecycles().unwrap()[0];
+        assert_eq!(lifecycle.application_id, "A");
+        assert_eq!(lifecycle.status, ApplicationStatus::Submitted);
+        assert_eq!(lifecycle.history.len(), 2);
+        assert_eq!(
+            reopened.application("B").unwrap().unwrap().funding.amount,
+            2_500.5
+        );
+    }
+
+    #[test]
+    fn replay_keeps_the_latest_review_award_and_application() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("journal.jsonl");
+
+        let mut store = JournalStore::open(&path).unwrap();
+        store.save_application(&application("A", 1_000.0)).unwrap();
+        store.save_application(&application("A", 4_000.0)).unwrap();
+        store.save_review(&review("A", "R1", 40.0)).unwrap();
+        store.save_review(&review("A", "R2", 60.0)).unwrap();
+        store.save_review(&review("A", "R1", 90.0)).unwrap();
+        store.save_award(&award("A", 5_000.0)).unwrap();
+        store.save_award(&award("A", 7_500.0)).unwrap();
+        store.set_processed_count(1).unwrap();
+        store.set_processed_count(3).unwrap();
+        drop(store);
+
+        let reopened = JournalStore::open(&path).unwrap();
+        let applications = reopened.applications().unwrap();
+        assert_eq!(applications.len(), 1);
+        assert_eq!(applications[0].funding.amount, 4_000.0);
+        let scores: Vec<(String, Option<f64>)> = reopened
+            .reviews()
+            .unwrap()
+            .into_iter()
+            .map(|review| (review.reviewer_id, review.score))
+            .collect();
+        assert_eq!(
+            scores,
+            [
+                ("R1".to_string(), Some(90.0)),
+                ("R2".to_string(), Some(60.0))
+            ]
+        );
+        let awards = reopened.awards().unwrap();
+        assert_eq!(awards.len(), 1);
+        assert_eq!(awards[0].awarded, 7_500.0);
+        assert_eq!(reopened.processed_count().unwrap(), 3);
+    }
+
+    #[test]
+    fn writes_after_reopening_append_to_the_journal() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("journal.jsonl");
+
+        let mut store = JournalStore::open(&path).unwrap();
+        store.save_application(&application("A", 1_000.0)).unwrap();
+        drop(store);
+        let mut store = JournalStore::open(&path).unwrap();
+        store.save_application(&application("B", 2_000.0)).unwrap();
+        drop(store);
+
+        let ids: Vec<String> = JournalStore::open(&path)
+            .unwrap()
+            .applications()
+            .unwrap()
+            .into_iter()
+            .map(|application| application.id)
+            .collect();
+        assert_eq!(ids, ["A", "B"]);
+        assert_eq!(fs::read_to_string(&path).unwrap().lines().count(), 2);
+    }
+
+    #[test]
+    fn a_missing_journal_opens_empty_and_is_created() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("journal.jsonl");
+
+        let store = JournalStore::open(&path).unwrap();
+        assert!(store.applications().unwrap().is_empty());
+        assert!(store.lifecycles().unwrap().is_empty());
+        assert_eq!(store.processed_count().unwrap(), 0);
+        assert_eq!(store.path(), path);
+        assert!(path.exists());
+    }
+
+    #[test]
+    fn blank_lines_are_skipped() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("journal.jsonl");
+        fs::write(
+            &path,
+            "\n{\"event\":\"processed_count\",\"count\":4}\n   \n\n",
+        )
+        .unwrap();
+
+        let store = JournalStore::open(&path).unwrap();
+        assert_eq!(store.processed_count().unwrap(), 4);
+    }
+
+    #[test]
+    fn a_corrupt_line_is_reported_with_its_line_number() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("journal.jsonl");
+        fs::write(
+            &path,
+            "{\"event\":\"processed_count\",\"count\":1}\n\n{\"event\":\"processed_count\",\"count\":}\n",
+        )
+        .unwrap();
+
+        match JournalStore::open(&path).unwrap_err() {
+            GrantError::Parse {
+                path: error_path,
+                line,
+                column,
+                ..
+            } => {
+                assert_eq!(error_path.as_deref(), Some(path.as_path()));
+                assert_eq!(line, Some(3));
+                assert!(column.is_some());
+            }
+            other => panic!("expected a parse error, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn an_unknown_event_is_a_parse_error() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("journal.jsonl");
+        fs::write(&path, "{\"event\":\"renamed\",\"count\":1}\n").unwrap();
+
+        let error = JournalStore::open(&path).unwrap_err();
+        assert!(matches!(error, GrantError::Parse { line: Some(1), .. }));
+    }
+
+    #[test]
+    fn an_incomplete_last_line_is_dropped() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("journal.jsonl");
+        let complete = "{\"event\":\"processed_count\",\"count\":2}\n";
+        fs::write(&path, format!("{complete}{{\"event\":\"processed_co")).unwrap();
+
+        let mut store = JournalStore::open(&path).unwrap();
+        assert_eq!(store.processed_count().unwrap(), 2);
+        assert_eq!(fs::read_to_string(&path).unwrap(), complete);
+
+        store.set_processed_count(3).unwrap();
+        drop(store);
+        let store = JournalStore::open(&path).unwrap();
+        assert_eq!(store.processed_count().unwrap(), 3);
+    }
+
+    #[test]
+    fn a_complete_last_line_without_a_newline_is_kept() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("journal.jsonl");
+        fs::write(&path, "{\"event\":\"processed_count\",\"count\":2}").unwrap();
+
+        let mut store = JournalStore::open(&path).unwrap();
+        assert_eq!(store.processed_count().unwrap(), 2);
+        store.set_processed_count(5).unwrap();
+        drop(store);
+        let store = JournalStore::open(&path).unwrap();
+        assert_eq!(store.processed_count().unwrap(), 5);
+    }
+
+    #[test]
+    fn a_corrupt_line_before_the_last_is_still_an_error() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("journal.jsonl");
+        let contents = "{\"event\":\"processed_co\n{\"event\":\"processed_count\",\"count\":2}";
+        fs::write(&path, contents).unwrap();
+
+        let error = JournalStore::open(&path).unwrap_err();
+        assert!(matches!(error, GrantError::Parse { line: Some(1), .. }));
+        assert_eq!(fs::read_to_string(&path).unwrap(), contents);
+    }
+}
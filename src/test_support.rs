@@ -0,0 +1,48 @@
+// src/test_support.rs
+/*
+ * Fixtures shared by the unit tests
+ */
+
+use crate::{GrantApplication, ProgramDefinition};
+
+/// A well-formed application from a US nonprofit asking for `amount`
+pub(crate) fn application(id: &str, amount: f64) -> GrantApplication {
+    serde_json::from_value(serde_json::json!({
+        "id": id,
+        "title": "River cleanup",
+        "applicant": {
+            "id": "A1",
+            "name": "Green Org",
+            "org_type": "nonprofit",
+            "email": "x@green.org",
+            "country": "US",
+            "institution": "Green"
+        },
+        "program": { "id": "P1" },
+        "funding": {
+            "amount": amount,
+            "duration_months": 12,
+            "budget": [{ "category": "personnel", "amount": amount }]
+        },
+        "documents": ["proposal", "budget"],
+        "keywords": ["water", "ecology"],
+        "ratings": { "impact": 4.0, "feasibility": 3.0 }
+    }))
+    .expect("fixture application is valid")
+}
+
+/// Program `P1` with the given pool, award limits of 1000..=50000 and no window
+pub(crate) fn program(total_pool: f64) -> ProgramDefinition {
+    ProgramDefinition {
+        id: "P1".to_string(),
+        name: "Clean Water".to_string(),
+        window: None,
+        total_pool,
+        min_award: 1000.0,
+        max_award: 50000.0,
+        eligible_applicant_types: Vec::new(),
+        required_documents: vec!["proposal".to_string()],
+        rules: Vec::new(),
+        rubric: None,
+    }
+}